@@ -45,3 +45,96 @@ impl TryFrom<&str> for KeyType {
         })
     }
 }
+
+#[derive(Type, Debug, Ord, PartialOrd, Eq, PartialEq, Deserialize, Serialize)]
+#[sqlx(type_name = "repo_visibility", rename_all = "lowercase")]
+#[serde(rename_all(serialize = "lowercase", deserialize = "lowercase"))]
+pub enum RepoVisibility {
+    Public,
+    Internal,
+    Private,
+}
+
+impl Display for RepoVisibility {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use RepoVisibility::*;
+
+        f.write_str(match self {
+            Public => "Public",
+            Internal => "Internal",
+            Private => "Private",
+        })
+    }
+}
+
+#[derive(Type, Debug, Ord, PartialOrd, Eq, PartialEq, Deserialize, Serialize)]
+#[sqlx(type_name = "access_level", rename_all = "lowercase")]
+#[serde(rename_all(serialize = "lowercase", deserialize = "lowercase"))]
+pub enum AccessLevel {
+    Viewer,
+    Supporter,
+    Coder,
+    Manager,
+    Admin,
+}
+
+// Currently all these methods are hard coded but in the future they will be configurable on a per repo/org basis
+impl AccessLevel {
+    /// All access levels, from least to most privileged
+    pub const ALL: [AccessLevel; 5] = [
+        AccessLevel::Viewer,
+        AccessLevel::Supporter,
+        AccessLevel::Coder,
+        AccessLevel::Manager,
+        AccessLevel::Admin,
+    ];
+
+    /// Name of the access level as stored in the database
+    pub fn as_str(&self) -> &'static str {
+        use AccessLevel::*;
+
+        match self {
+            Viewer => "viewer",
+            Supporter => "supporter",
+            Coder => "coder",
+            Manager => "manager",
+            Admin => "admin",
+        }
+    }
+
+    pub fn can_view(&self) -> bool {
+        true
+    }
+
+    pub fn can_manage_issues(&self) -> bool {
+        match self {
+            AccessLevel::Viewer | AccessLevel::Coder => false,
+            AccessLevel::Supporter | AccessLevel::Manager | AccessLevel::Admin => true,
+        }
+    }
+
+    pub fn can_push(&self) -> bool {
+        match self {
+            AccessLevel::Viewer | AccessLevel::Supporter => false,
+            AccessLevel::Coder | AccessLevel::Manager | AccessLevel::Admin => true,
+        }
+    }
+
+    pub fn can_admin(&self) -> bool {
+        matches!(self, AccessLevel::Admin)
+    }
+}
+
+impl Display for AccessLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use AccessLevel::*;
+
+        f.write_str(match self {
+            Viewer => "Viewer",
+            Supporter => "Supporter",
+            Coder => "Coder",
+            Manager => "Manager",
+            Admin => "Admin",
+        })
+    }
+}
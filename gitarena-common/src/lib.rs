@@ -3,3 +3,4 @@ pub mod ipc;
 pub mod log;
 pub mod packets;
 pub mod prelude;
+pub mod privileges;
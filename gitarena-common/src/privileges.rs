@@ -0,0 +1,72 @@
+use crate::database::models::{AccessLevel, RepoVisibility};
+use crate::database::Database;
+
+use anyhow::{Context, Result};
+use sqlx::Executor;
+
+/// Fields of a repository its access rules depend on
+pub struct RepoAccess<'a> {
+    pub id: i32,
+    pub owner: i32,
+    pub visibility: &'a RepoVisibility,
+    pub disabled: bool,
+}
+
+/// Fields of the user requesting access to a repository
+pub struct UserAccess {
+    pub id: i32,
+    pub admin: bool,
+}
+
+/// Returns whenever `user` (or an anonymous visitor if `None`) is allowed to read `repo`.
+/// This is the single source of truth for repository read access, shared by the web server and the SSH component.
+pub async fn check_access<'e, E: Executor<'e, Database = Database>>(
+    repo: &RepoAccess<'_>,
+    user: Option<&UserAccess>,
+    executor: E,
+) -> Result<bool> {
+    if repo.disabled {
+        return Ok(user.map_or_else(|| false, |user| user.admin));
+    }
+
+    Ok(match repo.visibility {
+        RepoVisibility::Private => {
+            if let Some(user) = user {
+                if user.id != repo.owner && !user.admin {
+                    access_level(repo.id, user.id, executor)
+                        .await
+                        .with_context(|| {
+                            format!(
+                                "Unable to get repo privileges for user {} in repo {}",
+                                &user.id, &repo.id
+                            )
+                        })?
+                        .map_or_else(|| false, |access_level| access_level.can_view())
+                } else {
+                    true
+                }
+            } else {
+                false
+            }
+        }
+        RepoVisibility::Internal => user.is_some(),
+        RepoVisibility::Public => true,
+    })
+}
+
+/// Returns the access level `user_id` has been granted in `repo_id`, if any
+pub async fn access_level<'e, E: Executor<'e, Database = Database>>(
+    repo_id: i32,
+    user_id: i32,
+    executor: E,
+) -> Result<Option<AccessLevel>> {
+    let option: Option<(AccessLevel,)> = sqlx::query_as(
+        "select access_level from privileges where user_id = $1 and repo_id = $2 limit 1",
+    )
+    .bind(user_id)
+    .bind(repo_id)
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(option.map(|(access_level,)| access_level))
+}
@@ -1,3 +1,5 @@
+use std::env;
+
 use anyhow::Result;
 use futures::TryStreamExt;
 use gitarena_common::database::models::KeyType;
@@ -5,17 +7,30 @@ use gitarena_common::database::Database;
 use gitarena_common::prelude::*;
 use sqlx::{Executor, Row};
 
+/// Every key is restricted to running this executable with the username of the key owner,
+/// which then decides what the key is allowed to do based on `SSH_ORIGINAL_COMMAND`
 pub(crate) async fn print_all<'e, E: Executor<'e, Database = Database>>(executor: E) -> Result<()> {
+    let executable = env::current_exe()?;
+
     let mut stream = sqlx::query(
-        "select algorithm, key from ssh_keys where expires_at is null or expires_at < now()",
+        "select ssh_keys.algorithm, ssh_keys.key, users.username from ssh_keys \
+        inner join users on users.id = ssh_keys.owner \
+        where users.disabled = false and (ssh_keys.expires_at is null or ssh_keys.expires_at < now())",
     )
     .fetch(executor);
 
     while let Some(row) = stream.try_next().await? {
         let algorithm: KeyType = row.try_get("algorithm")?;
         let key: &[u8] = row.try_get("key")?;
+        let username: &str = row.try_get("username")?;
 
-        println!("{} {}", algorithm, base64::encode(key));
+        println!(
+            "command=\"{} --user={}\",no-port-forwarding,no-X11-forwarding,no-agent-forwarding,no-pty {} {}",
+            executable.display(),
+            username,
+            algorithm,
+            base64::encode(key)
+        );
     }
 
     Ok(())
@@ -4,6 +4,7 @@ use gitarena_common::database::create_postgres_pool;
 use gitarena_common::prelude::*;
 
 mod keys;
+mod shell;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -17,7 +18,10 @@ async fn main() -> Result<()> {
 
     match &args.command {
         Some(AuthorizedKeys) => keys::print_all(&mut transaction).await?,
-        _ => bail!("GitArena does currently not provide SSH access"),
+        None => match &args.user {
+            Some(user) => shell::run(user.as_str(), &mut transaction).await?,
+            None => bail!("GitArena does currently not provide SSH access"),
+        },
     }
 
     transaction.commit().await?;
@@ -39,6 +43,9 @@ enum Command {
     long_about = "SSH component for GitArena: a software development platform with built-in vcs, issue tracking and code review"
 )]
 struct Args {
+    /// Serves the Git command requested via `SSH_ORIGINAL_COMMAND` as this user.
+    /// Set by the `command` option in the lines printed by `authorized-keys`
+    #[clap(long)]
     user: Option<String>,
 
     #[clap(subcommand)]
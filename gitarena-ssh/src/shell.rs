@@ -0,0 +1,121 @@
+use std::env;
+use std::process::Stdio;
+
+use anyhow::{bail, Context, Result};
+use gitarena_common::database::models::RepoVisibility;
+use gitarena_common::database::Database;
+use gitarena_common::prelude::*;
+use gitarena_common::privileges::{self, RepoAccess, UserAccess};
+use sqlx::Transaction;
+use tokio::process::Command;
+
+/// Executes the Git service requested by the SSH client (`SSH_ORIGINAL_COMMAND`) on behalf of `username`
+pub(crate) async fn run(username: &str, transaction: &mut Transaction<'_, Database>) -> Result<()> {
+    let original_command = match env::var("SSH_ORIGINAL_COMMAND") {
+        Ok(command) => command,
+        Err(_) => bail!("GitArena does not provide interactive shell access"),
+    };
+
+    let (service, path) = original_command
+        .split_once(' ')
+        .context("Invalid command")?;
+
+    match service {
+        "git-upload-archive" => upload_archive(username, path, transaction).await,
+        _ => bail!("{} is currently not supported over SSH", service),
+    }
+}
+
+/// `git archive --remote`. Arguments are validated by `git upload-archive` itself, which only allows archiving reachable objects
+async fn upload_archive(
+    username: &str,
+    path: &str,
+    transaction: &mut Transaction<'_, Database>,
+) -> Result<()> {
+    let (owner, repo_name) = parse_repo_path(path).context("Invalid repository path")?;
+    let repo_dir = find_readable_repo(username, owner, repo_name, transaction).await?;
+
+    // stdin and stdout are inherited so Git talks directly to the SSH client
+    let status = Command::new("git")
+        .arg("upload-archive")
+        .arg(repo_dir.as_str())
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .status()
+        .await?;
+
+    if !status.success() {
+        bail!("git upload-archive exited with non-zero status: {}", status);
+    }
+
+    Ok(())
+}
+
+/// Parses the quoted path sent by Git clients, e.g. `'/username/repo.git'`
+fn parse_repo_path(path: &str) -> Option<(&str, &str)> {
+    let path = path.trim().trim_matches('\'').trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+
+    let (owner, repo_name) = path.split_once('/')?;
+
+    if owner.is_empty() || repo_name.is_empty() || repo_name.contains('/') {
+        return None;
+    }
+
+    Some((owner, repo_name))
+}
+
+/// Returns the path on disk of `owner/repo_name` if `username` is allowed to read it.
+/// Uses the same access rules as the web server, see [privileges::check_access].
+async fn find_readable_repo(
+    username: &str,
+    owner: &str,
+    repo_name: &str,
+    transaction: &mut Transaction<'_, Database>,
+) -> Result<String> {
+    let (user_id, admin): (i32, bool) = sqlx::query_as(
+        "select id, admin from users where lower(username) = lower($1) and disabled = false limit 1",
+    )
+    .bind(username)
+    .fetch_optional(&mut *transaction)
+    .await?
+    .context("User not found")?;
+
+    let option: Option<(i32, i32, RepoVisibility, bool, String, String)> = sqlx::query_as(
+        "select repositories.id, repositories.owner, repositories.visibility, repositories.disabled, \
+        owners.username, repositories.name from repositories \
+        inner join users owners on owners.id = repositories.owner \
+        where lower(owners.username) = lower($1) and lower(repositories.name) = lower($2) \
+        limit 1",
+    )
+    .bind(owner)
+    .bind(repo_name)
+    .fetch_optional(&mut *transaction)
+    .await?;
+
+    // Do not leak whenever the repository exists or the user is lacking permissions
+    let (repo_id, repo_owner, visibility, disabled, owner, repo_name) = match option {
+        Some(tuple) => tuple,
+        None => bail!("Repository not found"),
+    };
+
+    let repo = RepoAccess {
+        id: repo_id,
+        owner: repo_owner,
+        visibility: &visibility,
+        disabled,
+    };
+    let user = UserAccess { id: user_id, admin };
+
+    if !privileges::check_access(&repo, Some(&user), &mut *transaction).await? {
+        bail!("Repository not found");
+    }
+
+    let (base_dir,): (String,) =
+        sqlx::query_as("select value from settings where key = 'repositories.base_dir' limit 1")
+            .fetch_one(&mut *transaction)
+            .await?;
+
+    Ok(format!("{}/{}/{}", base_dir, owner, repo_name))
+}
@@ -0,0 +1,35 @@
+-- Code search
+
+create extension if not exists pg_trgm;
+
+create table if not exists code_search_files
+(
+    id       serial
+        constraint code_search_files_pk
+            primary key,
+    repo     integer      not null
+        constraint code_search_files_repositories_id_fk
+            references repositories
+            on delete cascade,
+    branch   varchar(256) not null,
+    path     text         not null,
+    blob     varchar(64)  not null,
+    language varchar(64) default NULL::character varying,
+    content  text         not null
+);
+
+comment on table code_search_files is 'Contains the content of all text files in indexed branches, used for code search';
+
+create unique index if not exists code_search_files_repo_branch_path_uindex
+    on code_search_files (repo, branch, path);
+
+-- Trigram indices allow Postgres to answer `like`, `ilike`, `~` and `~*` queries without scanning every file
+create index if not exists code_search_files_content_trgm_index
+    on code_search_files using gin (content gin_trgm_ops);
+
+create index if not exists code_search_files_path_trgm_index
+    on code_search_files using gin (path gin_trgm_ops);
+
+insert into settings (key, value, type) values ('search.enabled', true, 'boolean') on conflict do nothing;
+insert into settings (key, value, type) values ('search.all_branches', false, 'boolean') on conflict do nothing;
+insert into settings (key, value, type) values ('search.max_file_size', 1048576, 'int') on conflict do nothing;
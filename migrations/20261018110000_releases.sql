@@ -0,0 +1,55 @@
+-- Releases
+
+create table if not exists releases
+(
+    id           serial
+        constraint releases_pk
+            primary key,
+    repo         integer                                            not null
+        constraint releases_repositories_id_fk
+            references repositories
+            on delete cascade,
+    tag          varchar(256)                                       not null,
+    title        varchar(256)                                       not null,
+    notes        text                     default ''::text          not null,
+    author       integer                                            not null
+        constraint releases_users_id_fk
+            references users
+            on delete cascade,
+    draft        boolean                  default false             not null,
+    prerelease   boolean                  default false             not null,
+    created_at   timestamp with time zone default current_timestamp not null,
+    published_at timestamp with time zone
+);
+
+create unique index if not exists releases_repo_tag_uindex
+    on releases (repo, tag);
+
+create table if not exists release_assets
+(
+    id           serial
+        constraint release_assets_pk
+            primary key,
+    release      integer                                            not null
+        constraint release_assets_releases_id_fk
+            references releases
+            on delete cascade,
+    name         varchar(256)                                       not null,
+    content_type varchar(256)                                       not null,
+    size         bigint                                             not null,
+    sha256       varchar(64)                                        not null,
+    downloads    bigint                   default 0                 not null,
+    uploader     integer                                            not null
+        constraint release_assets_users_id_fk
+            references users
+            on delete cascade,
+    created_at   timestamp with time zone default current_timestamp not null
+);
+
+comment on column release_assets.sha256 is 'Hex encoded SHA-256 checksum of the asset, computed while uploading';
+
+create unique index if not exists release_assets_release_name_uindex
+    on release_assets (release, name);
+
+insert into settings (key, value, type) values ('releases.dir', 'releases', 'string') on conflict do nothing;
+insert into settings (key, value, type) values ('releases.max_asset_size', 536870912, 'int') on conflict do nothing;
@@ -0,0 +1,3 @@
+-- Separate origin (e.g. https://raw.example.com) untrusted HTML and SVG files of public repositories are served from
+
+insert into settings (key, value, type) values ('raw.download_domain', null, 'string') on conflict do nothing;
@@ -0,0 +1,16 @@
+-- Language statistics of the default branch, updated after every push
+
+create table if not exists repository_languages
+(
+    repo     integer     not null
+        constraint repository_languages_repositories_id_fk
+            references repositories
+            on delete cascade,
+    language varchar(64) not null,
+    bytes    bigint      not null,
+    constraint repository_languages_pk
+        primary key (repo, language)
+);
+
+create index if not exists repository_languages_language_index
+    on repository_languages (language);
@@ -0,0 +1,14 @@
+-- Cache of the commit which last modified a path, as seen from a revision (commit id)
+
+create table if not exists last_commits
+(
+    repo        integer     not null
+        constraint last_commits_repositories_id_fk
+            references repositories
+            on delete cascade,
+    revision    varchar(40) not null,
+    path        text        not null,
+    last_commit varchar(40) not null,
+    constraint last_commits_pk
+        primary key (repo, revision, path)
+);
@@ -0,0 +1,68 @@
+-- Commit and tag signature verification
+
+alter table ssh_keys
+    add column if not exists signing boolean default false not null;
+
+alter table repositories
+    add column if not exists require_signed_commits boolean default false not null;
+
+-- GPG keys
+
+create table if not exists gpg_keys
+(
+    id          serial                                             not null
+        constraint gpg_keys_pk
+            primary key,
+    owner       integer                                            not null
+        constraint gpg_keys_users_id_fk
+            references users
+            on delete cascade,
+    key_id      varchar(16)                                        not null,
+    fingerprint varchar(40)                                        not null,
+    subkey_ids  varchar(16)[]            default ARRAY []::varchar[] not null,
+    emails      varchar(256)[]           default ARRAY []::varchar[] not null,
+    key         text                                               not null,
+    created_at  timestamp with time zone default current_timestamp not null,
+    expires_at  timestamp with time zone
+);
+
+comment on column gpg_keys.emails is 'Emails of the user ids of the key; Signatures are only verified for emails which are also verified for the owner';
+
+create unique index if not exists gpg_keys_fingerprint_uindex
+    on gpg_keys (fingerprint);
+
+create index if not exists gpg_keys_owner_index
+    on gpg_keys (owner);
+
+-- Verification results
+
+-- https://stackoverflow.com/a/48382296/11494565
+do
+$$
+    begin
+        create type signature_status as enum ('verified', 'unverified', 'unknown_key');
+    exception
+        when duplicate_object then null;
+    end
+$$;
+
+create table if not exists signature_verifications
+(
+    repo     integer          not null
+        constraint signature_verifications_repositories_id_fk
+            references repositories
+            on delete cascade,
+    object   varchar(40)      not null,
+    status   signature_status not null,
+    kind     varchar(4)       not null,
+    key_id   varchar(64)      not null,
+    signer   integer
+        constraint signature_verifications_users_id_fk
+            references users
+            on delete set null,
+    reason   varchar(256),
+    constraint signature_verifications_pk
+        primary key (repo, object)
+);
+
+comment on table signature_verifications is 'Cache of signature verification results of commits and tags; Invalidated whenever keys or emails change';
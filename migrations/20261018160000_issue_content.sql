@@ -0,0 +1,36 @@
+-- Issue content
+
+alter table repositories add column if not exists issue_counter integer default 0 not null;
+
+comment on column repositories.issue_counter is 'Index of the most recently created issue, incremented (and thus row locked) when creating issues';
+
+update repositories
+set issue_counter = coalesce((select max(issues.index) from issues where issues.repo = repositories.id), 0);
+
+create unique index if not exists issues_repo_index_uindex
+    on issues (repo, index);
+
+create table if not exists issue_revisions
+(
+    id         serial
+        constraint issue_revisions_pk
+            primary key,
+    issue      integer                                            not null
+        constraint issue_revisions_issues_id_fk
+            references issues
+            on delete cascade,
+    author     integer
+        constraint issue_revisions_users_id_fk
+            references users
+            on delete set null,
+    title      varchar(256)                                       not null,
+    body       text                     default ''::text          not null,
+    created_at timestamp with time zone default current_timestamp not null
+);
+
+comment on table issue_revisions is 'Every edit of the title or body of an issue; The most recent revision is the current content';
+
+create index if not exists issue_revisions_issue_index
+    on issue_revisions (issue, id);
+
+comment on table issues is 'Contains issues and their corresponding data; The text content is stored in issue_revisions';
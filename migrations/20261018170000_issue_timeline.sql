@@ -0,0 +1,116 @@
+-- Issue comments
+
+create table if not exists issue_comments
+(
+    id         serial
+        constraint issue_comments_pk
+            primary key,
+    issue      integer                                            not null
+        constraint issue_comments_issues_id_fk
+            references issues
+            on delete cascade,
+    parent     integer
+        constraint issue_comments_issue_comments_id_fk
+            references issue_comments
+            on delete cascade,
+    author     integer
+        constraint issue_comments_users_id_fk
+            references users
+            on delete set null,
+    body       text                                               not null,
+    created_at timestamp with time zone default current_timestamp not null,
+    updated_at timestamp with time zone default current_timestamp not null,
+    deleted_at timestamp with time zone,
+    deleted_by integer
+        constraint issue_comments_users_id_fk_2
+            references users
+            on delete set null
+);
+
+comment on column issue_comments.parent is 'Top level comment this comment is a reply to; Replies can not be replied to themselves';
+comment on column issue_comments.deleted_at is 'Comments are soft deleted by moderators, their body is hidden but kept in issue_comment_revisions';
+
+create index if not exists issue_comments_issue_index
+    on issue_comments (issue);
+
+create table if not exists issue_comment_revisions
+(
+    id         serial
+        constraint issue_comment_revisions_pk
+            primary key,
+    comment    integer                                            not null
+        constraint issue_comment_revisions_issue_comments_id_fk
+            references issue_comments
+            on delete cascade,
+    author     integer
+        constraint issue_comment_revisions_users_id_fk
+            references users
+            on delete set null,
+    body       text                                               not null,
+    created_at timestamp with time zone default current_timestamp not null
+);
+
+create index if not exists issue_comment_revisions_comment_index
+    on issue_comment_revisions (comment, id);
+
+-- Issue events
+
+do
+$$
+    begin
+        create type issue_event_type as enum (
+            'opened',
+            'closed',
+            'reopened',
+            'labeled',
+            'unlabeled',
+            'assigned',
+            'unassigned',
+            'milestoned',
+            'demilestoned',
+            'referenced',
+            'locked',
+            'unlocked'
+        );
+    exception
+        when duplicate_object then null;
+    end
+$$;
+
+create table if not exists issue_events
+(
+    id          serial
+        constraint issue_events_pk
+            primary key,
+    issue       integer                                            not null
+        constraint issue_events_issues_id_fk
+            references issues
+            on delete cascade,
+    actor       integer
+        constraint issue_events_users_id_fk
+            references users
+            on delete set null,
+    event       issue_event_type                                   not null,
+    label       integer,
+    milestone   integer,
+    assignee    integer
+        constraint issue_events_users_id_fk_2
+            references users
+            on delete set null,
+    commit      varchar(40),
+    commit_repo integer
+        constraint issue_events_repositories_id_fk
+            references repositories
+            on delete cascade,
+    created_at  timestamp with time zone default current_timestamp not null
+);
+
+comment on column issue_events.commit_repo is 'Repository containing the referencing commit, may differ from the repository of the issue';
+
+create index if not exists issue_events_issue_index
+    on issue_events (issue);
+
+insert into issue_events (issue, actor, event, created_at)
+select id, author, 'opened', created_at
+from issues
+where not exists(select 1 from issue_events where issue_events.issue = issues.id);
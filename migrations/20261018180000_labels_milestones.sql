@@ -0,0 +1,131 @@
+-- Labels
+
+create table if not exists labels
+(
+    id          serial
+        constraint labels_pk
+            primary key,
+    repo        integer                                            not null
+        constraint labels_repositories_id_fk
+            references repositories
+            on delete cascade,
+    name        varchar(64)                                        not null,
+    color       varchar(7)                                         not null,
+    description varchar(256)             default ''::varchar       not null,
+    created_at  timestamp with time zone default current_timestamp not null
+);
+
+comment on column labels.name is 'Labels named scope::name are scoped, an issue can only have one label per scope';
+comment on column labels.color is 'Hex color including the leading #';
+
+create unique index if not exists labels_repo_name_uindex
+    on labels (repo, lower(name));
+
+-- Milestones
+
+create table if not exists milestones
+(
+    id          serial
+        constraint milestones_pk
+            primary key,
+    repo        integer                                            not null
+        constraint milestones_repositories_id_fk
+            references repositories
+            on delete cascade,
+    title       varchar(256)                                       not null,
+    description text                     default ''::text          not null,
+    due_date    date,
+    closed      boolean                  default false             not null,
+    created_at  timestamp with time zone default current_timestamp not null
+);
+
+create unique index if not exists milestones_repo_title_uindex
+    on milestones (repo, lower(title));
+
+-- Labels and milestones used to be ids referencing nothing. Every id in use gets a placeholder label (or milestone)
+-- in the repository it is used in, which can be renamed afterwards
+
+create temporary table label_migration as
+select distinct issues.repo, old_id
+from issues,
+     unnest(issues.labels) as old_id
+union
+select issues.repo, issue_events.label
+from issue_events
+         inner join issues on issues.id = issue_events.issue
+where issue_events.label is not null;
+
+alter table label_migration
+    add column new_id integer;
+
+update label_migration set new_id = nextval('labels_id_seq');
+
+insert into labels (id, repo, name, color)
+select new_id, repo, 'label-' || old_id, '#767676'
+from label_migration;
+
+update issues
+set labels = coalesce((select array_agg(label_migration.new_id order by old_labels.position)
+                       from unnest(issues.labels) with ordinality as old_labels(old_id, position)
+                                inner join label_migration on label_migration.repo = issues.repo
+                           and label_migration.old_id = old_labels.old_id), array []::integer[])
+where cardinality(labels) > 0;
+
+update issue_events
+set label = label_migration.new_id
+from issues,
+     label_migration
+where issues.id = issue_events.issue
+  and label_migration.repo = issues.repo
+  and label_migration.old_id = issue_events.label;
+
+create temporary table milestone_migration as
+select distinct repo, milestone as old_id
+from issues
+where milestone is not null
+union
+select issues.repo, issue_events.milestone
+from issue_events
+         inner join issues on issues.id = issue_events.issue
+where issue_events.milestone is not null;
+
+alter table milestone_migration
+    add column new_id integer;
+
+update milestone_migration set new_id = nextval('milestones_id_seq');
+
+insert into milestones (id, repo, title)
+select new_id, repo, 'Milestone ' || old_id
+from milestone_migration;
+
+update issues
+set milestone = milestone_migration.new_id
+from milestone_migration
+where milestone_migration.repo = issues.repo
+  and milestone_migration.old_id = issues.milestone;
+
+update issue_events
+set milestone = milestone_migration.new_id
+from issues,
+     milestone_migration
+where issues.id = issue_events.issue
+  and milestone_migration.repo = issues.repo
+  and milestone_migration.old_id = issue_events.milestone;
+
+drop table label_migration;
+drop table milestone_migration;
+
+alter table issues
+    add constraint issues_milestones_id_fk
+        foreign key (milestone) references milestones
+            on delete set null;
+
+alter table issue_events
+    add constraint issue_events_labels_id_fk
+        foreign key (label) references labels
+            on delete set null;
+
+alter table issue_events
+    add constraint issue_events_milestones_id_fk
+        foreign key (milestone) references milestones
+            on delete set null;
@@ -0,0 +1,23 @@
+-- Issue search
+
+alter table issues add column if not exists search_vector tsvector default ''::tsvector not null;
+
+comment on column issues.search_vector is 'Full text search index of the current title (weight A) and body (weight B), updated alongside issue_revisions';
+
+update issues
+set search_vector = setweight(to_tsvector('simple', issues.title), 'A') ||
+                    setweight(to_tsvector('simple', coalesce((select issue_revisions.body
+                                                              from issue_revisions
+                                                              where issue_revisions.issue = issues.id
+                                                              order by issue_revisions.id desc
+                                                              limit 1), '')), 'B');
+
+create index if not exists issues_search_vector_index
+    on issues using gin (search_vector);
+
+-- Keyset pagination of the issue list
+create index if not exists issues_repo_created_at_index
+    on issues (repo, created_at, id);
+
+create index if not exists issues_repo_updated_at_index
+    on issues (repo, updated_at, id);
@@ -0,0 +1,3 @@
+insert into settings (key, value, type) values ('mail.incoming.enabled', false, 'boolean') on conflict do nothing;
+insert into settings (key, value, type) values ('mail.incoming.maildir', null, 'string') on conflict do nothing;
+insert into settings (key, value, type) values ('mail.incoming.address', null, 'string') on conflict do nothing;
@@ -0,0 +1,74 @@
+-- Project boards
+
+do
+$$
+    begin
+        create type board_automation as enum ('none', 'opened', 'closed', 'reopened');
+    exception
+        when duplicate_object then null;
+    end
+$$;
+
+create table if not exists boards
+(
+    id          serial
+        constraint boards_pk
+            primary key,
+    repo        integer                                            not null
+        constraint boards_repositories_id_fk
+            references repositories
+            on delete cascade,
+    name        varchar(64)                                        not null,
+    description varchar(256)             default ''::varchar       not null,
+    created_at  timestamp with time zone default current_timestamp not null
+);
+
+create unique index if not exists boards_repo_name_uindex
+    on boards (repo, lower(name));
+
+create table if not exists board_columns
+(
+    id         serial
+        constraint board_columns_pk
+            primary key,
+    board      integer                                not null
+        constraint board_columns_boards_id_fk
+            references boards
+            on delete cascade,
+    name       varchar(64)                            not null,
+    position   integer                                not null,
+    label      integer
+        constraint board_columns_labels_id_fk
+            references labels
+            on delete set null,
+    automation board_automation default 'none'::board_automation not null
+);
+
+comment on column board_columns.label is 'Columns with a label collect all open issues with that label, moving an issue into the column adds the label';
+comment on column board_columns.automation is 'Issue event which moves the issue into this column';
+
+create index if not exists board_columns_board_index
+    on board_columns (board);
+
+create table if not exists board_cards
+(
+    id           serial
+        constraint board_cards_pk
+            primary key,
+    board        integer not null
+        constraint board_cards_boards_id_fk
+            references boards
+            on delete cascade,
+    board_column integer not null
+        constraint board_cards_board_columns_id_fk
+            references board_columns
+            on delete cascade,
+    issue        integer not null
+        constraint board_cards_issues_id_fk
+            references issues
+            on delete cascade,
+    position     integer not null
+);
+
+create unique index if not exists board_cards_board_issue_uindex
+    on board_cards (board, issue);
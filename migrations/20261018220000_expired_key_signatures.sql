@@ -0,0 +1,6 @@
+-- Good signatures of expired GPG keys
+
+alter type signature_status add value if not exists 'expired_key' after 'verified';
+
+-- These have been cached as unverified before, let them be verified again
+delete from signature_verifications where kind = 'gpg' and reason = 'Key expired';
@@ -0,0 +1,296 @@
+use crate::issue::{EventDetails, Issue, IssueEventType};
+use crate::label::exclusive_scope_ids;
+
+use anyhow::Result;
+use chrono::serde::ts_seconds;
+use chrono::{DateTime, Utc};
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use sqlx::{Executor, FromRow, Postgres, Transaction, Type};
+
+/// Kanban board over the issues of a repository.
+///
+/// Issues are placed on a board using [cards](BoardCard). Columns with a label additionally collect
+/// all open issues with that label which are not on the board yet.
+#[derive(FromRow, Display, Debug, Serialize)]
+#[display(fmt = "{}", name)]
+pub(crate) struct Board {
+    pub(crate) id: i32,
+    pub(crate) repo: i32,
+
+    pub(crate) name: String,
+    pub(crate) description: String,
+
+    #[serde(with = "ts_seconds")]
+    pub(crate) created_at: DateTime<Utc>,
+}
+
+impl Board {
+    pub(crate) async fn find<'e, E: Executor<'e, Database = Postgres>>(
+        repo_id: i32,
+        id: i32,
+        executor: E,
+    ) -> Result<Option<Board>> {
+        Ok(
+            sqlx::query_as::<_, Board>("select * from boards where repo = $1 and id = $2 limit 1")
+                .bind(repo_id)
+                .bind(id)
+                .fetch_optional(executor)
+                .await?,
+        )
+    }
+
+    /// Returns all boards of a repository, ordered by name
+    pub(crate) async fn all<'e, E: Executor<'e, Database = Postgres>>(
+        repo_id: i32,
+        executor: E,
+    ) -> Result<Vec<Board>> {
+        Ok(
+            sqlx::query_as::<_, Board>("select * from boards where repo = $1 order by lower(name)")
+                .bind(repo_id)
+                .fetch_all(executor)
+                .await?,
+        )
+    }
+
+    /// Returns the columns of this board from left to right
+    pub(crate) async fn columns<'e, E: Executor<'e, Database = Postgres>>(
+        &self,
+        executor: E,
+    ) -> Result<Vec<BoardColumn>> {
+        Ok(sqlx::query_as::<_, BoardColumn>(
+            "select * from board_columns where board = $1 order by position, id",
+        )
+        .bind(self.id)
+        .fetch_all(executor)
+        .await?)
+    }
+
+    /// Returns all cards of this board, ordered by their position within their column
+    pub(crate) async fn cards<'e, E: Executor<'e, Database = Postgres>>(
+        &self,
+        executor: E,
+    ) -> Result<Vec<BoardCard>> {
+        Ok(sqlx::query_as::<_, BoardCard>(
+            "select * from board_cards where board = $1 order by position, id",
+        )
+        .bind(self.id)
+        .fetch_all(executor)
+        .await?)
+    }
+}
+
+#[derive(FromRow, Display, Debug, Serialize)]
+#[display(fmt = "{}", name)]
+pub(crate) struct BoardColumn {
+    pub(crate) id: i32,
+    pub(crate) board: i32,
+
+    pub(crate) name: String,
+    pub(crate) position: i32,
+
+    /// Label which gets added to issues moved into this column
+    pub(crate) label: Option<i32>,
+    pub(crate) automation: BoardAutomation,
+}
+
+impl BoardColumn {
+    pub(crate) async fn find<'e, E: Executor<'e, Database = Postgres>>(
+        board_id: i32,
+        id: i32,
+        executor: E,
+    ) -> Result<Option<BoardColumn>> {
+        Ok(sqlx::query_as::<_, BoardColumn>(
+            "select * from board_columns where board = $1 and id = $2 limit 1",
+        )
+        .bind(board_id)
+        .bind(id)
+        .fetch_optional(executor)
+        .await?)
+    }
+}
+
+/// Issue event which moves issues into a column
+#[derive(Type, Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[sqlx(type_name = "board_automation", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum BoardAutomation {
+    #[default]
+    None,
+    /// Newly opened issues get added to the board
+    Opened,
+    /// Issues on the board get moved when they're closed
+    Closed,
+    /// Issues on the board get moved when they're reopened
+    Reopened,
+}
+
+#[derive(FromRow, Debug, Serialize)]
+pub(crate) struct BoardCard {
+    pub(crate) id: i32,
+    pub(crate) board: i32,
+    pub(crate) board_column: i32,
+    pub(crate) issue: i32,
+    pub(crate) position: i32,
+}
+
+/// Moves `issue` into `column` at `position` (or to the end), adding it to the board if needed.
+///
+/// Labels of the other label driven columns of the board are removed from the issue, the label of `column` is added
+/// and replaces labels sharing its scope. Returns the updated issue.
+pub(crate) async fn move_card(
+    issue: Issue,
+    column: &BoardColumn,
+    position: Option<usize>,
+    actor: Option<i32>,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Issue> {
+    let mut order: Vec<i32> = sqlx::query_as::<_, (i32,)>(
+        "select issue from board_cards where board_column = $1 and issue <> $2 order by position, id",
+    )
+    .bind(column.id)
+    .bind(issue.id)
+    .fetch_all(&mut *transaction)
+    .await?
+    .into_iter()
+    .map(|(id,)| id)
+    .collect();
+
+    let position = position.unwrap_or(order.len()).min(order.len());
+    order.insert(position, issue.id);
+
+    sqlx::query(
+        "insert into board_cards (board, board_column, issue, position) values ($1, $2, $3, $4) \
+        on conflict (board, issue) do update set board_column = $2, position = $4",
+    )
+    .bind(column.board)
+    .bind(column.id)
+    .bind(issue.id)
+    .bind(position as i32)
+    .execute(&mut *transaction)
+    .await?;
+
+    // Positions are rewritten for the whole column to keep them gapless
+    sqlx::query(
+        "update board_cards set position = ordered.position - 1 \
+        from unnest($2::integer[]) with ordinality as ordered(issue, position) \
+        where board_cards.board_column = $1 and board_cards.issue = ordered.issue",
+    )
+    .bind(column.id)
+    .bind(&order)
+    .execute(&mut *transaction)
+    .await?;
+
+    let (board_labels,): (Vec<i32>,) = sqlx::query_as(
+        "select coalesce(array_agg(label), array []::integer[]) from board_columns \
+        where board = $1 and label is not null and id <> $2",
+    )
+    .bind(column.board)
+    .bind(column.id)
+    .fetch_one(&mut *transaction)
+    .await?;
+
+    // Labels of the other columns are removed and the label of `column` gets added last, so it takes precedence
+    // over other labels sharing its scope
+    let candidates = issue
+        .labels
+        .iter()
+        .copied()
+        .filter(|label| !board_labels.contains(label) && Some(*label) != column.label)
+        .chain(column.label)
+        .collect::<Vec<_>>();
+    let labels = exclusive_scope_ids(candidates.as_slice(), &mut *transaction).await?;
+
+    let removed = issue
+        .labels
+        .iter()
+        .copied()
+        .filter(|label| !labels.contains(label))
+        .collect::<Vec<_>>();
+    let added = labels
+        .iter()
+        .copied()
+        .filter(|label| !issue.labels.contains(label))
+        .collect::<Vec<_>>();
+
+    if removed.is_empty() && added.is_empty() {
+        return Ok(issue);
+    }
+
+    let events = removed
+        .iter()
+        .map(|label| (*label, IssueEventType::Unlabeled))
+        .chain(added.iter().map(|label| (*label, IssueEventType::Labeled)));
+
+    for (label, event) in events {
+        let details = EventDetails {
+            label: Some(label),
+            ..Default::default()
+        };
+
+        issue
+            .record_event(actor, event, details, &mut *transaction)
+            .await?;
+    }
+
+    Ok(sqlx::query_as::<_, Issue>(
+        "update issues set labels = $1, updated_at = current_timestamp where id = $2 returning *",
+    )
+    .bind(&labels)
+    .bind(issue.id)
+    .fetch_one(&mut *transaction)
+    .await?)
+}
+
+/// Runs the board automations of the repository of `issue` for `event`.
+///
+/// Opened issues get added to boards with an `opened` column, closed and reopened issues get moved
+/// if they're on the board (or collected by a label driven column). Per board, the leftmost matching column is used.
+pub(crate) async fn automate(
+    issue: Issue,
+    event: IssueEventType,
+    actor: Option<i32>,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Issue> {
+    let automation = match event {
+        IssueEventType::Opened => BoardAutomation::Opened,
+        IssueEventType::Closed => BoardAutomation::Closed,
+        IssueEventType::Reopened => BoardAutomation::Reopened,
+        _ => return Ok(issue),
+    };
+
+    let columns = sqlx::query_as::<_, BoardColumn>(
+        "select distinct on (board_columns.board) board_columns.* from board_columns \
+        join boards on boards.id = board_columns.board \
+        where boards.repo = $1 and board_columns.automation = $2 \
+        order by board_columns.board, board_columns.position, board_columns.id",
+    )
+    .bind(issue.repo)
+    .bind(automation)
+    .fetch_all(&mut *transaction)
+    .await?;
+
+    let mut issue = issue;
+
+    for column in columns {
+        if automation != BoardAutomation::Opened {
+            let (on_board,): (bool,) = sqlx::query_as(
+                "select exists(select 1 from board_cards where board = $1 and issue = $2) \
+                or exists(select 1 from board_columns where board = $1 and label = any($3))",
+            )
+            .bind(column.board)
+            .bind(issue.id)
+            .bind(&issue.labels)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+            if !on_board {
+                continue;
+            }
+        }
+
+        issue = move_card(issue, &column, None, actor, &mut *transaction).await?;
+    }
+
+    Ok(issue)
+}
@@ -0,0 +1,173 @@
+use crate::prelude::LibGit2TimeExtensions;
+
+use std::borrow::Cow;
+
+use anyhow::Result;
+use bstr::ByteSlice;
+use chrono::{DateTime, FixedOffset};
+use git2::Commit;
+
+/// Commit metadata used while writing an archive. Owned so it can be moved into the archive writer task.
+pub(crate) struct ArchiveCommit {
+    id: String,
+    tree: String,
+    parents: Vec<String>,
+
+    author_name: String,
+    author_email: String,
+    author_time: DateTime<FixedOffset>,
+
+    committer_name: String,
+    committer_email: String,
+    committer_time: DateTime<FixedOffset>,
+
+    message: String,
+}
+
+impl ArchiveCommit {
+    pub(crate) fn new(commit: &Commit<'_>) -> Result<ArchiveCommit> {
+        let author = commit.author();
+        let committer = commit.committer();
+
+        Ok(ArchiveCommit {
+            id: commit.id().to_string(),
+            tree: commit.tree_id().to_string(),
+            parents: commit.parent_ids().map(|oid| oid.to_string()).collect(),
+            author_name: author.name().unwrap_or_default().to_owned(),
+            author_email: author.email().unwrap_or_default().to_owned(),
+            author_time: author.when().try_as_chrono()?,
+            committer_name: committer.name().unwrap_or_default().to_owned(),
+            committer_email: committer.email().unwrap_or_default().to_owned(),
+            committer_time: committer.when().try_as_chrono()?,
+            message: commit.message().unwrap_or_default().to_owned(),
+        })
+    }
+
+    /// Commit time, used as the modification time of every archive entry in order to be reproducible
+    pub(crate) fn time(&self) -> DateTime<FixedOffset> {
+        self.committer_time
+    }
+
+    /// Expands all `$Format:...$` placeholders in `content` like `git archive` does for files marked with `export-subst`
+    pub(crate) fn substitute<'a>(&self, content: &'a [u8]) -> Cow<'a, [u8]> {
+        const START: &[u8] = b"$Format:";
+
+        if content.find(START).is_none() {
+            return Cow::Borrowed(content);
+        }
+
+        let mut output = Vec::<u8>::with_capacity(content.len());
+        let mut rest = content;
+
+        while let Some(start) = rest.find(START) {
+            let after_start = &rest[start + START.len()..];
+
+            // Placeholders have to be closed on the same line
+            let end = match after_start.find_byteset(b"$\n") {
+                Some(end) if after_start[end] == b'$' => end,
+                _ => break,
+            };
+
+            output.extend_from_slice(&rest[..start]);
+            output.extend_from_slice(self.format(&after_start[..end].to_str_lossy()).as_bytes());
+
+            rest = &after_start[end + 1..];
+        }
+
+        output.extend_from_slice(rest);
+
+        Cow::Owned(output)
+    }
+
+    /// Implements the subset of `git log --pretty=format:` placeholders commonly used in `export-subst` files.
+    /// Unknown placeholders are left untouched.
+    fn format(&self, format: &str) -> String {
+        let mut output = String::with_capacity(format.len());
+        let mut chars = format.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                output.push(c);
+                continue;
+            }
+
+            let placeholder = match chars.next() {
+                Some(placeholder) => placeholder,
+                None => {
+                    output.push('%');
+                    break;
+                }
+            };
+
+            match placeholder {
+                '%' => output.push('%'),
+                'n' => output.push('\n'),
+                'H' => output.push_str(self.id.as_str()),
+                'h' => output.push_str(short(self.id.as_str())),
+                'T' => output.push_str(self.tree.as_str()),
+                't' => output.push_str(short(self.tree.as_str())),
+                'P' => output.push_str(self.parents.join(" ").as_str()),
+                'p' => output.push_str(
+                    self.parents
+                        .iter()
+                        .map(|parent| short(parent.as_str()))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                        .as_str(),
+                ),
+                's' => output.push_str(self.summary()),
+                'b' => output.push_str(self.body()),
+                'B' => output.push_str(self.message.as_str()),
+                'a' | 'c' => {
+                    let (name, email, time) = if placeholder == 'a' {
+                        (&self.author_name, &self.author_email, &self.author_time)
+                    } else {
+                        (
+                            &self.committer_name,
+                            &self.committer_email,
+                            &self.committer_time,
+                        )
+                    };
+
+                    match chars.peek().copied() {
+                        Some('n') => output.push_str(name.as_str()),
+                        Some('e') => output.push_str(email.as_str()),
+                        Some('d') => output
+                            .push_str(time.format("%a %b %-d %H:%M:%S %Y %z").to_string().as_str()),
+                        Some('t') => output.push_str(time.timestamp().to_string().as_str()),
+                        Some('i') => output
+                            .push_str(time.format("%Y-%m-%d %H:%M:%S %z").to_string().as_str()),
+                        Some('I') => output.push_str(time.to_rfc3339().as_str()),
+                        _ => {
+                            output.push('%');
+                            output.push(placeholder);
+                            continue;
+                        }
+                    }
+
+                    chars.next();
+                }
+                _ => {
+                    output.push('%');
+                    output.push(placeholder);
+                }
+            }
+        }
+
+        output
+    }
+
+    fn summary(&self) -> &str {
+        self.message.lines().next().unwrap_or_default()
+    }
+
+    fn body(&self) -> &str {
+        self.message
+            .split_once("\n\n")
+            .map_or("", |(_, body)| body.trim_end())
+    }
+}
+
+fn short(oid: &str) -> &str {
+    &oid[..oid.len().min(7)]
+}
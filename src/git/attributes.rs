@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use bstr::ByteSlice;
+use regex::Regex;
+
+/// State of an attribute for a path, see gitattributes(5)
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum AttributeValue {
+    /// `attr`
+    Set,
+    /// `-attr`
+    Unset,
+    /// `attr=value`
+    Value(String),
+}
+
+/// Attributes of the `.gitattributes` files encountered while walking a tree.
+///
+/// Attributes files deeper in the tree take precedence over the ones closer to the root,
+/// and within a file later lines take precedence over earlier ones.
+#[derive(Default)]
+pub(crate) struct GitAttributes {
+    frames: Vec<AttributesFrame>,
+}
+
+struct AttributesFrame {
+    /// Directory the `.gitattributes` file is located in, relative to the root and without trailing slash
+    directory: String,
+    rules: Vec<AttributeRule>,
+}
+
+struct AttributeRule {
+    pattern: Regex,
+    /// Patterns without a slash match the file name at any depth, all others the path relative to the attributes file
+    basename_only: bool,
+    directory_only: bool,
+
+    /// `None` resets the attribute to unspecified (`!attr`)
+    attributes: HashMap<String, Option<AttributeValue>>,
+}
+
+impl GitAttributes {
+    /// Parses the `.gitattributes` file located in `directory` and pushes its rules onto the stack.
+    /// Every call has to be matched by a call to [pop](GitAttributes::pop) once the directory has been left.
+    pub(crate) fn push(&mut self, directory: &str, content: &[u8]) {
+        let rules = content
+            .lines()
+            .filter_map(|line| parse_rule(line.to_str_lossy().as_ref()))
+            .collect();
+
+        self.frames.push(AttributesFrame {
+            directory: directory.to_owned(),
+            rules,
+        });
+    }
+
+    pub(crate) fn pop(&mut self) {
+        self.frames.pop();
+    }
+
+    /// Returns true if `name` is set for `path` (relative to the root of the walked tree).
+    /// Attributes with a value (`attr=value`) count as set unless the value is `false`.
+    pub(crate) fn is_set(&self, path: &str, is_directory: bool, name: &str) -> bool {
+        match self.get(path, is_directory, name) {
+            Some(AttributeValue::Set) => true,
+            Some(AttributeValue::Value(value)) => value != "false",
+            _ => false,
+        }
+    }
+
+    /// Returns the value of `name` for `path` if it is specified
+    pub(crate) fn get(&self, path: &str, is_directory: bool, name: &str) -> Option<AttributeValue> {
+        let mut value = None;
+
+        for frame in &self.frames {
+            let relative = if frame.directory.is_empty() {
+                path
+            } else {
+                match path
+                    .strip_prefix(frame.directory.as_str())
+                    .and_then(|rest| rest.strip_prefix('/'))
+                {
+                    Some(relative) => relative,
+                    None => continue,
+                }
+            };
+
+            for rule in &frame.rules {
+                if rule.directory_only && !is_directory {
+                    continue;
+                }
+
+                let attribute = match rule.attributes.get(name) {
+                    Some(attribute) => attribute,
+                    None => continue,
+                };
+
+                let subject = if rule.basename_only {
+                    relative.rsplit('/').next().unwrap_or(relative)
+                } else {
+                    relative
+                };
+
+                if rule.pattern.is_match(subject) {
+                    value = attribute.clone();
+                }
+            }
+        }
+
+        value
+    }
+}
+
+fn parse_rule(line: &str) -> Option<AttributeRule> {
+    let line = line.trim();
+
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let mut tokens = line.split_whitespace();
+    let mut pattern = tokens.next()?;
+
+    let attributes = tokens
+        .map(|token| {
+            if let Some(name) = token.strip_prefix('-') {
+                (name.to_owned(), Some(AttributeValue::Unset))
+            } else if let Some(name) = token.strip_prefix('!') {
+                (name.to_owned(), None)
+            } else if let Some((name, value)) = token.split_once('=') {
+                (
+                    name.to_owned(),
+                    Some(AttributeValue::Value(value.to_owned())),
+                )
+            } else {
+                (token.to_owned(), Some(AttributeValue::Set))
+            }
+        })
+        .collect::<HashMap<_, _>>();
+
+    if attributes.is_empty() {
+        return None;
+    }
+
+    let directory_only = pattern.len() > 1 && pattern.ends_with('/');
+
+    if directory_only {
+        pattern = &pattern[..pattern.len() - 1];
+    }
+
+    let basename_only = !pattern.contains('/');
+    let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+
+    Some(AttributeRule {
+        pattern: glob_to_regex(pattern).ok()?,
+        basename_only,
+        directory_only,
+        attributes,
+    })
+}
+
+/// Converts a gitattributes glob pattern into an anchored regular expression
+fn glob_to_regex(pattern: &str) -> Result<Regex> {
+    let mut regex = String::from("^");
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+
+                if chars.peek() == Some(&'/') {
+                    // `**/` matches zero or more directories
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                let mut class = String::new();
+                let mut closed = false;
+
+                if chars.peek() == Some(&'!') || chars.peek() == Some(&'^') {
+                    chars.next();
+                    class.push('^');
+                }
+
+                for c in chars.by_ref() {
+                    if c == ']' && !class.is_empty() && class != "^" {
+                        closed = true;
+                        break;
+                    }
+
+                    if c == '\\' || c == '[' {
+                        class.push('\\');
+                    }
+
+                    class.push(c);
+                }
+
+                if closed {
+                    regex.push('[');
+                    regex.push_str(class.as_str());
+                    regex.push(']');
+                } else {
+                    regex.push_str(regex::escape(format!("[{}", class).as_str()).as_str());
+                }
+            }
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    regex.push_str(regex::escape(escaped.to_string().as_str()).as_str());
+                }
+            }
+            _ => regex.push_str(regex::escape(c.to_string().as_str()).as_str()),
+        }
+    }
+
+    regex.push('$');
+
+    Ok(Regex::new(regex.as_str())?)
+}
@@ -1,5 +1,5 @@
 use crate::prelude::*;
-use crate::privileges::repo_visibility::RepoVisibility;
+use crate::privileges::privilege;
 use crate::repository::Repository;
 use crate::user::User;
 use crate::{crypto, die, err};
@@ -7,7 +7,8 @@ use crate::{crypto, die, err};
 use actix_web::http::header::{CONTENT_TYPE, WWW_AUTHENTICATE};
 use actix_web::{Either, HttpRequest, HttpResponse};
 use anyhow::Result;
-use sqlx::{Executor, Postgres};
+use gitarena_common::database::models::RepoVisibility;
+use sqlx::{Executor, Postgres, Transaction};
 use tracing::instrument;
 use tracing_unwrap::OptionExt;
 
@@ -41,6 +42,40 @@ where
     }
 }
 
+/// Looks up `username/repository` and validates that the requesting user is allowed to read it,
+/// prompting for basic auth if the repository is not public
+#[instrument(skip(request, transaction), err)]
+pub(crate) async fn validate_read_access(
+    username: &str,
+    repository: &str,
+    content_type: &str,
+    request: &HttpRequest,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Either<(Option<User>, Repository), HttpResponse>> {
+    let user_option: Option<(i32,)> =
+        sqlx::query_as("select id from users where lower(username) = lower($1) limit 1")
+            .bind(username)
+            .fetch_optional(&mut *transaction)
+            .await?;
+
+    let repo_option = match user_option {
+        Some((user_id,)) => Repository::open(user_id, repository, &mut *transaction).await,
+        None => None,
+    };
+
+    let (user, repo) =
+        match validate_repo_access(repo_option, content_type, request, &mut *transaction).await? {
+            Either::Left(tuple) => tuple,
+            Either::Right(response) => return Ok(Either::Right(response)),
+        };
+
+    if !privilege::check_access(&repo, user.as_ref(), &mut *transaction).await? {
+        die!(NOT_FOUND, "Repository not found");
+    }
+
+    Ok(Either::Left((user, repo)))
+}
+
 #[instrument(skip(request, executor), err)]
 pub(crate) async fn login_flow<'e, E>(
     request: &HttpRequest,
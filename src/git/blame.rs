@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Result;
+use futures_locks::RwLock;
+use git2::{BlameHunk as LibGit2BlameHunk, BlameOptions, Commit, ObjectType, Oid, Repository};
+use once_cell::sync::Lazy;
+use tracing::instrument;
+
+/// Name of the file which lists revisions to be skipped when attributing lines.
+/// Uses the same format as `git blame --ignore-revs-file`.
+pub(crate) const IGNORE_REVS_FILE: &str = ".git-blame-ignore-revs";
+
+/// Upper bound of cached blames. Once reached, the cache gets cleared.
+const MAX_CACHE_ENTRIES: usize = 512;
+
+/// Max amount of ignored commits that get skipped for a single hunk before we give up
+const MAX_IGNORE_DEPTH: usize = 16;
+
+static BLAME_CACHE: Lazy<RwLock<HashMap<BlameCacheKey, Arc<Vec<BlameHunk>>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+#[derive(Hash, Eq, PartialEq)]
+struct BlameCacheKey {
+    commit: Oid,
+    path: String,
+    ignore_revs: Option<Oid>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct BlameHunk {
+    pub(crate) commit: Oid,
+
+    pub(crate) start_line: usize, // Starts counting from 1
+    pub(crate) lines: usize,
+
+    /// Path of the file in `commit`, differs from the requested path if the file has been renamed since then
+    pub(crate) orig_path: Option<String>,
+    pub(crate) orig_start_line: usize,
+}
+
+impl From<&LibGit2BlameHunk<'_>> for BlameHunk {
+    fn from(hunk: &LibGit2BlameHunk<'_>) -> Self {
+        BlameHunk {
+            commit: hunk.final_commit_id(),
+            start_line: hunk.final_start_line(),
+            lines: hunk.lines_in_hunk(),
+            orig_path: hunk.path().map(|path| path.to_string_lossy().into_owned()),
+            orig_start_line: hunk.orig_start_line(),
+        }
+    }
+}
+
+/// Blames `path` as of `commit` and returns the hunks sorted by line number.
+///
+/// If `ignore_revs` is set, lines last changed by any of these commits get attributed to the commit
+/// which changed them before. This is done line-by-line, so hunks of ignored commits are expected
+/// to not have shifted the line numbers (which is the case for most formatting commits).
+///
+/// Results are cached by the id of `commit`, which identifies the whole history leading up to it,
+/// so repeated views of the same file are cheap.
+#[instrument(err, skip(repo, commit, ignore_revs))]
+pub(crate) async fn blame_file(
+    repo: &Repository,
+    commit: &Commit<'_>,
+    path: &str,
+    ignore_revs: Option<(Oid, &[Oid])>,
+) -> Result<Arc<Vec<BlameHunk>>> {
+    let key = BlameCacheKey {
+        commit: commit.id(),
+        path: path.to_owned(),
+        ignore_revs: ignore_revs.map(|(oid, _)| oid),
+    };
+
+    if let Some(hunks) = BLAME_CACHE.read().await.get(&key) {
+        return Ok(hunks.clone());
+    }
+
+    let mut options = BlameOptions::new();
+    options.newest_commit(commit.id());
+
+    let blame = repo.blame_file(Path::new(path), Some(&mut options))?;
+    let mut hunks = Vec::<BlameHunk>::with_capacity(blame.len());
+
+    for hunk in blame.iter() {
+        let hunk = BlameHunk::from(&hunk);
+
+        match ignore_revs {
+            Some((_, revs)) if revs.contains(&hunk.commit) => {
+                hunks.extend(skip_ignored_commit(repo, hunk, revs, 0)?)
+            }
+            _ => hunks.push(hunk),
+        }
+    }
+
+    hunks.sort_by_key(|hunk| hunk.start_line);
+
+    let hunks = Arc::new(hunks);
+    let mut cache = BLAME_CACHE.write().await;
+
+    if cache.len() >= MAX_CACHE_ENTRIES {
+        cache.clear();
+    }
+
+    cache.insert(key, hunks.clone());
+
+    Ok(hunks)
+}
+
+/// Re-blames the lines of `hunk` at the first parent of the ignored commit which introduced them
+fn skip_ignored_commit(
+    repo: &Repository,
+    hunk: BlameHunk,
+    ignore_revs: &[Oid],
+    depth: usize,
+) -> Result<Vec<BlameHunk>> {
+    if depth >= MAX_IGNORE_DEPTH {
+        return Ok(vec![hunk]);
+    }
+
+    let commit = repo.find_commit(hunk.commit)?;
+
+    let parent = match commit.parents().next() {
+        Some(parent) => parent,
+        None => return Ok(vec![hunk]), // Root commits can't be skipped
+    };
+
+    let path = match hunk.orig_path.as_deref() {
+        Some(path) => path,
+        None => return Ok(vec![hunk]),
+    };
+
+    // The file has been introduced by the ignored commit
+    let parent_lines = match parent.tree()?.get_path(Path::new(path)) {
+        Ok(entry) if entry.kind() == Some(ObjectType::Blob) => {
+            let blob = repo.find_blob(entry.id())?;
+            blob.content().split(|byte| *byte == b'\n').count()
+        }
+        _ => return Ok(vec![hunk]),
+    };
+
+    let first_line = hunk.orig_start_line;
+    let last_line = hunk.orig_start_line + hunk.lines - 1;
+
+    // The ignored commit added lines, these have to be attributed to it
+    if last_line > parent_lines {
+        return Ok(vec![hunk]);
+    }
+
+    let mut options = BlameOptions::new();
+    options.newest_commit(parent.id());
+    options.min_line(first_line);
+    options.max_line(last_line);
+
+    let blame = match repo.blame_file(Path::new(path), Some(&mut options)) {
+        Ok(blame) => blame,
+        Err(_) => return Ok(vec![hunk]),
+    };
+
+    let mut results = Vec::<BlameHunk>::new();
+
+    for parent_hunk in blame.iter() {
+        let mut parent_hunk = BlameHunk::from(&parent_hunk);
+
+        // Translate the line numbers of the parent back into the line numbers of the blamed file
+        parent_hunk.start_line = parent_hunk.start_line - first_line + hunk.start_line;
+
+        if ignore_revs.contains(&parent_hunk.commit) {
+            results.extend(skip_ignored_commit(
+                repo,
+                parent_hunk,
+                ignore_revs,
+                depth + 1,
+            )?);
+        } else {
+            results.push(parent_hunk);
+        }
+    }
+
+    Ok(results)
+}
+
+/// Parses a `.git-blame-ignore-revs` file. Comments (`#`) and empty lines are skipped.
+/// Only full object ids are accepted, just like in Git itself.
+pub(crate) fn parse_ignore_revs(content: &str) -> Vec<Oid> {
+    content
+        .lines()
+        .map(|line| line.split_once('#').map_or(line, |(rev, _)| rev).trim())
+        .filter(|line| line.len() == 40)
+        .filter_map(|line| Oid::from_str(line).ok())
+        .collect()
+}
+
+/// Reads `.git-blame-ignore-revs` from the root of `commit`, returning the blob id as well as the parsed revisions
+#[instrument(err, skip(repo, commit))]
+pub(crate) async fn ignore_revs_at(
+    repo: &Repository,
+    commit: &Commit<'_>,
+) -> Result<Option<(Oid, Vec<Oid>)>> {
+    let entry = match commit.tree()?.get_name(IGNORE_REVS_FILE) {
+        Some(entry) if entry.kind() == Some(ObjectType::Blob) => entry.id(),
+        _ => return Ok(None),
+    };
+
+    let blob = repo.find_blob(entry)?;
+    let content = String::from_utf8_lossy(blob.content());
+
+    Ok(Some((entry, parse_ignore_revs(content.as_ref()))))
+}
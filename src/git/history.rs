@@ -1,8 +1,47 @@
+use std::collections::VecDeque;
+use std::mem;
+use std::path::Path;
+
 use anyhow::Result;
 use async_recursion::async_recursion;
-use git2::{DiffOptions, Oid, Repository as Git2Repository, Sort};
+use git2::{Commit, Delta, DiffFindOptions, DiffOptions, Oid, Repository as Git2Repository, Sort};
 use tracing::instrument;
 
+/// A commit which changed a path, as returned by [path_history]
+#[derive(Debug)]
+pub(crate) struct PathHistoryEntry {
+    pub(crate) commit: Oid,
+
+    /// Path of the file at this commit. Only differs from the requested path if renames are followed.
+    pub(crate) path: String,
+
+    /// Previous path if the file was renamed in this commit
+    pub(crate) renamed_from: Option<String>,
+}
+
+enum PathChange {
+    Unchanged,
+    Modified,
+    Renamed(String),
+}
+
+/// Resolves a revision (branch or tag name, full or abbreviated commit id or expressions such as `HEAD~3`)
+/// to a commit. Returns the full name of the reference if the revision names one (e.g. `refs/heads/main`).
+///
+/// Returns `None` if the revision does not exist or does not point to a commit.
+#[instrument(skip(repo))]
+pub(crate) async fn resolve_revision(
+    repo: &Git2Repository,
+    revision: &str,
+) -> Option<(Option<String>, Oid)> {
+    let (object, reference) = repo.revparse_ext(revision).ok()?;
+    let commit = object.peel_to_commit().ok()?;
+
+    let reference_name = reference.and_then(|reference| reference.name().map(str::to_owned));
+
+    Some((reference_name, commit.id()))
+}
+
 #[instrument(err, skip(repo))]
 pub(crate) async fn last_commit_for_blob(
     repo: &Git2Repository,
@@ -14,12 +53,17 @@ pub(crate) async fn last_commit_for_blob(
     Ok(commits.first().copied())
 }
 
+/// `reference_name` can be either a full ref name or a OID string (ascii-hex-numeric, 40 digits)
 #[instrument(err, skip(repo))]
 #[async_recursion(?Send)]
 pub(crate) async fn last_commit_for_ref(
     repo: &Git2Repository,
     reference_name: &str,
 ) -> Result<Option<Oid>> {
+    if let Ok(oid) = Oid::from_str(reference_name) {
+        return Ok(Some(repo.find_commit(oid)?.id()));
+    }
+
     let reference = repo.find_reference(reference_name)?;
 
     if let Some(target) = reference.symbolic_target() {
@@ -29,6 +73,7 @@ pub(crate) async fn last_commit_for_ref(
     Ok(reference.target())
 }
 
+/// `reference` can be either a full ref name or a OID string (ascii-hex-numeric, 40 digits)
 #[instrument(err, skip(repo))]
 pub(crate) async fn commits_for_blob(
     repo: &Git2Repository,
@@ -44,7 +89,11 @@ pub(crate) async fn commits_for_blob(
 
     let mut rev_walk = repo.revwalk()?;
     rev_walk.set_sorting(Sort::TIME)?;
-    rev_walk.push_ref(reference)?;
+
+    match Oid::from_str(reference) {
+        Ok(oid) => rev_walk.push(oid)?,
+        Err(_) => rev_walk.push_ref(reference)?,
+    }
 
     'outer: for result in rev_walk {
         let commit_oid = result?;
@@ -82,6 +131,137 @@ pub(crate) async fn commits_for_blob(
     Ok(results)
 }
 
+/// Returns the commits which changed `path`, newest first. This is the equivalent of `git log [--follow] -- <path>`.
+///
+/// `reference` can be either a full ref name or a OID string (ascii-hex-numeric, 40 digits).
+/// If `follow` is true, the history continues past renames of the file.
+///
+/// Paging works the same as in the commits view: If `after` is set, returns `limit` commits which come after that commit.
+/// If `before` is set, returns the `limit` commits which come right before that commit.
+#[instrument(err, skip(repo))]
+pub(crate) async fn path_history(
+    repo: &Git2Repository,
+    reference: &str,
+    path: &str,
+    follow: bool,
+    after: Option<Oid>,
+    before: Option<Oid>,
+    limit: usize,
+) -> Result<Vec<PathHistoryEntry>> {
+    let mut results = VecDeque::<PathHistoryEntry>::with_capacity(limit + 1);
+
+    let mut rev_walk = repo.revwalk()?;
+    rev_walk.set_sorting(Sort::TIME)?;
+
+    match Oid::from_str(reference) {
+        Ok(oid) => rev_walk.push(oid)?,
+        Err(_) => rev_walk.push_ref(reference)?,
+    }
+
+    // Renames need to be tracked even while skipping to the requested page, so we always walk from the start
+    let mut current_path = path.to_owned();
+    let mut skipping = after.is_some();
+
+    for result in rev_walk {
+        let commit_oid = result?;
+
+        if before == Some(commit_oid) {
+            break;
+        }
+
+        let commit = repo.find_commit(commit_oid)?;
+
+        let entry = match path_change(repo, &commit, current_path.as_str(), follow)? {
+            PathChange::Unchanged => None,
+            PathChange::Modified => Some(PathHistoryEntry {
+                commit: commit_oid,
+                path: current_path.clone(),
+                renamed_from: None,
+            }),
+            PathChange::Renamed(previous) => Some(PathHistoryEntry {
+                commit: commit_oid,
+                path: mem::replace(&mut current_path, previous.clone()),
+                renamed_from: Some(previous),
+            }),
+        };
+
+        if skipping {
+            skipping = after != Some(commit_oid);
+            continue;
+        }
+
+        if let Some(entry) = entry {
+            results.push_back(entry);
+        }
+
+        if results.len() > limit {
+            results.pop_front();
+        }
+
+        // If we look for commits before a certain commit, we need to walk until we find it
+        if before.is_none() && results.len() >= limit {
+            break;
+        }
+    }
+
+    Ok(results.into())
+}
+
+fn path_change(
+    repo: &Git2Repository,
+    commit: &Commit,
+    path: &str,
+    follow: bool,
+) -> Result<PathChange> {
+    let path = Path::new(path);
+
+    let entry = commit.tree()?.get_path(path).ok().map(|e| e.id());
+    let parents = commit.parents().collect::<Vec<Commit>>();
+    let parent_entries = parents
+        .iter()
+        .map(|parent| {
+            parent
+                .tree()
+                .ok()
+                .and_then(|tree| tree.get_path(path).ok())
+                .map(|e| e.id())
+        })
+        .collect::<Vec<Option<Oid>>>();
+
+    // Commits which have the same content as one of their parents did not change the path (same as `git log` does)
+    if parent_entries.contains(&entry) || (parents.is_empty() && entry.is_none()) {
+        return Ok(PathChange::Unchanged);
+    }
+
+    // The path was added in this commit; check whenever it actually was renamed
+    if follow
+        && entry.is_some()
+        && !parents.is_empty()
+        && parent_entries.iter().all(Option::is_none)
+    {
+        let mut diff =
+            repo.diff_tree_to_tree(Some(&parents[0].tree()?), Some(&commit.tree()?), None)?;
+        diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+        let previous = diff
+            .deltas()
+            .find(|delta| delta.status() == Delta::Renamed && delta.new_file().path() == Some(path))
+            .and_then(|delta| {
+                delta
+                    .old_file()
+                    .path()
+                    .and_then(|p| p.to_str())
+                    .map(str::to_owned)
+            });
+
+        if let Some(previous) = previous {
+            return Ok(PathChange::Renamed(previous));
+        }
+    }
+
+    Ok(PathChange::Modified)
+}
+
 /// `reference` can be either a full ref name or a OID string (ascii-hex-numeric, 40 digits)
 /// Returns at most `limit` commits or all commits if `limit == 0`
 #[instrument(err, skip(repo))]
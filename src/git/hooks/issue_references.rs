@@ -0,0 +1,371 @@
+use crate::board;
+use crate::git::ref_update::RefUpdate;
+use crate::issue::{EventDetails, Issue, IssueEventType};
+use crate::privileges::privilege;
+use crate::repository::Repository;
+use crate::user::User;
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use git2::{BranchType, Oid, Repository as Git2Repository, Sort};
+use gitarena_common::database::models::RepoVisibility;
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::instrument;
+use tracing_unwrap::ResultExt;
+
+/// Maximum amount of new commits per ref update which get checked for issue references
+const MAX_COMMITS: usize = 1000;
+
+// Matches `#42`, `user/repo#7` and closing keywords in front of them (`Fixes #42`) if they're not part of a word or path
+static ISSUE_REFERENCE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?i)(?:^|[^\w/#&@.])(?:(?P<keyword>close[sd]?|fix(?:e[sd])?|resolve[sd]?):?\s+)?(?:(?P<owner>[A-Za-z0-9_-]{3,32})/(?P<repo>[A-Za-z0-9_-]+))?#(?P<index>\d+)\b",
+    )
+    .unwrap_or_log()
+});
+
+/// Issue mentioned in a commit message
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct IssueReference {
+    /// Owner and name of the repository, `None` for issues of the repository the commit was pushed to
+    pub(crate) repo: Option<(String, String)>,
+    pub(crate) index: i32,
+
+    /// Whether the reference was preceded by a closing keyword such as `Fixes`
+    pub(crate) closes: bool,
+}
+
+/// Returns all issues mentioned in `message`. Every issue is only returned once
+pub(crate) fn parse_references(message: &str) -> Vec<IssueReference> {
+    let mut references = Vec::<IssueReference>::new();
+
+    for captures in ISSUE_REFERENCE.captures_iter(message) {
+        let index = match captures["index"].parse::<i32>() {
+            Ok(index) => index,
+            Err(_) => continue,
+        };
+        let repo = captures
+            .name("owner")
+            .zip(captures.name("repo"))
+            .map(|(owner, repo)| (owner.as_str().to_owned(), repo.as_str().to_owned()));
+        let closes = captures.name("keyword").is_some();
+
+        match references
+            .iter_mut()
+            .find(|reference| reference.index == index && reference.repo == repo)
+        {
+            Some(reference) => reference.closes |= closes,
+            None => references.push(IssueReference {
+                repo,
+                index,
+                closes,
+            }),
+        }
+    }
+
+    references
+}
+
+/// Commit which got pushed and has not been seen on any other branch before
+struct PushedCommit {
+    oid: String,
+    message: String,
+
+    /// Whether the commit landed on the default branch
+    default_branch: bool,
+}
+
+/// Runs [process] in the background, so pushes do not have to wait for it
+pub(crate) fn schedule(db_pool: PgPool, repo_id: i32, pusher_id: i32, updates: Vec<RefUpdate>) {
+    tokio::spawn(async move {
+        if let Err(err) = process_by_id(repo_id, pusher_id, updates.as_slice(), &db_pool).await {
+            warn!(
+                "Failed to process issue references for repo id {}: {}",
+                repo_id, err
+            );
+        }
+    });
+}
+
+async fn process_by_id(
+    repo_id: i32,
+    pusher_id: i32,
+    updates: &[RefUpdate],
+    db_pool: &PgPool,
+) -> Result<()> {
+    let repo = sqlx::query_as::<_, Repository>("select * from repositories where id = $1 limit 1")
+        .bind(repo_id)
+        .fetch_one(db_pool)
+        .await?;
+    let pusher = sqlx::query_as::<_, User>("select * from users where id = $1 limit 1")
+        .bind(pusher_id)
+        .fetch_one(db_pool)
+        .await?;
+
+    process(&repo, &pusher, updates, db_pool).await
+}
+
+/// Adds timeline entries to all issues referenced in the messages of newly pushed commits.
+///
+/// Issues get closed if the commit landed on the default branch, used a closing keyword
+/// and `pusher` is allowed to manage issues of the repository the issue belongs to.
+/// Issues of other repositories are only referenced if all their viewers are able to read `repo`.
+/// Everything runs in its own transaction, so failures do not affect the push itself.
+#[instrument(err, skip(db_pool, updates))]
+async fn process(
+    repo: &Repository,
+    pusher: &User,
+    updates: &[RefUpdate],
+    db_pool: &PgPool,
+) -> Result<()> {
+    let mut transaction = db_pool.begin().await?;
+
+    let path = repo.get_fs_path(&mut transaction).await?;
+    let default_ref = format!("refs/heads/{}", repo.default_branch);
+    let branch_updates = updates
+        .iter()
+        .filter(|update| update.target_ref.starts_with("refs/heads/"))
+        .filter_map(|update| {
+            update.new.clone().map(|new| {
+                (
+                    update.target_ref.clone(),
+                    update.old.clone(),
+                    new,
+                    update.target_ref == default_ref,
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+
+    if branch_updates.is_empty() {
+        return Ok(());
+    }
+
+    let commits =
+        tokio::task::spawn_blocking(move || new_commits(path.as_str(), &branch_updates)).await??;
+
+    // Repositories referenced by owner and name, `None` if they don't exist or the pusher has no access
+    let mut repositories = HashMap::<(String, String), Option<Repository>>::new();
+
+    for commit in commits {
+        for reference in parse_references(commit.message.as_str()) {
+            let target = match &reference.repo {
+                Some(key) => {
+                    if !repositories.contains_key(key) {
+                        let target =
+                            find_repository(&key.0, &key.1, pusher, &mut transaction).await?;
+                        repositories.insert(key.clone(), target);
+                    }
+
+                    match repositories.get(key) {
+                        Some(Some(target)) => target,
+                        _ => continue,
+                    }
+                }
+                None => repo,
+            };
+
+            reference_issue(target, repo, pusher, &commit, &reference, &mut transaction).await?;
+        }
+    }
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+async fn find_repository(
+    owner: &str,
+    name: &str,
+    pusher: &User,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Option<Repository>> {
+    let owner = match User::find_using_name(owner, &mut *transaction).await {
+        Some(owner) => owner,
+        None => return Ok(None),
+    };
+
+    let target = match Repository::open(owner, name, &mut *transaction).await {
+        Some(target) => target,
+        None => return Ok(None),
+    };
+
+    if !privilege::check_access(&target, Some(pusher), &mut *transaction).await? {
+        return Ok(None);
+    }
+
+    Ok(Some(target))
+}
+
+async fn reference_issue(
+    target: &Repository,
+    repo: &Repository,
+    pusher: &User,
+    commit: &PushedCommit,
+    reference: &IssueReference,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<()> {
+    if target.archived || !readable_by_issue_viewers(repo, target) {
+        return Ok(());
+    }
+
+    let issue = match Issue::find(target.id, reference.index, &mut *transaction).await? {
+        Some(issue) => issue,
+        None => return Ok(()),
+    };
+
+    let can_manage =
+        privilege::check_manage_issues(target, Some(pusher), &mut *transaction).await?;
+
+    if !issue.is_visible_to(Some(pusher), can_manage) {
+        return Ok(());
+    }
+
+    let details = || EventDetails {
+        commit: Some(commit.oid.clone()),
+        commit_repo: Some(repo.id),
+        ..Default::default()
+    };
+
+    // Commits pushed to a branch first and merged into the default branch later are only referenced once
+    let (referenced,): (bool,) = sqlx::query_as(
+        "select exists(select 1 from issue_events where issue = $1 and event = 'referenced' and commit = $2 limit 1)",
+    )
+    .bind(issue.id)
+    .bind(commit.oid.as_str())
+    .fetch_one(&mut *transaction)
+    .await?;
+
+    if !referenced {
+        issue
+            .record_event(
+                Some(pusher.id),
+                IssueEventType::Referenced,
+                details(),
+                &mut *transaction,
+            )
+            .await?;
+    }
+
+    if reference.closes && commit.default_branch && can_manage && !issue.closed {
+        sqlx::query(
+            "update issues set closed = true, updated_at = current_timestamp where id = $1",
+        )
+        .bind(issue.id)
+        .execute(&mut *transaction)
+        .await?;
+
+        issue
+            .record_event(
+                Some(pusher.id),
+                IssueEventType::Closed,
+                details(),
+                &mut *transaction,
+            )
+            .await?;
+
+        let issue = board::automate(
+            issue,
+            IssueEventType::Closed,
+            Some(pusher.id),
+            &mut *transaction,
+        )
+        .await?;
+
+        info!(
+            "Issue #{} of repo id {} closed by commit {}",
+            issue.index, target.id, &commit.oid
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns true if everyone able to see issues of `target` is also able to read `repo`.
+///
+/// Timeline entries expose the commit, the pusher and the repository it was pushed to, so references
+/// from repositories the viewers of the issue might not have access to are not recorded at all.
+fn readable_by_issue_viewers(repo: &Repository, target: &Repository) -> bool {
+    if repo.id == target.id {
+        return true;
+    }
+
+    if repo.disabled {
+        return false;
+    }
+
+    match repo.visibility {
+        RepoVisibility::Public => true,
+        RepoVisibility::Internal => target.visibility != RepoVisibility::Public,
+        RepoVisibility::Private => false,
+    }
+}
+
+/// Returns the commits introduced by `updates` (target ref, old oid, new oid, whether it's the default branch).
+///
+/// Commits of newly created branches which are already part of another branch are skipped,
+/// as they have been processed when they were pushed to that branch.
+fn new_commits(
+    path: &str,
+    updates: &[(String, Option<String>, String, bool)],
+) -> Result<Vec<PushedCommit>> {
+    let repo = Git2Repository::open(path)?;
+
+    let mut commits = Vec::<PushedCommit>::new();
+    let mut seen = HashMap::<Oid, usize>::new();
+
+    for (target_ref, old, new, default_branch) in updates {
+        let mut walk = repo.revwalk()?;
+        walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+        walk.push(Oid::from_str(new)?)?;
+
+        match old {
+            Some(old) => {
+                // The old commit may be gone after a force push
+                if let Ok(oid) = Oid::from_str(old) {
+                    if repo.find_commit(oid).is_ok() {
+                        walk.hide(oid)?;
+                    }
+                }
+            }
+            None => {
+                for branch in repo.branches(Some(BranchType::Local))? {
+                    let (branch, _) = branch?;
+                    let reference = branch.get();
+
+                    if reference.name() == Some(target_ref.as_str()) {
+                        continue;
+                    }
+
+                    if let Some(oid) = reference.target() {
+                        walk.hide(oid)?;
+                    }
+                }
+            }
+        }
+
+        for oid in walk.take(MAX_COMMITS) {
+            let oid = oid?;
+
+            if let Some(index) = seen.get(&oid) {
+                commits[*index].default_branch |= default_branch;
+                continue;
+            }
+
+            let commit = repo.find_commit(oid)?;
+
+            seen.insert(oid, commits.len());
+            commits.push(PushedCommit {
+                oid: oid.to_string(),
+                message: commit.message().unwrap_or_default().to_owned(),
+                default_branch: *default_branch,
+            });
+        }
+    }
+
+    Ok(commits)
+}
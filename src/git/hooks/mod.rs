@@ -1,3 +1,4 @@
 pub(crate) mod detect_license;
+pub(crate) mod issue_references;
 pub(crate) mod post_update;
 //pub(crate) mod repo_size;
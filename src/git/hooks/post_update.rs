@@ -1,4 +1,6 @@
 use crate::git::hooks::detect_license::detect_license;
+use crate::git::last_commits;
+use crate::languages;
 use crate::repository::Repository;
 
 use std::sync::Arc;
@@ -6,7 +8,7 @@ use std::sync::Arc;
 use anyhow::Result;
 use git_repository::odb::Store;
 use log::warn;
-use sqlx::{Executor, Postgres};
+use sqlx::{Executor, PgPool, Postgres};
 
 // TODO: run these async in the background without waiting
 // prefered: https://www.reddit.com/r/rust/comments/fddf6y/handling_longrunning_background_tasks_in_actixweb/
@@ -15,6 +17,7 @@ use sqlx::{Executor, Postgres};
 pub(crate) async fn run<'e, E: Executor<'e, Database = Postgres>>(
     store: Arc<Store>,
     repo: &mut Repository,
+    db_pool: &PgPool,
     executor: E,
 ) -> Result<()> {
     let gitoxide_repo = repo.gitoxide(executor).await?;
@@ -23,5 +26,9 @@ pub(crate) async fn run<'e, E: Executor<'e, Database = Postgres>>(
         warn!("Failed to detect license for repo id {}: {}", repo.id, err);
     }
 
+    // These walk whole trees or histories, so they run in the background
+    languages::schedule_update(db_pool.clone(), repo.id);
+    last_commits::schedule_fill(db_pool.clone(), repo.id);
+
     Ok(())
 }
@@ -0,0 +1,345 @@
+use crate::repository::Repository;
+use crate::utils::repo_scheduler::RepoScheduler;
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use anyhow::Result;
+use git2::{BranchType, ObjectType, Oid, Repository as Git2Repository, Sort, Tree};
+use log::info;
+use once_cell::sync::Lazy;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use tracing::instrument;
+
+/// Amount of rows inserted into the cache per query
+const INSERT_CHUNK_SIZE: usize = 1000;
+
+static FILLING: Lazy<RepoScheduler> = Lazy::new(|| RepoScheduler::new("fill last commit cache"));
+
+/// Returns the commit which last modified each entry of `directory` at `start`, keyed by their path.
+/// If `recursive` is true, all entries of all subdirectories are included as well.
+///
+/// In contrast to calling [last_commit_for_blob](crate::git::history::last_commit_for_blob) for every entry, this
+/// walks the history only once. Trees which did not change between a commit and its parent are skipped without
+/// looking at their entries, and the walk stops as soon as every entry has been resolved.
+pub(crate) fn last_commits(
+    repo: &Git2Repository,
+    start: Oid,
+    directory: &str,
+    recursive: bool,
+) -> Result<HashMap<String, Oid>> {
+    let root = repo.find_commit(start)?.tree()?;
+
+    let tree = if directory.is_empty() {
+        root
+    } else {
+        match root
+            .get_path(Path::new(directory))?
+            .to_object(repo)?
+            .into_tree()
+        {
+            Ok(tree) => tree,
+            Err(_) => return Ok(HashMap::new()), // Not a directory
+        }
+    };
+
+    let mut unresolved = BTreeSet::<String>::new();
+    collect_paths(repo, &tree, directory, recursive, &mut unresolved)?;
+
+    let mut results = HashMap::with_capacity(unresolved.len());
+
+    let mut rev_walk = repo.revwalk()?;
+    rev_walk.set_sorting(Sort::TIME)?;
+    rev_walk.push(start)?;
+
+    for result in rev_walk {
+        if unresolved.is_empty() {
+            break;
+        }
+
+        let commit_oid = result?;
+        let commit = repo.find_commit(commit_oid)?;
+        let tree = commit.tree()?;
+
+        // Same as `commits_for_blob`, changes are determined by comparing against the first parent
+        let previous_tree = if commit.parent_count() > 0 {
+            Some(commit.parent(0)?.tree()?)
+        } else {
+            None
+        };
+
+        resolve_changes(
+            repo,
+            &tree,
+            previous_tree.as_ref(),
+            "",
+            commit_oid,
+            &mut unresolved,
+            &mut results,
+        )?;
+    }
+
+    Ok(results)
+}
+
+fn collect_paths(
+    repo: &Git2Repository,
+    tree: &Tree<'_>,
+    directory: &str,
+    recursive: bool,
+    paths: &mut BTreeSet<String>,
+) -> Result<()> {
+    for entry in tree.iter() {
+        let name = match entry.name() {
+            Some(name) => name,
+            None => continue,
+        };
+
+        let path = join_path(directory, name);
+
+        if recursive && entry.kind() == Some(ObjectType::Tree) {
+            let subtree = repo.find_tree(entry.id())?;
+            collect_paths(repo, &subtree, path.as_str(), recursive, paths)?;
+        }
+
+        paths.insert(path);
+    }
+
+    Ok(())
+}
+
+fn resolve_changes(
+    repo: &Git2Repository,
+    tree: &Tree<'_>,
+    previous_tree: Option<&Tree<'_>>,
+    directory: &str,
+    commit: Oid,
+    unresolved: &mut BTreeSet<String>,
+    results: &mut HashMap<String, Oid>,
+) -> Result<()> {
+    if previous_tree.map_or(false, |previous| previous.id() == tree.id()) {
+        return Ok(());
+    }
+
+    for entry in tree.iter() {
+        let name = match entry.name() {
+            Some(name) => name,
+            None => continue,
+        };
+
+        let path = join_path(directory, name);
+
+        let wanted = unresolved.contains(&path);
+        let descend = has_unresolved_children(unresolved, path.as_str());
+
+        if !wanted && !descend {
+            continue;
+        }
+
+        let previous = previous_tree.and_then(|previous| previous.get_name(name));
+
+        if previous.as_ref().map_or(false, |previous| {
+            previous.id() == entry.id() && previous.filemode() == entry.filemode()
+        }) {
+            continue;
+        }
+
+        if wanted {
+            unresolved.remove(&path);
+            results.insert(path.clone(), commit);
+        }
+
+        if descend && entry.kind() == Some(ObjectType::Tree) {
+            let subtree = repo.find_tree(entry.id())?;
+            let previous_subtree = match previous {
+                Some(previous) if previous.kind() == Some(ObjectType::Tree) => {
+                    Some(repo.find_tree(previous.id())?)
+                }
+                _ => None,
+            };
+
+            resolve_changes(
+                repo,
+                &subtree,
+                previous_subtree.as_ref(),
+                path.as_str(),
+                commit,
+                unresolved,
+                results,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn has_unresolved_children(unresolved: &BTreeSet<String>, path: &str) -> bool {
+    let prefix = format!("{}/", path);
+
+    unresolved
+        .range(prefix.clone()..)
+        .next()
+        .map_or(false, |next| next.starts_with(prefix.as_str()))
+}
+
+fn join_path(directory: &str, name: &str) -> String {
+    if directory.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{}/{}", directory, name)
+    }
+}
+
+/// Returns the cached last commits of `paths` at `revision`, or `None` if at least one of them is not cached yet
+pub(crate) async fn cached_last_commits<'e, E: Executor<'e, Database = Postgres>>(
+    repo_id: i32,
+    revision: &str,
+    paths: &[String],
+    executor: E,
+) -> Result<Option<HashMap<String, Oid>>> {
+    let rows = sqlx::query_as::<_, (String, String)>(
+        "select path, last_commit from last_commits where repo = $1 and revision = $2 and path = any($3)",
+    )
+    .bind(repo_id)
+    .bind(revision)
+    .bind(paths)
+    .fetch_all(executor)
+    .await?;
+
+    if rows.len() < paths.len() {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        rows.into_iter()
+            .map(|(path, oid)| Ok((path, Oid::from_str(oid.as_str())?)))
+            .collect::<Result<HashMap<_, _>>>()?,
+    ))
+}
+
+pub(crate) async fn store_last_commits(
+    repo_id: i32,
+    revision: &str,
+    last_commits: &HashMap<String, Oid>,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<()> {
+    let entries = last_commits.iter().collect::<Vec<_>>();
+
+    for chunk in entries.chunks(INSERT_CHUNK_SIZE) {
+        let paths = chunk
+            .iter()
+            .map(|(path, _)| (*path).to_owned())
+            .collect::<Vec<_>>();
+        let commits = chunk
+            .iter()
+            .map(|(_, oid)| oid.to_string())
+            .collect::<Vec<_>>();
+
+        sqlx::query(
+            "insert into last_commits (repo, revision, path, last_commit) \
+            select $1, $2, * from unnest($3::text[], $4::varchar[]) on conflict do nothing",
+        )
+        .bind(repo_id)
+        .bind(revision)
+        .bind(&paths)
+        .bind(&commits)
+        .execute(&mut *transaction)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Fills the last commit cache for the heads of all branches of a repository in the background.
+/// Cached entries of revisions which are no longer a branch head get removed.
+///
+/// If the cache of the repository is already being filled, it gets filled again after the current run finishes.
+pub(crate) fn schedule_fill(db_pool: PgPool, repo_id: i32) {
+    FILLING.schedule(db_pool, repo_id, |db_pool, repo_id| async move {
+        fill_cache(&db_pool, repo_id).await
+    });
+}
+
+#[instrument(err, skip(db_pool))]
+async fn fill_cache(db_pool: &PgPool, repo_id: i32) -> Result<()> {
+    let mut transaction = db_pool.begin().await?;
+
+    let repo = match sqlx::query_as::<_, Repository>("select * from repositories where id = $1")
+        .bind(repo_id)
+        .fetch_optional(&mut transaction)
+        .await?
+    {
+        Some(repo) => repo,
+        None => return Ok(()), // Repository got deleted in the meantime
+    };
+
+    let path = repo.get_fs_path(&mut transaction).await?;
+    transaction.commit().await?;
+
+    let heads = {
+        let path = path.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<Vec<String>> {
+            let git2_repo = Git2Repository::open(path)?;
+            let mut heads = Vec::new();
+
+            for branch in git2_repo.branches(Some(BranchType::Local))? {
+                let (branch, _) = branch?;
+
+                if let Some(oid) = branch.get().target() {
+                    heads.push(oid.to_string());
+                }
+            }
+
+            heads.sort();
+            heads.dedup();
+
+            Ok(heads)
+        })
+        .await??
+    };
+
+    sqlx::query("delete from last_commits where repo = $1 and revision <> all($2)")
+        .bind(repo.id)
+        .bind(&heads)
+        .execute(db_pool)
+        .await?;
+
+    for head in heads.iter() {
+        // The empty path (the root directory itself) is only stored once the whole tree has been filled
+        let (filled,): (bool,) = sqlx::query_as(
+            "select exists(select 1 from last_commits where repo = $1 and revision = $2 and path = '')",
+        )
+        .bind(repo.id)
+        .bind(head)
+        .fetch_one(db_pool)
+        .await?;
+
+        if filled {
+            continue;
+        }
+
+        let path = path.clone();
+        let start = Oid::from_str(head.as_str())?;
+
+        let mut last_commits = tokio::task::spawn_blocking(move || {
+            let git2_repo = Git2Repository::open(path)?;
+            last_commits(&git2_repo, start, "", true)
+        })
+        .await??;
+
+        last_commits.insert(String::new(), start);
+
+        let mut transaction = db_pool.begin().await?;
+        store_last_commits(repo.id, head.as_str(), &last_commits, &mut transaction).await?;
+        transaction.commit().await?;
+    }
+
+    info!(
+        "Filled last commit cache for repo id {} ({} branch heads)",
+        repo.id,
+        heads.len()
+    );
+
+    Ok(())
+}
@@ -1,15 +1,20 @@
 use git_repository::hash::Kind;
 
+pub(crate) mod archive;
+pub(crate) mod attributes;
 pub(crate) mod basic_auth;
+pub(crate) mod blame;
 pub(crate) mod capabilities;
 pub(crate) mod fetch;
 pub(crate) mod history;
 pub(crate) mod hooks;
 pub(crate) mod io;
+pub(crate) mod last_commits;
 pub(crate) mod ls_refs;
 pub(crate) mod pack;
 pub(crate) mod receive_pack;
 pub(crate) mod ref_update;
+pub(crate) mod refs;
 pub(crate) mod utils;
 pub(crate) mod write;
 
@@ -18,7 +18,10 @@ use tracing::instrument;
 
 /// Returns path to index file, pack file and temporary dir.
 /// Ensure that the third tuple argument, the temporary dir, is alive for the whole duration of your usage.
-/// It being dropped results in the index and pack file to be deleted and thus the paths becoming invalid
+/// It being dropped results in the index and pack file to be deleted and thus the paths becoming invalid.
+///
+/// The files are written into the `pack` directory of the temporary dir, so it can be added as an alternate object
+/// directory in order to read the received objects without writing them into the repository
 #[instrument(err, skip(data, executor))]
 pub(crate) async fn read<'e, E: Executor<'e, Database = Postgres>>(
     data: &[u8],
@@ -56,9 +59,12 @@ pub(crate) async fn write_to_fs<'e, E: Executor<'e, Database = Postgres>>(
 
     let buf_reader = BufReader::new(data);
 
+    let pack_dir = temp_dir.path().join("pack");
+    std::fs::create_dir(&pack_dir)?;
+
     let bundle = Bundle::write_to_directory(
         buf_reader,
-        Some(&temp_dir),
+        Some(&pack_dir),
         progress::Discard,
         &AtomicBool::new(false), // The Actix runtime (+ tokio) handles timeouts for us
         Some(Box::new(move |oid, buffer| {
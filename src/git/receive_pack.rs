@@ -14,6 +14,7 @@ use std::sync::Arc;
 
 use anyhow::{anyhow, Result};
 use bstr::BString;
+use git2::Repository as Git2Repository;
 use git_repository::actor::Signature;
 use git_repository::lock::acquire::Fail;
 use git_repository::objs::{CommitRef, Kind};
@@ -35,7 +36,6 @@ pub(crate) async fn process_create_update(
     writer: &mut GitWriter,
     index_path: Option<&PathBuf>,
     pack_path: Option<&PathBuf>,
-    raw_pack: &[u8],
 ) -> Result<()> {
     assert!(ref_update.new.is_some());
 
@@ -131,23 +131,17 @@ pub(crate) async fn process_create_update(
             .commit(&Signature::from(commit.committer))?;
     }
 
-    // # libgit2 zone
-    // This block writes the payload into the repo odb
-    {
-        let git2_repo = repo.libgit2(&mut transaction).await?;
+    report_status(ref_update, None, writer).await
+}
 
-        let odb = git2_repo.odb()?;
-        let mut pack_writer = odb.packwriter()?;
+/// Writes a received pack file into the object database of the repository.
+/// Needs to be done once before processing the ref updates pointing into it
+pub(crate) fn write_pack(git2_repo: &Git2Repository, raw_pack: &[u8]) -> Result<()> {
+    let odb = git2_repo.odb()?;
+    let mut pack_writer = odb.packwriter()?;
 
-        pack_writer.write_all(raw_pack)?;
-        pack_writer.commit()?;
-    }
-
-    if ref_update.report_status || ref_update.report_status_v2 {
-        writer
-            .write_text_sideband_pktline(Band::Data, format!("ok {}", ref_update.target_ref))
-            .await?;
-    }
+    pack_writer.write_all(raw_pack)?;
+    pack_writer.commit()?;
 
     Ok(())
 }
@@ -189,10 +183,23 @@ pub(crate) async fn process_delete<'e, E: Executor<'e, Database = Postgres>>(
         })?
         .commit(&Signature::gitarena_default())?;
 
+    report_status(ref_update, None, writer).await
+}
+
+/// Reports the result of a ref update to the client if it asked for it using the `report-status` capability.
+/// A `rejection` reason results in a `ng` line, meaning the ref has been left untouched
+pub(crate) async fn report_status(
+    ref_update: &RefUpdate,
+    rejection: Option<&str>,
+    writer: &mut GitWriter,
+) -> Result<()> {
     if ref_update.report_status || ref_update.report_status_v2 {
-        writer
-            .write_text_sideband_pktline(Band::Data, format!("ok {}", ref_update.target_ref))
-            .await?;
+        let line = match rejection {
+            Some(reason) => format!("ng {} {}", ref_update.target_ref, reason),
+            None => format!("ok {}", ref_update.target_ref),
+        };
+
+        writer.write_text_sideband_pktline(Band::Data, line).await?;
     }
 
     Ok(())
@@ -0,0 +1,120 @@
+use anyhow::Result;
+use git2::{BranchType, ObjectType, Oid, Repository as Git2Repository};
+use tracing::instrument;
+
+/// Branch or tag as listed on the branches and tags pages
+#[derive(Debug)]
+pub(crate) struct RefSummary {
+    /// Short name without the `refs/heads/` or `refs/tags/` prefix
+    pub(crate) name: String,
+
+    /// Commit the reference (eventually) points to
+    pub(crate) commit: Oid,
+
+    /// Annotated tag object, `None` for branches and lightweight tags
+    pub(crate) tag: Option<Oid>,
+
+    /// Time of the commit, or tagger time for annotated tags. Used for sorting.
+    pub(crate) time: i64,
+}
+
+/// Returns all local branches whose name contains `search` (case insensitive), most recently updated first
+#[instrument(err, skip(repo))]
+pub(crate) fn list_branches(repo: &Git2Repository, search: &str) -> Result<Vec<RefSummary>> {
+    let search = search.to_lowercase();
+    let mut results = Vec::<RefSummary>::new();
+
+    for branch in repo.branches(Some(BranchType::Local))? {
+        let (branch, _) = branch?;
+
+        let name = match branch.name()? {
+            Some(name) if name.to_lowercase().contains(search.as_str()) => name.to_owned(),
+            _ => continue,
+        };
+
+        let commit = branch.get().peel_to_commit()?;
+
+        results.push(RefSummary {
+            name,
+            commit: commit.id(),
+            tag: None,
+            time: commit.time().seconds(),
+        });
+    }
+
+    sort_by_time(&mut results);
+
+    Ok(results)
+}
+
+/// Returns all tags whose name contains `search` (case insensitive), most recent first
+#[instrument(err, skip(repo))]
+pub(crate) fn list_tags(repo: &Git2Repository, search: &str) -> Result<Vec<RefSummary>> {
+    let search = search.to_lowercase();
+    let mut results = Vec::<RefSummary>::new();
+
+    for name in repo.tag_names(None)?.iter().flatten() {
+        if !name.to_lowercase().contains(search.as_str()) {
+            continue;
+        }
+
+        // Tags pointing to trees or blobs can't be displayed
+        if let Some(summary) = find_tag(repo, name)? {
+            results.push(summary);
+        }
+    }
+
+    sort_by_time(&mut results);
+
+    Ok(results)
+}
+
+/// Looks up a single tag. Returns `None` if it does not exist or does not point to a commit.
+pub(crate) fn find_tag(repo: &Git2Repository, name: &str) -> Result<Option<RefSummary>> {
+    let reference = match repo.find_reference(format!("refs/tags/{}", name).as_str()) {
+        Ok(reference) => reference,
+        Err(_) => return Ok(None),
+    };
+
+    let commit = match reference.peel(ObjectType::Commit) {
+        Ok(object) => object.peel_to_commit()?,
+        Err(_) => return Ok(None),
+    };
+
+    let tag = reference
+        .target()
+        .and_then(|target| repo.find_tag(target).ok());
+
+    let time = tag
+        .as_ref()
+        .and_then(|tag| tag.tagger())
+        .map_or_else(|| commit.time().seconds(), |tagger| tagger.when().seconds());
+
+    Ok(Some(RefSummary {
+        name: name.to_owned(),
+        commit: commit.id(),
+        tag: tag.map(|tag| tag.id()),
+        time,
+    }))
+}
+
+/// Returns how many commits `commit` is ahead and behind of `base`
+pub(crate) fn ahead_behind(
+    repo: &Git2Repository,
+    commit: Oid,
+    base: Oid,
+) -> Result<(usize, usize)> {
+    if commit == base {
+        return Ok((0, 0));
+    }
+
+    Ok(repo.graph_ahead_behind(commit, base)?)
+}
+
+fn sort_by_time(refs: &mut [RefSummary]) {
+    refs.sort_by(|lhs, rhs| {
+        rhs.time
+            .cmp(&lhs.time)
+            .then_with(|| lhs.name.cmp(&rhs.name))
+    });
+}
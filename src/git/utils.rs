@@ -21,14 +21,7 @@ pub(crate) async fn repo_files_at_ref<'a>(
     buffer: &'a mut Vec<u8>,
 ) -> Result<TreeRef<'a>> {
     match &reference.target {
-        Target::Peeled(object_id) => {
-            let cache = store.to_cache_arc();
-
-            let commit = cache.find_commit(object_id.as_ref(), buffer)?.0.tree();
-            let (tree, _) = cache.find_tree(commit.as_ref(), buffer)?;
-
-            Ok(tree)
-        }
+        Target::Peeled(object_id) => repo_files_at_commit(object_id.as_ref(), store, buffer).await,
         Target::Symbolic(target) => {
             let reference = repo.refs.find_loose(target.to_partial())?;
 
@@ -37,6 +30,20 @@ pub(crate) async fn repo_files_at_ref<'a>(
     }
 }
 
+#[instrument(err, skip(store, buffer))]
+pub(crate) async fn repo_files_at_commit<'a>(
+    commit_id: &oid,
+    store: Arc<Store>,
+    buffer: &'a mut Vec<u8>,
+) -> Result<TreeRef<'a>> {
+    let cache = store.to_cache_arc();
+
+    let commit = cache.find_commit(commit_id, buffer)?.0.tree();
+    let (tree, _) = cache.find_tree(commit.as_ref(), buffer)?;
+
+    Ok(tree)
+}
+
 pub(crate) async fn repo_files_at_head<'a>(
     store: Arc<Store>,
     repo: &'a Repository,
@@ -63,3 +70,9 @@ pub(crate) async fn read_blob_content(oid: &oid, store: Arc<Store>) -> Result<St
 
     Ok(file_content.to_owned())
 }
+
+/// Returns true if the blob content contains a NUL byte. Unlike git, which only checks the first 8000 bytes,
+/// the whole content is checked as text containing NUL bytes can neither be stored in Postgres nor displayed.
+pub(crate) fn is_binary(content: &[u8]) -> bool {
+    content.contains(&0)
+}
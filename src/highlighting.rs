@@ -0,0 +1,161 @@
+use std::path::Path;
+
+use anyhow::Result;
+use log::info;
+use once_cell::sync::{Lazy, OnceCell};
+use regex::Regex;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::{styled_line_to_highlighted_html, IncludeBackground};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+use tracing_unwrap::{OptionExt, ResultExt};
+
+/// Theme used for all highlighted code. Needs to be one of the themes bundled with syntect.
+const THEME_NAME: &str = "base16-ocean.dark";
+
+/// Files bigger than this are displayed without highlighting as highlighting them would take too long
+pub(crate) const MAX_HIGHLIGHT_SIZE: usize = 512_000;
+
+/// Amount of lines at the start and end of a file which get searched for a modeline
+const MODELINE_SEARCH_LINES: usize = 5;
+
+static SYNTAX_SET: OnceCell<SyntaxSet> = OnceCell::new();
+static THEME: OnceCell<Theme> = OnceCell::new();
+
+// vim: set ft=rust: / vi: syntax=python / ex: filetype=sh
+static VIM_MODELINE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?:^|\s)(?:vi|vim|ex)(?:[<=>]?\d+)?:.*?\b(?:ft|filetype|syntax)=([\w+\-]+)")
+        .unwrap_or_log()
+});
+
+// -*- mode: python -*- / -*- python -*-
+static EMACS_MODELINE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"-\*-(?:.*?\bmode:\s*|\s*)([\w+\-]+)[\s;]*.*?-\*-").unwrap_or_log());
+
+pub(crate) async fn init() {
+    let _ = SYNTAX_SET.set(SyntaxSet::load_defaults_newlines());
+
+    let mut themes = ThemeSet::load_defaults();
+    let _ = THEME.set(
+        themes
+            .themes
+            .remove(THEME_NAME)
+            .expect_or_log("Highlighting theme is not bundled with syntect"),
+    );
+
+    info!(
+        "Successfully loaded {} syntax definitions",
+        syntax_set().syntaxes().len()
+    );
+}
+
+pub(crate) fn syntax_set() -> &'static SyntaxSet {
+    SYNTAX_SET.get().unwrap_or_log()
+}
+
+fn theme() -> &'static Theme {
+    THEME.get().unwrap_or_log()
+}
+
+/// Detects the language of a file. Detection happens in the following order:
+///
+/// 1. Vim or Emacs modeline in the first or last five lines
+/// 2. File extension or full file name (e.g. `Makefile`)
+/// 3. First line of the file (e.g. shebang or `<?xml`)
+///
+/// If no language could be detected, plain text is returned.
+pub(crate) fn detect_language(file_name: &str, content: &str) -> &'static SyntaxReference {
+    let syntax_set = syntax_set();
+
+    if let Some(syntax) = modeline_language(content) {
+        return syntax;
+    }
+
+    let path = Path::new(file_name);
+
+    let by_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| syntax_set.find_syntax_by_extension(name));
+    let by_extension = || {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| syntax_set.find_syntax_by_extension(extension))
+    };
+    let by_first_line = || {
+        content
+            .lines()
+            .next()
+            .and_then(|line| syntax_set.find_syntax_by_first_line(line))
+    };
+
+    by_name
+        .or_else(by_extension)
+        .or_else(by_first_line)
+        .unwrap_or_else(|| syntax_set.find_syntax_plain_text())
+}
+
+fn modeline_language(content: &str) -> Option<&'static SyntaxReference> {
+    let lines = content.lines().collect::<Vec<&str>>();
+    let tail_start = lines.len().saturating_sub(MODELINE_SEARCH_LINES);
+
+    lines
+        .iter()
+        .take(MODELINE_SEARCH_LINES)
+        .chain(lines.iter().skip(tail_start.max(MODELINE_SEARCH_LINES)))
+        .find_map(|line| {
+            VIM_MODELINE
+                .captures(line)
+                .or_else(|| EMACS_MODELINE.captures(line))
+                .and_then(|captures| captures.get(1))
+                .and_then(|token| syntax_set().find_syntax_by_token(token.as_str()))
+        })
+}
+
+/// Incremental highlighter which keeps the parser state between lines.
+/// Lines need to be passed in order, but don't need to be known ahead of time (which is the case for diffs).
+pub(crate) struct Highlighter {
+    inner: HighlightLines<'static>,
+}
+
+impl Highlighter {
+    pub(crate) fn new(syntax: &SyntaxReference) -> Highlighter {
+        Highlighter {
+            inner: HighlightLines::new(syntax, theme()),
+        }
+    }
+
+    /// Highlights a single line and returns it as HTML. The returned HTML is already escaped.
+    pub(crate) fn line(&mut self, line: &str) -> Result<String> {
+        // The syntax set was loaded with newline-terminated syntaxes, so make sure the line ends with one
+        let line = if line.ends_with('\n') {
+            line.to_owned()
+        } else {
+            format!("{}\n", line)
+        };
+
+        let regions = self.inner.highlight_line(line.as_str(), syntax_set())?;
+        let html = styled_line_to_highlighted_html(&regions[..], IncludeBackground::No)?;
+
+        // Every line is displayed in its own table row, so the trailing line break is not needed
+        Ok(html.replace(['\n', '\r'], ""))
+    }
+}
+
+/// Highlights the whole content, returning a HTML string for every line.
+///
+/// Files bigger than [MAX_HIGHLIGHT_SIZE] are treated as plain text.
+pub(crate) fn highlight_lines(file_name: &str, content: &str) -> Result<Vec<String>> {
+    let syntax = if content.len() > MAX_HIGHLIGHT_SIZE {
+        syntax_set().find_syntax_plain_text()
+    } else {
+        detect_language(file_name, content)
+    };
+
+    let mut highlighter = Highlighter::new(syntax);
+
+    LinesWithEndings::from(content)
+        .map(|line| highlighter.line(line))
+        .collect()
+}
@@ -0,0 +1,446 @@
+//! Incoming emails are delivered by the mail server into a [Maildir](https://cr.yp.to/proto/maildir.html) which gets polled.
+//!
+//! Every user gets their own address per repository (to open issues) and per issue (to reply to it).
+//! These addresses are the configured `mail.incoming.address` with a signed token in the detail part of the local part,
+//! e.g. `incoming+i42-7-<signature>@example.com` for replies to the issue with id 42 by the user with id 7.
+//! The sender additionally needs to match one of the verified email addresses of that user.
+
+use crate::config::{get_optional_setting, get_setting};
+use crate::issue::{Issue, IssueComment};
+use crate::privileges::privilege;
+use crate::repository::Repository;
+use crate::user::User;
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use hmac::{Hmac, Mac};
+use log::{info, warn};
+use mail_parser::{Addr, HeaderValue, Message, MessageParser, MessagePart, MimeHeaders, PartType};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sha2::Sha256;
+use sqlx::{PgPool, Postgres, Transaction};
+use tokio::fs;
+use tracing::instrument;
+use tracing_unwrap::ResultExt;
+
+/// Interval in which the Maildir gets checked for new emails
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Amount of signature bytes included in a token
+const SIGNATURE_LENGTH: usize = 16;
+
+// Attribution line most mail clients put in front of the quoted message, e.g. `On Mon, 1 Jan 2024, Jane <jane@example.com> wrote:`
+static ATTRIBUTION_LINE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^On\s.+\swrote:\s*$").unwrap_or_log());
+
+// Prefixes added by mail clients to the subject of replies and forwards
+static SUBJECT_PREFIX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)^\s*(?:(?:re|fwd?|aw|wg)\s*:\s*)+").unwrap_or_log());
+
+/// What an incoming email addressed to a token does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MailTarget {
+    /// Opens a new issue in the repository with this id
+    Repository(i32),
+    /// Replies to the issue with this id
+    Issue(i32),
+}
+
+impl Display for MailTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            MailTarget::Repository(id) => write!(f, "r{}", id),
+            MailTarget::Issue(id) => write!(f, "i{}", id),
+        }
+    }
+}
+
+impl MailTarget {
+    fn parse(input: &str) -> Option<MailTarget> {
+        let id = input.get(1..)?.parse::<i32>().ok()?;
+
+        match input.chars().next()? {
+            'r' => Some(MailTarget::Repository(id)),
+            'i' => Some(MailTarget::Issue(id)),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the address `user` can send emails to in order to act on `target`,
+/// `None` if incoming emails are disabled or no address is configured
+pub(crate) async fn reply_address(
+    target: MailTarget,
+    user: &User,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Option<String>> {
+    if !get_setting::<bool, _>("mail.incoming.enabled", &mut *transaction).await? {
+        return Ok(None);
+    }
+
+    let address = match get_optional_setting::<String, _>(
+        "mail.incoming.address",
+        &mut *transaction,
+    )
+    .await?
+    {
+        Some(address) => address,
+        None => return Ok(None),
+    };
+    let secret = get_setting::<String, _>("secret", &mut *transaction).await?;
+
+    let (local, domain) = address
+        .split_once('@')
+        .ok_or_else(|| anyhow!("mail.incoming.address is not a valid email address"))?;
+
+    Ok(Some(format!(
+        "{}+{}@{}",
+        local,
+        sign(target, user.id, secret.as_str())?,
+        domain
+    )))
+}
+
+/// Creates a token in the format `<target>-<user id>-<signature>`
+fn sign(target: MailTarget, user_id: i32, secret: &str) -> Result<String> {
+    let payload = format!("{}-{}", target, user_id);
+    let signature = mac(payload.as_str(), secret)?.finalize().into_bytes();
+
+    Ok(format!(
+        "{}-{}",
+        payload,
+        hex::encode(&signature[..SIGNATURE_LENGTH])
+    ))
+}
+
+/// Returns the target and user id of a token if its signature is valid
+fn verify(token: &str, secret: &str) -> Option<(MailTarget, i32)> {
+    let (payload, signature) = token.rsplit_once('-')?;
+    let (target, user_id) = payload.split_once('-')?;
+
+    let signature = hex::decode(signature).ok()?;
+
+    if signature.len() != SIGNATURE_LENGTH {
+        return None;
+    }
+
+    mac(payload, secret)
+        .ok()?
+        .verify_truncated_left(signature.as_slice())
+        .ok()?;
+
+    Some((MailTarget::parse(target)?, user_id.parse().ok()?))
+}
+
+fn mac(payload: &str, secret: &str) -> Result<Hmac<Sha256>> {
+    // The instance secret is also used for cookies, so the key is bound to this purpose
+    let mut mac = Hmac::<Sha256>::new_from_slice(format!("incoming-mail:{}", secret).as_bytes())
+        .map_err(|err| anyhow!("Failed to create HMAC: {}", err))?;
+    mac.update(payload.as_bytes());
+
+    Ok(mac)
+}
+
+/// Spawns a task which periodically processes new emails in the Maildir configured in `mail.incoming.maildir`
+pub(crate) fn spawn_poller(db_pool: PgPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(err) = poll(&db_pool).await {
+                warn!("Failed to process incoming emails: {}", err);
+            }
+        }
+    });
+}
+
+async fn poll(db_pool: &PgPool) -> Result<()> {
+    let mut transaction = db_pool.begin().await?;
+
+    if !get_setting::<bool, _>("mail.incoming.enabled", &mut transaction).await? {
+        return Ok(());
+    }
+
+    let maildir =
+        match get_optional_setting::<String, _>("mail.incoming.maildir", &mut transaction).await? {
+            Some(maildir) => maildir,
+            None => return Ok(()),
+        };
+    let address = get_optional_setting::<String, _>("mail.incoming.address", &mut transaction)
+        .await?
+        .ok_or_else(|| anyhow!("mail.incoming.address needs to be set to receive emails"))?;
+    let secret = get_setting::<String, _>("secret", &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    let new_dir = Path::new(maildir.as_str()).join("new");
+    let cur_dir = Path::new(maildir.as_str()).join("cur");
+
+    let mut entries = fs::read_dir(&new_dir)
+        .await
+        .with_context(|| format!("Failed to read Maildir {}", new_dir.display()))?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+
+        // Files starting with a dot are not (yet) deliveries
+        if name.starts_with('.') {
+            continue;
+        }
+
+        // Emails are moved to `cur` and marked as seen before processing them, regardless of whether they get accepted.
+        // This guarantees an email is never processed twice, even if processing fails halfway through
+        let path = cur_dir.join(format!("{}:2,S", name));
+
+        if let Err(err) = fs::rename(entry.path(), &path).await {
+            warn!("Failed to move incoming email {} to cur: {}", &name, err);
+            continue;
+        }
+
+        let raw = match fs::read(&path).await {
+            Ok(raw) => raw,
+            Err(err) => {
+                warn!("Failed to read incoming email {}: {}", &name, err);
+                continue;
+            }
+        };
+
+        if let Err(err) = process(raw.as_slice(), address.as_str(), secret.as_str(), db_pool).await
+        {
+            warn!("Rejected incoming email {}: {}", &name, err);
+        }
+    }
+
+    Ok(())
+}
+
+#[instrument(err, skip(raw, secret, db_pool))]
+async fn process(raw: &[u8], address: &str, secret: &str, db_pool: &PgPool) -> Result<()> {
+    let mail = MessageParser::default()
+        .parse(raw)
+        .ok_or_else(|| anyhow!("Failed to parse email"))?;
+
+    let (target, user_id) = recipient_token(&mail, address)
+        .and_then(|token| verify(token.as_str(), secret))
+        .ok_or_else(|| anyhow!("No recipient with a valid token"))?;
+
+    let sender = mail
+        .from()
+        .and_then(|from| from.first())
+        .and_then(Addr::address)
+        .map(str::to_owned)
+        .ok_or_else(|| anyhow!("Missing sender"))?;
+
+    let mut transaction = db_pool.begin().await?;
+
+    let user = sqlx::query_as::<_, User>("select * from users where id = $1 limit 1")
+        .bind(user_id)
+        .fetch_optional(&mut transaction)
+        .await?
+        .ok_or_else(|| anyhow!("User id {} does not exist", user_id))?;
+
+    if user.disabled {
+        bail!("User {} is disabled", &user.username);
+    }
+
+    let (verified,): (bool,) = sqlx::query_as(
+        "select exists(select 1 from emails where owner = $1 and lower(email) = lower($2) and verified_at is not null limit 1)",
+    )
+    .bind(user.id)
+    .bind(sender.as_str())
+    .fetch_one(&mut transaction)
+    .await?;
+
+    if !verified {
+        bail!(
+            "Sender {} is not a verified email address of {}",
+            sender,
+            &user.username
+        );
+    }
+
+    let body = strip_quoted_reply(text_body(&mail)?.as_str());
+
+    match target {
+        MailTarget::Repository(repo_id) => {
+            let repo = find_repository(repo_id, &user, &mut transaction).await?;
+
+            let subject = mail.subject().unwrap_or_default();
+            let title = SUBJECT_PREFIX.replace(subject, "");
+            let title = title.trim();
+
+            if title.is_empty() || title.len() > 256 {
+                bail!("Subject must be between 1 and 256 characters long");
+            }
+
+            let issue = Issue::create(
+                repo.id,
+                &user,
+                title,
+                body.as_str(),
+                false,
+                &mut transaction,
+            )
+            .await?;
+
+            info!(
+                "New issue #{} created in repo id {} by {} via email",
+                issue.index, repo.id, &user.username
+            );
+        }
+        MailTarget::Issue(issue_id) => {
+            let issue = sqlx::query_as::<_, Issue>("select * from issues where id = $1 limit 1")
+                .bind(issue_id)
+                .fetch_optional(&mut transaction)
+                .await?
+                .ok_or_else(|| anyhow!("Issue id {} does not exist", issue_id))?;
+
+            let repo = find_repository(issue.repo, &user, &mut transaction).await?;
+            let can_manage =
+                privilege::check_manage_issues(&repo, Some(&user), &mut transaction).await?;
+
+            if !issue.is_visible_to(Some(&user), can_manage) {
+                bail!("Issue id {} is not visible to {}", issue.id, &user.username);
+            }
+
+            if !issue.accepts_comments_from(can_manage) {
+                bail!("Issue id {} is locked", issue.id);
+            }
+
+            if body.is_empty() {
+                bail!("Comment may not be empty");
+            }
+
+            IssueComment::create(&issue, None, &user, body.as_str(), &mut transaction).await?;
+
+            info!(
+                "New comment on issue id {} by {} via email",
+                issue.id, &user.username
+            );
+        }
+    }
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Looks up a repository which `user` is allowed to create issues and comments in
+async fn find_repository(
+    repo_id: i32,
+    user: &User,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Repository> {
+    let repo = sqlx::query_as::<_, Repository>("select * from repositories where id = $1 limit 1")
+        .bind(repo_id)
+        .fetch_optional(&mut *transaction)
+        .await?
+        .ok_or_else(|| anyhow!("Repository id {} does not exist", repo_id))?;
+
+    if !privilege::check_access(&repo, Some(user), &mut *transaction).await? {
+        bail!(
+            "{} has no access to repository id {}",
+            &user.username,
+            repo.id
+        );
+    }
+
+    if repo.archived {
+        bail!("Repository id {} is archived", repo.id);
+    }
+
+    Ok(repo)
+}
+
+/// Returns the token of the first recipient which is a sub-address of `address` (`local+token@domain`)
+fn recipient_token(mail: &Message, address: &str) -> Option<String> {
+    let (local, domain) = address.split_once('@')?;
+
+    // Mail servers record the envelope recipient in `Delivered-To` or `X-Original-To`, which also covers Bcc
+    let mut recipients = ["Delivered-To", "X-Original-To"]
+        .iter()
+        .flat_map(|header| mail.header_values(*header))
+        .filter_map(HeaderValue::as_text)
+        .map(|value| {
+            value
+                .trim()
+                .trim_start_matches('<')
+                .trim_end_matches('>')
+                .to_owned()
+        })
+        .collect::<Vec<_>>();
+
+    for list in [mail.to(), mail.cc()].into_iter().flatten() {
+        recipients.extend(list.iter().filter_map(Addr::address).map(str::to_owned));
+    }
+
+    recipients.into_iter().find_map(|recipient| {
+        let (recipient_local, recipient_domain) = recipient.rsplit_once('@')?;
+        let (base, token) = recipient_local.split_once('+')?;
+
+        (base.eq_ignore_ascii_case(local) && recipient_domain.eq_ignore_ascii_case(domain))
+            .then(|| token.to_owned())
+    })
+}
+
+/// Returns the `text/plain` body of an email, looking into multipart emails if needed
+fn text_body(mail: &Message) -> Result<String> {
+    (0..mail.text_body_count())
+        .filter_map(|index| mail.text_part(index))
+        .filter(|part| is_plain_text(part))
+        .find_map(|part| match &part.body {
+            PartType::Text(text) => Some(text.to_string()),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow!("Email has no plain text body"))
+}
+
+/// HTML parts are skipped instead of being converted, parts without a content type are plain text
+fn is_plain_text(part: &MessagePart) -> bool {
+    part.content_type().map_or(true, |content_type| {
+        content_type.ctype().eq_ignore_ascii_case("text")
+            && content_type
+                .subtype()
+                .map_or(false, |subtype| subtype.eq_ignore_ascii_case("plain"))
+    })
+}
+
+/// Removes the quoted message, attribution line and signature which mail clients add to replies
+fn strip_quoted_reply(body: &str) -> String {
+    let lines = body.lines().collect::<Vec<_>>();
+    let mut kept = Vec::<&str>::with_capacity(lines.len());
+
+    for (index, line) in lines.iter().enumerate() {
+        let trimmed = line.trim_end();
+
+        if trimmed == "--" && line.starts_with("-- ")
+            || trimmed == "-----Original Message-----"
+            || ATTRIBUTION_LINE.is_match(trimmed)
+        {
+            break;
+        }
+
+        // Some clients wrap long attribution lines
+        if trimmed.starts_with("On ") {
+            if let Some(next) = lines.get(index + 1) {
+                if ATTRIBUTION_LINE.is_match(format!("{} {}", trimmed, next.trim()).as_str()) {
+                    break;
+                }
+            }
+        }
+
+        if trimmed.starts_with('>') {
+            continue;
+        }
+
+        kept.push(trimmed);
+    }
+
+    kept.join("\n").trim().to_owned()
+}
@@ -1,31 +1,495 @@
-use chrono::serde::ts_seconds;
+use crate::board;
+use crate::user::User;
+
+use anyhow::Result;
+use chrono::serde::{ts_seconds, ts_seconds_option};
 use chrono::{DateTime, Utc};
 use derive_more::Display;
+use gitarena_common::database::models::AccessLevel;
 use serde::Serialize;
-use sqlx::FromRow;
+use sqlx::{Executor, FromRow, Postgres, Transaction, Type};
 
-/// Contains issues and their corresponding data; Does *not* contain the actual text content
+/// Contains issues and their corresponding data; The text content is stored in [revisions](IssueRevision)
 #[derive(FromRow, Display, Debug, Serialize)]
 #[display(fmt = "{}", title)]
 pub(crate) struct Issue {
     pub(crate) id: i32,
 
-    repo: i32,
-    index: i32, // Issue # per repository (not global instance)
+    pub(crate) repo: i32,
+    pub(crate) index: i32, // Issue # per repository (not global instance)
 
     pub(crate) author: i32,
-    title: String,
+    pub(crate) title: String,
 
     pub(crate) milestone: Option<i32>,
     pub(crate) labels: Vec<i32>,
     pub(crate) assignees: Vec<i32>,
 
-    closed: bool,
-    confidential: bool,
-    locked: bool,
+    pub(crate) closed: bool,
+    pub(crate) confidential: bool,
+    pub(crate) locked: bool,
+
+    #[serde(with = "ts_seconds")]
+    pub(crate) created_at: DateTime<Utc>,
+    #[serde(with = "ts_seconds")]
+    pub(crate) updated_at: DateTime<Utc>,
+}
+
+impl Issue {
+    pub(crate) async fn find<'e, E: Executor<'e, Database = Postgres>>(
+        repo_id: i32,
+        index: i32,
+        executor: E,
+    ) -> Result<Option<Issue>> {
+        Ok(sqlx::query_as::<_, Issue>(
+            "select * from issues where repo = $1 and index = $2 limit 1",
+        )
+        .bind(repo_id)
+        .bind(index)
+        .fetch_optional(executor)
+        .await?)
+    }
+
+    /// Returns the amount of open issues in a repository visible to `viewer`
+    pub(crate) async fn count_open<'e, E: Executor<'e, Database = Postgres>>(
+        repo_id: i32,
+        viewer: Option<&User>,
+        can_manage_issues: bool,
+        executor: E,
+    ) -> Result<i64> {
+        let (count,): (i64,) = sqlx::query_as(&format!(
+            "select count(*) from issues where repo = $1 and closed = false and {}",
+            visibility_condition("$2", "$3")
+        ))
+        .bind(repo_id)
+        .bind(can_manage_issues)
+        .bind(viewer.map(|user| user.id))
+        .fetch_one(executor)
+        .await?;
+
+        Ok(count)
+    }
+
+    /// Creates a new issue alongside its first revision and adds it to boards which collect opened issues.
+    ///
+    /// The index is taken from `repositories.issue_counter`. Incrementing it locks the repository row until
+    /// `transaction` ends, so concurrent creations in the same repository are serialized and never share an index.
+    pub(crate) async fn create(
+        repo_id: i32,
+        author: &User,
+        title: &str,
+        body: &str,
+        confidential: bool,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Issue> {
+        let (index,): (i32,) = sqlx::query_as(
+            "update repositories set issue_counter = issue_counter + 1 where id = $1 returning issue_counter",
+        )
+        .bind(repo_id)
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        let issue = sqlx::query_as::<_, Issue>(
+            "insert into issues (repo, index, author, title, confidential) values ($1, $2, $3, $4, $5) returning *",
+        )
+        .bind(repo_id)
+        .bind(index)
+        .bind(author.id)
+        .bind(title)
+        .bind(confidential)
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        issue
+            .add_revision(author, title, body, &mut *transaction)
+            .await?;
+        issue
+            .record_event(
+                Some(author.id),
+                IssueEventType::Opened,
+                EventDetails::default(),
+                &mut *transaction,
+            )
+            .await?;
+
+        board::automate(
+            issue,
+            IssueEventType::Opened,
+            Some(author.id),
+            &mut *transaction,
+        )
+        .await
+    }
+
+    /// Stores a new revision of the title and body and updates the full text search index accordingly.
+    /// The title of the issue itself is not updated
+    pub(crate) async fn add_revision<'e, E: Executor<'e, Database = Postgres>>(
+        &self,
+        author: &User,
+        title: &str,
+        body: &str,
+        executor: E,
+    ) -> Result<IssueRevision> {
+        Ok(sqlx::query_as::<_, IssueRevision>(
+            "with search as (update issues set search_vector = \
+                setweight(to_tsvector('simple', $3), 'A') || setweight(to_tsvector('simple', $4), 'B') \
+                where id = $1) \
+            insert into issue_revisions (issue, author, title, body) values ($1, $2, $3, $4) returning *",
+        )
+        .bind(self.id)
+        .bind(author.id)
+        .bind(title)
+        .bind(body)
+        .fetch_one(executor)
+        .await?)
+    }
+
+    /// Returns the current content of this issue
+    pub(crate) async fn current_revision<'e, E: Executor<'e, Database = Postgres>>(
+        &self,
+        executor: E,
+    ) -> Result<Option<IssueRevision>> {
+        Ok(sqlx::query_as::<_, IssueRevision>(
+            "select * from issue_revisions where issue = $1 order by id desc limit 1",
+        )
+        .bind(self.id)
+        .fetch_optional(executor)
+        .await?)
+    }
+
+    /// Returns all revisions of this issue, oldest first
+    pub(crate) async fn revisions<'e, E: Executor<'e, Database = Postgres>>(
+        &self,
+        executor: E,
+    ) -> Result<Vec<IssueRevision>> {
+        Ok(sqlx::query_as::<_, IssueRevision>(
+            "select * from issue_revisions where issue = $1 order by id",
+        )
+        .bind(self.id)
+        .fetch_all(executor)
+        .await?)
+    }
+
+    /// Adds an entry to the timeline of this issue
+    pub(crate) async fn record_event<'e, E: Executor<'e, Database = Postgres>>(
+        &self,
+        actor: Option<i32>,
+        event: IssueEventType,
+        details: EventDetails,
+        executor: E,
+    ) -> Result<()> {
+        sqlx::query(
+            "insert into issue_events (issue, actor, event, label, milestone, assignee, commit, commit_repo) \
+            values ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(self.id)
+        .bind(actor)
+        .bind(event)
+        .bind(details.label)
+        .bind(details.milestone)
+        .bind(details.assignee)
+        .bind(details.commit)
+        .bind(details.commit_repo)
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    pub(crate) async fn events<'e, E: Executor<'e, Database = Postgres>>(
+        &self,
+        executor: E,
+    ) -> Result<Vec<IssueEvent>> {
+        Ok(sqlx::query_as::<_, IssueEvent>(
+            "select * from issue_events where issue = $1 order by id",
+        )
+        .bind(self.id)
+        .fetch_all(executor)
+        .await?)
+    }
+
+    /// Returns all comments including soft deleted ones, oldest first
+    pub(crate) async fn comments<'e, E: Executor<'e, Database = Postgres>>(
+        &self,
+        executor: E,
+    ) -> Result<Vec<IssueComment>> {
+        Ok(sqlx::query_as::<_, IssueComment>(
+            "select * from issue_comments where issue = $1 order by id",
+        )
+        .bind(self.id)
+        .fetch_all(executor)
+        .await?)
+    }
+
+    /// Confidential issues are only visible to their author, their assignees and users which can manage issues
+    /// (repository owner, instance admins and collaborators with an [AccessLevel]
+    /// allowing it). Queries filter using [visibility_condition], which needs to be kept in sync
+    pub(crate) fn is_visible_to(&self, user: Option<&User>, can_manage_issues: bool) -> bool {
+        !self.confidential
+            || can_manage_issues
+            || user.map_or(false, |user| {
+                user.id == self.author || self.assignees.contains(&user.id)
+            })
+    }
+
+    /// Title and body can be edited (and the issue closed or reopened) by its author, unless it is locked,
+    /// and by users which can manage issues
+    pub(crate) fn is_editable_by(&self, user: &User, can_manage_issues: bool) -> bool {
+        can_manage_issues || (!self.locked && user.id == self.author)
+    }
+
+    /// Locked issues only accept comments from users which can manage issues
+    pub(crate) fn accepts_comments_from(&self, can_manage_issues: bool) -> bool {
+        can_manage_issues || !self.locked
+    }
+}
+
+/// SQL condition matching the rows of `issues` visible to a viewer, the counterpart of [Issue::is_visible_to].
+///
+/// `can_manage_issues` and `viewer` are SQL expressions (usually placeholders) evaluating to whether the viewer
+/// can manage issues of the repository and to the user id of the viewer (`null` for anonymous users)
+pub(crate) fn visibility_condition(can_manage_issues: &str, viewer: &str) -> String {
+    format!(
+        "(issues.confidential = false or {manage} or issues.author = {viewer} or {viewer} = any(issues.assignees))",
+        manage = can_manage_issues,
+        viewer = viewer
+    )
+}
+
+/// SQL condition matching if `viewer` can manage issues of the joined `repositories` row, the counterpart of
+/// [check_manage_issues](crate::privileges::privilege::check_manage_issues) for queries spanning multiple repositories.
+///
+/// `viewer` is a SQL expression evaluating to the user id of the viewer. Instance admins are not taken into account.
+pub(crate) fn manage_issues_condition(viewer: &str) -> String {
+    let access_levels = AccessLevel::ALL
+        .iter()
+        .filter(|access_level| access_level.can_manage_issues())
+        .map(|access_level| format!("'{}'", access_level.as_str()))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!(
+        "(repositories.owner = {viewer} or exists(select 1 from privileges \
+        where privileges.repo_id = repositories.id and privileges.user_id = {viewer} \
+        and privileges.access_level in ({access_levels})))",
+        viewer = viewer,
+        access_levels = access_levels
+    )
+}
+
+/// Title and body of an issue at one point in time
+#[derive(FromRow, Debug, Serialize)]
+pub(crate) struct IssueRevision {
+    pub(crate) id: i32,
+    pub(crate) issue: i32,
+
+    /// `None` if the user who made the edit has since been deleted
+    pub(crate) author: Option<i32>,
+
+    pub(crate) title: String,
+    pub(crate) body: String,
+
+    #[serde(with = "ts_seconds")]
+    pub(crate) created_at: DateTime<Utc>,
+}
+
+#[derive(Type, Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[sqlx(type_name = "issue_event_type", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum IssueEventType {
+    Opened,
+    Closed,
+    Reopened,
+    Labeled,
+    Unlabeled,
+    Assigned,
+    Unassigned,
+    Milestoned,
+    Demilestoned,
+    /// Issue was mentioned in a commit message
+    Referenced,
+    Locked,
+    Unlocked,
+}
+
+/// System generated entry in the timeline of an issue
+#[derive(FromRow, Debug, Serialize)]
+pub(crate) struct IssueEvent {
+    pub(crate) id: i32,
+    pub(crate) issue: i32,
+
+    /// `None` if the user who caused the event has since been deleted
+    pub(crate) actor: Option<i32>,
+    pub(crate) event: IssueEventType,
+
+    pub(crate) label: Option<i32>,
+    pub(crate) milestone: Option<i32>,
+    pub(crate) assignee: Option<i32>,
+
+    pub(crate) commit: Option<String>,
+    pub(crate) commit_repo: Option<i32>,
 
     #[serde(with = "ts_seconds")]
-    created_at: DateTime<Utc>,
+    pub(crate) created_at: DateTime<Utc>,
+}
+
+/// What an [event](IssueEvent) refers to. Which fields are set depends on the [type](IssueEventType) of the event
+#[derive(Default)]
+pub(crate) struct EventDetails {
+    pub(crate) label: Option<i32>,
+    pub(crate) milestone: Option<i32>,
+    pub(crate) assignee: Option<i32>,
+    pub(crate) commit: Option<String>,
+    pub(crate) commit_repo: Option<i32>,
+}
+
+#[derive(FromRow, Debug, Serialize)]
+pub(crate) struct IssueComment {
+    pub(crate) id: i32,
+    pub(crate) issue: i32,
+
+    /// Top level comment this comment replies to
+    pub(crate) parent: Option<i32>,
+    /// `None` if the author has since been deleted
+    pub(crate) author: Option<i32>,
+
+    pub(crate) body: String,
+
+    #[serde(with = "ts_seconds")]
+    pub(crate) created_at: DateTime<Utc>,
+    #[serde(with = "ts_seconds")]
+    pub(crate) updated_at: DateTime<Utc>,
+
+    #[serde(with = "ts_seconds_option")]
+    pub(crate) deleted_at: Option<DateTime<Utc>>,
+    pub(crate) deleted_by: Option<i32>,
+}
+
+impl IssueComment {
+    pub(crate) async fn find<'e, E: Executor<'e, Database = Postgres>>(
+        issue_id: i32,
+        id: i32,
+        executor: E,
+    ) -> Result<Option<IssueComment>> {
+        Ok(sqlx::query_as::<_, IssueComment>(
+            "select * from issue_comments where issue = $1 and id = $2 limit 1",
+        )
+        .bind(issue_id)
+        .bind(id)
+        .fetch_optional(executor)
+        .await?)
+    }
+
+    /// Creates a new comment alongside its first revision
+    pub(crate) async fn create(
+        issue: &Issue,
+        parent: Option<i32>,
+        author: &User,
+        body: &str,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<IssueComment> {
+        let comment = sqlx::query_as::<_, IssueComment>(
+            "insert into issue_comments (issue, parent, author, body) values ($1, $2, $3, $4) returning *",
+        )
+        .bind(issue.id)
+        .bind(parent)
+        .bind(author.id)
+        .bind(body)
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        comment
+            .add_revision(author, body, &mut *transaction)
+            .await?;
+
+        sqlx::query("update issues set updated_at = current_timestamp where id = $1")
+            .bind(issue.id)
+            .execute(&mut *transaction)
+            .await?;
+
+        Ok(comment)
+    }
+
+    /// Replaces the body and stores it as a new revision
+    pub(crate) async fn edit(
+        &self,
+        editor: &User,
+        body: &str,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<IssueComment> {
+        let comment = sqlx::query_as::<_, IssueComment>(
+            "update issue_comments set body = $1, updated_at = current_timestamp where id = $2 returning *",
+        )
+        .bind(body)
+        .bind(self.id)
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        comment
+            .add_revision(editor, body, &mut *transaction)
+            .await?;
+
+        Ok(comment)
+    }
+
+    async fn add_revision<'e, E: Executor<'e, Database = Postgres>>(
+        &self,
+        author: &User,
+        body: &str,
+        executor: E,
+    ) -> Result<()> {
+        sqlx::query(
+            "insert into issue_comment_revisions (comment, author, body) values ($1, $2, $3)",
+        )
+        .bind(self.id)
+        .bind(author.id)
+        .bind(body)
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns all revisions of this comment, oldest first
+    pub(crate) async fn revisions<'e, E: Executor<'e, Database = Postgres>>(
+        &self,
+        executor: E,
+    ) -> Result<Vec<IssueCommentRevision>> {
+        Ok(sqlx::query_as::<_, IssueCommentRevision>(
+            "select * from issue_comment_revisions where comment = $1 order by id",
+        )
+        .bind(self.id)
+        .fetch_all(executor)
+        .await?)
+    }
+
+    /// Hides the body of this comment. Revisions are kept so moderators can still look at it
+    pub(crate) async fn soft_delete<'e, E: Executor<'e, Database = Postgres>>(
+        &self,
+        moderator: &User,
+        executor: E,
+    ) -> Result<()> {
+        sqlx::query("update issue_comments set deleted_at = current_timestamp, deleted_by = $1 where id = $2")
+            .bind(moderator.id)
+            .bind(self.id)
+            .execute(executor)
+            .await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+}
+
+#[derive(FromRow, Debug, Serialize)]
+pub(crate) struct IssueCommentRevision {
+    pub(crate) id: i32,
+    pub(crate) comment: i32,
+    pub(crate) author: Option<i32>,
+    pub(crate) body: String,
+
     #[serde(with = "ts_seconds")]
-    updated_at: DateTime<Utc>,
+    pub(crate) created_at: DateTime<Utc>,
 }
@@ -0,0 +1,354 @@
+use crate::issue::{visibility_condition, Issue};
+use crate::label::Label;
+use crate::milestone::Milestone;
+use crate::user::User;
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Serialize;
+use sqlx::{Executor, Postgres, Transaction};
+use tracing::instrument;
+
+/// Issue query entered by the user, e.g. `is:open label:bug assignee:@me crash`.
+///
+/// Qualifiers can be mixed into the search term, values containing spaces need to be quoted (`milestone:"1.0 beta"`).
+/// Everything that is not a known qualifier is used for the full text search over titles and bodies.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct IssueQuery {
+    pub(crate) term: String,
+
+    pub(crate) closed: Option<bool>,
+    pub(crate) confidential: Option<bool>,
+    pub(crate) locked: Option<bool>,
+
+    pub(crate) labels: Vec<String>, // Issues need to have all of these labels
+    pub(crate) milestone: Option<String>,
+    pub(crate) assignee: Option<String>, // Username or `@me`
+    pub(crate) author: Option<String>,   // Username or `@me`
+
+    pub(crate) no_labels: bool,
+    pub(crate) no_milestone: bool,
+    pub(crate) no_assignee: bool,
+
+    pub(crate) sort: IssueSort,
+}
+
+impl IssueQuery {
+    pub(crate) fn parse(input: &str) -> IssueQuery {
+        let mut query = IssueQuery::default();
+        let mut term = Vec::<String>::new();
+
+        for token in tokenize(input) {
+            let (qualifier, value) = match token.split_once(':') {
+                Some((qualifier, value)) if !value.is_empty() => (qualifier, value),
+                _ => {
+                    term.push(token);
+                    continue;
+                }
+            };
+
+            match (qualifier, value) {
+                ("is", "open") => query.closed = Some(false),
+                ("is", "closed") => query.closed = Some(true),
+                ("is", "confidential") => query.confidential = Some(true),
+                ("is", "public") => query.confidential = Some(false),
+                ("is", "locked") => query.locked = Some(true),
+                ("is", "unlocked") => query.locked = Some(false),
+                ("label", _) => query.labels.push(value.to_owned()),
+                ("milestone", _) => query.milestone = Some(value.to_owned()),
+                ("assignee", _) => query.assignee = Some(value.to_owned()),
+                ("author", _) => query.author = Some(value.to_owned()),
+                ("no", "label") => query.no_labels = true,
+                ("no", "milestone") => query.no_milestone = true,
+                ("no", "assignee") => query.no_assignee = true,
+                ("sort", _) => match IssueSort::parse(value) {
+                    Some(sort) => query.sort = sort,
+                    None => term.push(token),
+                },
+                _ => term.push(token),
+            }
+        }
+
+        query.term = term.join(" ");
+        query
+    }
+
+    /// Resolves the names used in the qualifiers to their ids.
+    ///
+    /// Returns `None` if a label, milestone or user does not exist, as no issue can match the query in that case.
+    pub(crate) async fn resolve(
+        &self,
+        repo_id: i32,
+        viewer: Option<&User>,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<IssueFilter>> {
+        let mut labels = Vec::<i32>::with_capacity(self.labels.len());
+
+        if !self.labels.is_empty() {
+            let available = Label::all(repo_id, &mut *transaction).await?;
+
+            for name in self.labels.iter() {
+                match available
+                    .iter()
+                    .find(|label| label.name.eq_ignore_ascii_case(name))
+                {
+                    Some(label) => labels.push(label.id),
+                    None => return Ok(None),
+                }
+            }
+        }
+
+        let milestone = match &self.milestone {
+            Some(title) => match Milestone::find_by_title(repo_id, title, &mut *transaction).await?
+            {
+                Some(milestone) => Some(milestone.id),
+                None => return Ok(None),
+            },
+            None => None,
+        };
+
+        let assignee = match &self.assignee {
+            Some(username) => match resolve_user(username, viewer, transaction).await? {
+                Some(id) => Some(id),
+                None => return Ok(None),
+            },
+            None => None,
+        };
+
+        let author = match &self.author {
+            Some(username) => match resolve_user(username, viewer, transaction).await? {
+                Some(id) => Some(id),
+                None => return Ok(None),
+            },
+            None => None,
+        };
+
+        Ok(Some(IssueFilter {
+            term: Some(self.term.trim())
+                .filter(|term| !term.is_empty())
+                .map(str::to_owned),
+            closed: self.closed,
+            confidential: self.confidential,
+            locked: self.locked,
+            labels,
+            milestone,
+            assignee,
+            author,
+            no_labels: self.no_labels,
+            no_milestone: self.no_milestone,
+            no_assignee: self.no_assignee,
+            sort: self.sort,
+        }))
+    }
+}
+
+/// Splits the input at whitespace, except for whitespace inside of double quotes. The quotes themselves are removed
+fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens = Vec::<String>::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+/// Returns the id of the user named `username`, `@me` refers to `viewer`
+async fn resolve_user(
+    username: &str,
+    viewer: Option<&User>,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Option<i32>> {
+    if username == "@me" {
+        return Ok(viewer.map(|user| user.id));
+    }
+
+    Ok(User::find_using_name(username, &mut *transaction)
+        .await
+        .map(|user| user.id))
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum IssueSort {
+    #[default]
+    Created,
+    CreatedAsc,
+    Updated,
+    UpdatedAsc,
+}
+
+impl IssueSort {
+    fn parse(input: &str) -> Option<IssueSort> {
+        Some(match input {
+            "created" | "created-desc" => IssueSort::Created,
+            "created-asc" => IssueSort::CreatedAsc,
+            "updated" | "updated-desc" => IssueSort::Updated,
+            "updated-asc" => IssueSort::UpdatedAsc,
+            _ => return None,
+        })
+    }
+
+    fn column(&self) -> &'static str {
+        match self {
+            IssueSort::Created | IssueSort::CreatedAsc => "created_at",
+            IssueSort::Updated | IssueSort::UpdatedAsc => "updated_at",
+        }
+    }
+
+    fn ascending(&self) -> bool {
+        matches!(self, IssueSort::CreatedAsc | IssueSort::UpdatedAsc)
+    }
+
+    /// Returns the timestamp of `issue` this sort order uses
+    fn key(&self, issue: &Issue) -> DateTime<Utc> {
+        match self {
+            IssueSort::Created | IssueSort::CreatedAsc => issue.created_at,
+            IssueSort::Updated | IssueSort::UpdatedAsc => issue.updated_at,
+        }
+    }
+}
+
+/// [IssueQuery] with all names resolved to ids
+#[derive(Debug)]
+pub(crate) struct IssueFilter {
+    pub(crate) term: Option<String>,
+
+    pub(crate) closed: Option<bool>,
+    pub(crate) confidential: Option<bool>,
+    pub(crate) locked: Option<bool>,
+
+    pub(crate) labels: Vec<i32>,
+    pub(crate) milestone: Option<i32>,
+    pub(crate) assignee: Option<i32>,
+    pub(crate) author: Option<i32>,
+
+    pub(crate) no_labels: bool,
+    pub(crate) no_milestone: bool,
+    pub(crate) no_assignee: bool,
+
+    pub(crate) sort: IssueSort,
+}
+
+/// Position in the issue list for keyset pagination: Sort key (timestamp in microseconds) and issue id of the last issue
+/// of the previous page, formatted as `<timestamp>_<id>`
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct IssueCursor {
+    key: DateTime<Utc>,
+    id: i32,
+}
+
+impl IssueCursor {
+    pub(crate) fn parse(input: &str) -> Option<IssueCursor> {
+        let (micros, id) = input.split_once('_')?;
+        let micros = micros.parse::<i64>().ok()?;
+
+        let key = Utc
+            .timestamp_opt(
+                micros.div_euclid(1_000_000),
+                (micros.rem_euclid(1_000_000) * 1000) as u32,
+            )
+            .single()?;
+
+        Some(IssueCursor {
+            key,
+            id: id.parse().ok()?,
+        })
+    }
+
+    pub(crate) fn after(issue: &Issue, sort: IssueSort) -> IssueCursor {
+        IssueCursor {
+            key: sort.key(issue),
+            id: issue.id,
+        }
+    }
+}
+
+impl Display for IssueCursor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}_{}", self.key.timestamp_micros(), self.id)
+    }
+}
+
+/// Returns up to `limit` issues of a repository matching `filter`, starting after `cursor`.
+///
+/// Confidential issues are only returned to users they are [visible](Issue::is_visible_to) to.
+#[instrument(err, skip(executor))]
+pub(crate) async fn search_issues<'e, E: Executor<'e, Database = Postgres>>(
+    repo_id: i32,
+    filter: &IssueFilter,
+    viewer: Option<&User>,
+    can_manage: bool,
+    cursor: Option<IssueCursor>,
+    limit: i64,
+    executor: E,
+) -> Result<Vec<Issue>> {
+    let column = filter.sort.column();
+    let (comparison, order) = if filter.sort.ascending() {
+        (">", "asc")
+    } else {
+        ("<", "desc")
+    };
+
+    // Only the sort column and direction get formatted into the query, everything else is bound
+    let sql = format!(
+        "select * from issues \
+        where repo = $1 \
+        and {visibility} \
+        and ($4::boolean is null or closed = $4) \
+        and ($5::boolean is null or confidential = $5) \
+        and ($6::boolean is null or locked = $6) \
+        and labels @> $7 \
+        and ($8::integer is null or milestone = $8) \
+        and ($9::integer is null or $9 = any(assignees)) \
+        and ($10::integer is null or author = $10) \
+        and ($11 = false or cardinality(labels) = 0) \
+        and ($12 = false or milestone is null) \
+        and ($13 = false or cardinality(assignees) = 0) \
+        and ($14::varchar is null or search_vector @@ websearch_to_tsquery('simple', $14)) \
+        and ($15::timestamptz is null or ({column}, id) {comparison} ($15, $16)) \
+        order by {column} {order}, id {order} \
+        limit $17",
+        visibility = visibility_condition("$2", "$3"),
+        column = column,
+        comparison = comparison,
+        order = order
+    );
+
+    Ok(sqlx::query_as::<_, Issue>(sql.as_str())
+        .bind(repo_id)
+        .bind(can_manage)
+        .bind(viewer.map(|user| user.id))
+        .bind(filter.closed)
+        .bind(filter.confidential)
+        .bind(filter.locked)
+        .bind(&filter.labels)
+        .bind(filter.milestone)
+        .bind(filter.assignee)
+        .bind(filter.author)
+        .bind(filter.no_labels)
+        .bind(filter.no_milestone)
+        .bind(filter.no_assignee)
+        .bind(filter.term.as_deref())
+        .bind(cursor.map(|cursor| cursor.key))
+        .bind(cursor.map(|cursor| cursor.id))
+        .bind(limit)
+        .fetch_all(executor)
+        .await?)
+}
@@ -0,0 +1,327 @@
+//! Issue templates are read from the default branch of a repository, either from `.gitarena/ISSUE_TEMPLATE`
+//! or from `.github/ISSUE_TEMPLATE` for compatibility with repositories imported from GitHub.
+//!
+//! Markdown templates (`*.md`) pre-fill the description and may contain YAML front matter with a name, description,
+//! title prefix, default labels and default assignees. Form templates (`*.yml`, `*.yaml`) use the same keys but
+//! describe the description as a list of fields which get rendered as separate inputs.
+
+use crate::git::utils::{read_blob_content, repo_files_at_ref};
+use crate::markup::{render_markdown, MarkupContext};
+use crate::repository::Repository;
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use git_repository::objs::tree::EntryMode;
+use git_repository::objs::Tree;
+use git_repository::odb::pack::FindExt;
+use git_repository::odb::Store;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+use tracing::instrument;
+
+/// Directories searched for templates, the first directory containing templates wins
+const TEMPLATE_DIRECTORIES: [&str; 2] = [".gitarena/ISSUE_TEMPLATE", ".github/ISSUE_TEMPLATE"];
+
+#[derive(Debug, Serialize)]
+pub(crate) struct IssueTemplate {
+    /// Name of the file in the template directory, used to select the template
+    pub(crate) file_name: String,
+
+    pub(crate) name: String,
+    pub(crate) about: String,
+    /// Prefix the title gets pre-filled with
+    pub(crate) title: String,
+
+    /// Names of the labels added to issues created using this template
+    pub(crate) labels: Vec<String>,
+    /// Usernames of the users assigned to issues created using this template
+    pub(crate) assignees: Vec<String>,
+
+    /// Markdown the description gets pre-filled with, empty for form templates
+    pub(crate) body: String,
+    /// Fields of a form template, empty for Markdown templates
+    pub(crate) fields: Vec<TemplateField>,
+}
+
+impl IssueTemplate {
+    fn parse(file_name: &str, content: &str) -> Result<IssueTemplate> {
+        let lowered = file_name.to_lowercase();
+
+        if lowered.ends_with(".yml") || lowered.ends_with(".yaml") {
+            let form: FormTemplate = serde_yaml::from_str(content)?;
+
+            return Ok(IssueTemplate::new(
+                file_name,
+                form.meta,
+                String::new(),
+                form.body,
+            ));
+        }
+
+        // Front matter is delimited by `---` lines at the very beginning of the file
+        let (meta, body) = match content
+            .strip_prefix("---")
+            .and_then(|remaining| remaining.split_once("\n---"))
+        {
+            Some((front_matter, body)) => (
+                serde_yaml::from_str::<Option<FrontMatter>>(front_matter)?.unwrap_or_default(),
+                body.split_once('\n').map_or("", |(_, body)| body),
+            ),
+            None => (FrontMatter::default(), content),
+        };
+
+        Ok(IssueTemplate::new(
+            file_name,
+            meta,
+            body.trim_start().to_owned(),
+            Vec::new(),
+        ))
+    }
+
+    fn new(
+        file_name: &str,
+        meta: FrontMatter,
+        body: String,
+        fields: Vec<TemplateField>,
+    ) -> IssueTemplate {
+        IssueTemplate {
+            file_name: file_name.to_owned(),
+            name: meta
+                .name
+                .filter(|name| !name.trim().is_empty())
+                .unwrap_or_else(|| file_name.to_owned()),
+            about: meta.about.unwrap_or_default(),
+            title: meta.title.unwrap_or_default(),
+            labels: meta.labels.map(StringOrList::into_vec).unwrap_or_default(),
+            assignees: meta
+                .assignees
+                .map(StringOrList::into_vec)
+                .unwrap_or_default(),
+            body,
+            fields,
+        }
+    }
+
+    /// Renders the Markdown fields of a form template to HTML
+    pub(crate) fn render_markdown(&mut self, context: &MarkupContext) {
+        for field in self.fields.iter_mut() {
+            if let TemplateField::Markdown { attributes } = field {
+                attributes.html = render_markdown(attributes.value.as_str(), context);
+            }
+        }
+    }
+}
+
+#[derive(Default, Deserialize)]
+struct FrontMatter {
+    #[serde(default)]
+    name: Option<String>,
+    // GitHub calls this `about` in Markdown templates and `description` in form templates
+    #[serde(default, alias = "description")]
+    about: Option<String>,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    labels: Option<StringOrList>,
+    #[serde(default)]
+    assignees: Option<StringOrList>,
+}
+
+/// Lists can be written as YAML sequences or as comma separated strings
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StringOrList {
+    String(String),
+    List(Vec<String>),
+}
+
+impl StringOrList {
+    fn into_vec(self) -> Vec<String> {
+        let values = match self {
+            StringOrList::String(value) => value.split(',').map(str::to_owned).collect(),
+            StringOrList::List(values) => values,
+        };
+
+        values
+            .into_iter()
+            .map(|value| value.trim().to_owned())
+            .filter(|value| !value.is_empty())
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct FormTemplate {
+    #[serde(flatten)]
+    meta: FrontMatter,
+    body: Vec<TemplateField>,
+}
+
+/// Field of a form template. The submitted values are joined into the Markdown description,
+/// using the field labels as headings
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub(crate) enum TemplateField {
+    /// Text which is only shown in the form and not part of the description
+    Markdown { attributes: MarkdownAttributes },
+    Input {
+        attributes: FieldAttributes,
+        #[serde(default)]
+        validations: Validations,
+    },
+    Textarea {
+        attributes: FieldAttributes,
+        #[serde(default)]
+        validations: Validations,
+    },
+    Dropdown {
+        attributes: FieldAttributes,
+        #[serde(default)]
+        validations: Validations,
+    },
+    Checkboxes {
+        attributes: CheckboxAttributes,
+        #[serde(default)]
+        validations: Validations,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct MarkdownAttributes {
+    value: String,
+    #[serde(skip_deserializing)]
+    html: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct FieldAttributes {
+    label: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    placeholder: String,
+    /// Pre-filled value
+    #[serde(default)]
+    value: String,
+    /// Choices of a dropdown
+    #[serde(default)]
+    options: Vec<String>,
+    /// Whether multiple choices of a dropdown can be selected
+    #[serde(default)]
+    multiple: bool,
+    /// Language of a textarea, its value gets wrapped in a code block if set
+    #[serde(default)]
+    render: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct CheckboxAttributes {
+    label: String,
+    #[serde(default)]
+    description: String,
+    options: Vec<CheckboxOption>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct CheckboxOption {
+    label: String,
+    #[serde(default)]
+    required: bool,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(crate) struct Validations {
+    #[serde(default)]
+    required: bool,
+}
+
+/// Returns the issue templates on the default branch of `repo`.
+/// Templates which fail to parse are skipped, so a broken template never prevents creating issues.
+#[instrument(err, skip(transaction))]
+pub(crate) async fn issue_templates(
+    repo: &Repository,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Vec<IssueTemplate>> {
+    let gitoxide_repo = repo.gitoxide(&mut *transaction).await?;
+    let store = gitoxide_repo.objects.clone();
+
+    let default_ref = format!("refs/heads/{}", repo.default_branch);
+
+    // Empty repositories have no default branch yet
+    let reference = match gitoxide_repo.refs.find_loose(default_ref.as_str()) {
+        Ok(reference) => reference,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    let mut buffer = Vec::<u8>::new();
+    let root = Tree::from(
+        repo_files_at_ref(&reference, store.clone(), &gitoxide_repo, &mut buffer).await?,
+    );
+
+    for directory in TEMPLATE_DIRECTORIES {
+        let tree = match find_directory(&root, directory, &store)? {
+            Some(tree) => tree,
+            None => continue,
+        };
+
+        let mut templates = Vec::<IssueTemplate>::new();
+
+        for entry in tree.entries.iter() {
+            let file_name = String::from_utf8_lossy(&entry.filename[..]).into_owned();
+            let lowered = file_name.to_lowercase();
+
+            // `config.yml` configures the template chooser on GitHub and is not a template itself
+            if entry.mode != EntryMode::Blob
+                || lowered.starts_with("config.")
+                || !(lowered.ends_with(".md")
+                    || lowered.ends_with(".yml")
+                    || lowered.ends_with(".yaml"))
+            {
+                continue;
+            }
+
+            let content = read_blob_content(entry.oid.as_ref(), store.clone()).await?;
+
+            match IssueTemplate::parse(file_name.as_str(), content.as_str()) {
+                Ok(template) => templates.push(template),
+                Err(err) => warn!(
+                    "Skipping invalid issue template {}/{} in repo id {}: {}",
+                    directory, &file_name, repo.id, err
+                ),
+            }
+        }
+
+        if !templates.is_empty() {
+            return Ok(templates);
+        }
+    }
+
+    Ok(Vec::new())
+}
+
+/// Returns the tree at `path` (separated by `/`) relative to `root`, `None` if it does not exist
+fn find_directory(root: &Tree, path: &str, store: &Arc<Store>) -> Result<Option<Tree>> {
+    let mut current: Option<Tree> = None;
+
+    for component in path.split('/') {
+        let tree = current.as_ref().unwrap_or(root);
+
+        let oid = match tree
+            .entries
+            .iter()
+            .find(|entry| entry.mode == EntryMode::Tree && entry.filename == component)
+        {
+            Some(entry) => entry.oid,
+            None => return Ok(None),
+        };
+
+        let mut buffer = Vec::<u8>::new();
+        let (tree_ref, _) = store.to_handle_arc().find_tree(oid.as_ref(), &mut buffer)?;
+
+        current = Some(Tree::from(tree_ref));
+    }
+
+    Ok(current)
+}
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use chrono::serde::ts_seconds;
+use chrono::{DateTime, Utc};
+use derive_more::Display;
+use serde::Serialize;
+use sqlx::{Executor, FromRow, Postgres};
+
+/// Separator between the scope and the name of scoped labels, e.g. `area::ui`
+pub(crate) const SCOPE_SEPARATOR: &str = "::";
+
+#[derive(FromRow, Display, Debug, Clone, Serialize)]
+#[display(fmt = "{}", name)]
+pub(crate) struct Label {
+    pub(crate) id: i32,
+    pub(crate) repo: i32,
+
+    pub(crate) name: String,
+    pub(crate) color: String, // Hex color including the leading #
+    pub(crate) description: String,
+
+    #[serde(with = "ts_seconds")]
+    pub(crate) created_at: DateTime<Utc>,
+}
+
+impl Label {
+    pub(crate) async fn find<'e, E: Executor<'e, Database = Postgres>>(
+        repo_id: i32,
+        id: i32,
+        executor: E,
+    ) -> Result<Option<Label>> {
+        Ok(
+            sqlx::query_as::<_, Label>("select * from labels where repo = $1 and id = $2 limit 1")
+                .bind(repo_id)
+                .bind(id)
+                .fetch_optional(executor)
+                .await?,
+        )
+    }
+
+    /// Returns all labels of a repository, ordered by name
+    pub(crate) async fn all<'e, E: Executor<'e, Database = Postgres>>(
+        repo_id: i32,
+        executor: E,
+    ) -> Result<Vec<Label>> {
+        Ok(
+            sqlx::query_as::<_, Label>("select * from labels where repo = $1 order by lower(name)")
+                .bind(repo_id)
+                .fetch_all(executor)
+                .await?,
+        )
+    }
+
+    /// Returns the labels with the given ids, ordered by name
+    pub(crate) async fn find_all<'e, E: Executor<'e, Database = Postgres>>(
+        ids: &[i32],
+        executor: E,
+    ) -> Result<Vec<Label>> {
+        Ok(sqlx::query_as::<_, Label>(
+            "select * from labels where id = any($1) order by lower(name)",
+        )
+        .bind(ids)
+        .fetch_all(executor)
+        .await?)
+    }
+
+    /// Scope of a scoped label (`area` for `area::ui`), `None` for regular labels
+    pub(crate) fn scope(&self) -> Option<&str> {
+        self.name
+            .rsplit_once(SCOPE_SEPARATOR)
+            .map(|(scope, _)| scope)
+    }
+}
+
+/// Returns whether `color` is a hex color in the form of `#rrggbb`
+pub(crate) fn is_valid_color(color: &str) -> bool {
+    color.len() == 7 && color.starts_with('#') && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Returns the ids of `labels` with scoped labels deduplicated: If multiple labels share a scope, only the last one is kept
+pub(crate) fn exclusive_scopes(labels: &[Label]) -> Vec<i32> {
+    labels
+        .iter()
+        .enumerate()
+        .filter(|(index, label)| match label.scope() {
+            Some(scope) => !labels[index + 1..]
+                .iter()
+                .any(|other| other.scope() == Some(scope)),
+            None => true,
+        })
+        .map(|(_, label)| label.id)
+        .collect()
+}
+
+/// Same as [exclusive_scopes], but for label ids which are kept in their order. Ids of labels which do not exist are dropped
+pub(crate) async fn exclusive_scope_ids<'e, E: Executor<'e, Database = Postgres>>(
+    ids: &[i32],
+    executor: E,
+) -> Result<Vec<i32>> {
+    let mut labels = Label::find_all(ids, executor).await?;
+    labels.sort_by_key(|label| ids.iter().position(|id| *id == label.id));
+
+    Ok(exclusive_scopes(labels.as_slice()))
+}
@@ -0,0 +1,390 @@
+use crate::git::attributes::{AttributeValue, GitAttributes};
+use crate::git::utils::is_binary;
+use crate::highlighting::detect_language;
+use crate::repository::Repository;
+use crate::utils::repo_scheduler::RepoScheduler;
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use git2::{ObjectType, Repository as Git2Repository, Tree};
+use log::info;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use sqlx::{Executor, FromRow, PgPool, Postgres};
+use tracing::instrument;
+
+/// Languages which are data or prose and therefore not counted unless marked with `linguist-detectable`
+const NOT_DETECTABLE: &[&str] = &[
+    "Plain Text",
+    "Markdown",
+    "MultiMarkdown",
+    "reStructuredText",
+    "Textile",
+    "JSON",
+    "YAML",
+    "Diff",
+    "Regular Expression",
+    "Git Attributes",
+    "Git Commit",
+    "Git Config",
+    "Git Ignore",
+    "Git Link",
+    "Git Log",
+    "Git Mailmap",
+    "Git Rebase Todo",
+];
+
+/// Path segments of third party code which is not counted unless marked with `-linguist-vendored`
+const VENDORED_DIRECTORIES: &[&str] = &[
+    "node_modules",
+    "vendor",
+    "vendors",
+    "third_party",
+    "third-party",
+    "3rdparty",
+    "bower_components",
+    ".yarn",
+];
+
+/// Files generated by tools which are not counted unless marked with `-linguist-generated`
+const GENERATED_FILE_NAMES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "composer.lock",
+    "Gemfile.lock",
+    "poetry.lock",
+    "go.sum",
+];
+
+/// Colors of the most common languages, taken from GitHub linguist
+const LANGUAGE_COLORS: &[(&str, &str)] = &[
+    ("C", "#555555"),
+    ("C#", "#178600"),
+    ("C++", "#f34b7d"),
+    ("CSS", "#563d7c"),
+    ("Clojure", "#db5855"),
+    ("D", "#ba595e"),
+    ("Dart", "#00b4ab"),
+    ("Elixir", "#6e4a7e"),
+    ("Erlang", "#b83998"),
+    ("Go", "#00add8"),
+    ("Groovy", "#4298b8"),
+    ("HTML", "#e34c26"),
+    ("Haskell", "#5e5086"),
+    ("Java", "#b07219"),
+    ("JavaScript", "#f1e05a"),
+    ("Kotlin", "#a97bff"),
+    ("Lisp", "#3fb68b"),
+    ("Lua", "#000080"),
+    ("Makefile", "#427819"),
+    ("OCaml", "#3be133"),
+    ("Objective-C", "#438eff"),
+    ("PHP", "#4f5d95"),
+    ("Perl", "#0298c3"),
+    ("PowerShell", "#012456"),
+    ("Python", "#3572a5"),
+    ("R", "#198ce7"),
+    ("Ruby", "#701516"),
+    ("Rust", "#dea584"),
+    ("SQL", "#e38c00"),
+    ("Scala", "#c22d40"),
+    ("Shell", "#89e051"),
+    ("Swift", "#f05138"),
+    ("TeX", "#3d6117"),
+    ("TypeScript", "#3178c6"),
+];
+
+static ANALYZING: Lazy<RepoScheduler> =
+    Lazy::new(|| RepoScheduler::new("update language statistics"));
+
+#[derive(FromRow, Serialize, Debug)]
+pub(crate) struct LanguageStat {
+    pub(crate) language: String,
+    pub(crate) bytes: i64,
+}
+
+/// Language of a repository including its share of all counted bytes, used for the language bar
+#[derive(Serialize, Debug)]
+pub(crate) struct LanguageShare {
+    pub(crate) language: String,
+    pub(crate) color: String,
+    pub(crate) bytes: i64,
+    pub(crate) percentage: f64,
+}
+
+/// Updates the language statistics of a repository in the background.
+///
+/// If the repository is already being analyzed, it gets analyzed again after the current run finishes.
+pub(crate) fn schedule_update(db_pool: PgPool, repo_id: i32) {
+    ANALYZING.schedule(db_pool, repo_id, |db_pool, repo_id| async move {
+        update_languages(&db_pool, repo_id).await
+    });
+}
+
+#[instrument(err, skip(db_pool))]
+async fn update_languages(db_pool: &PgPool, repo_id: i32) -> Result<()> {
+    let mut transaction = db_pool.begin().await?;
+
+    let repo = match sqlx::query_as::<_, Repository>("select * from repositories where id = $1")
+        .bind(repo_id)
+        .fetch_optional(&mut transaction)
+        .await?
+    {
+        Some(repo) => repo,
+        None => return Ok(()), // Repository got deleted in the meantime
+    };
+
+    let path = repo.get_fs_path(&mut transaction).await?;
+    let reference = format!("refs/heads/{}", repo.default_branch);
+
+    let languages =
+        tokio::task::spawn_blocking(move || count_languages(path.as_str(), reference.as_str()))
+            .await??;
+
+    sqlx::query("delete from repository_languages where repo = $1")
+        .bind(repo.id)
+        .execute(&mut transaction)
+        .await?;
+
+    for (language, bytes) in languages.iter() {
+        sqlx::query("insert into repository_languages (repo, language, bytes) values ($1, $2, $3)")
+            .bind(repo.id)
+            .bind(language.as_str())
+            .bind(*bytes as i64)
+            .execute(&mut transaction)
+            .await?;
+    }
+
+    transaction.commit().await?;
+
+    info!(
+        "Updated language statistics for repo id {} ({} languages)",
+        repo.id,
+        languages.len()
+    );
+
+    Ok(())
+}
+
+/// Returns the languages of a repository sorted by their share, biggest first
+pub(crate) async fn repo_languages<'e, E: Executor<'e, Database = Postgres>>(
+    repo_id: i32,
+    executor: E,
+) -> Result<Vec<LanguageShare>> {
+    let stats = sqlx::query_as::<_, LanguageStat>(
+        "select language, bytes from repository_languages where repo = $1 order by bytes desc, language",
+    )
+    .bind(repo_id)
+    .fetch_all(executor)
+    .await?;
+
+    let total = stats.iter().map(|stat| stat.bytes).sum::<i64>().max(1) as f64;
+
+    Ok(stats
+        .into_iter()
+        .map(|stat| LanguageShare {
+            color: language_color(stat.language.as_str()),
+            percentage: stat.bytes as f64 * 100.0 / total,
+            language: stat.language,
+            bytes: stat.bytes,
+        })
+        .collect())
+}
+
+/// Returns the languages used by most repositories, used as filter options on the explore page
+pub(crate) async fn popular_languages<'e, E: Executor<'e, Database = Postgres>>(
+    limit: i64,
+    executor: E,
+) -> Result<Vec<String>> {
+    let languages = sqlx::query_as::<_, (String,)>(
+        "select language from repository_languages group by language order by count(*) desc, language limit $1",
+    )
+    .bind(limit)
+    .fetch_all(executor)
+    .await?;
+
+    Ok(languages.into_iter().map(|(language,)| language).collect())
+}
+
+/// Returns the color of a language for the language bar. Languages without a known color get one derived from their name.
+pub(crate) fn language_color(language: &str) -> String {
+    if let Some((_, color)) = LANGUAGE_COLORS.iter().find(|(name, _)| *name == language) {
+        return (*color).to_owned();
+    }
+
+    let hash = md5::compute(language);
+
+    format!("#{:02x}{:02x}{:02x}", hash[0], hash[1], hash[2])
+}
+
+/// Walks the tree of `reference` and sums up the size of all counted files per language
+fn count_languages(path: &str, reference: &str) -> Result<HashMap<String, u64>> {
+    let repo = Git2Repository::open(path)?;
+
+    let tree = match repo.find_reference(reference) {
+        Ok(reference) => reference.peel_to_tree()?,
+        Err(_) => return Ok(HashMap::new()), // Default branch does not exist (yet)
+    };
+
+    let mut languages = HashMap::new();
+    let mut attributes = GitAttributes::default();
+
+    count_tree(&repo, &tree, "", &mut attributes, &mut languages)?;
+
+    Ok(languages)
+}
+
+fn count_tree(
+    repo: &Git2Repository,
+    tree: &Tree<'_>,
+    directory: &str,
+    attributes: &mut GitAttributes,
+    languages: &mut HashMap<String, u64>,
+) -> Result<()> {
+    let attributes_file = tree
+        .get_name(".gitattributes")
+        .filter(|entry| entry.kind() == Some(ObjectType::Blob));
+
+    if let Some(entry) = attributes_file.as_ref() {
+        let blob = repo.find_blob(entry.id())?;
+        attributes.push(directory, blob.content());
+    }
+
+    for entry in tree.iter() {
+        let name = match entry.name() {
+            Some(name) => name,
+            None => continue,
+        };
+
+        let path = if directory.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{}/{}", directory, name)
+        };
+
+        match entry.kind() {
+            Some(ObjectType::Tree) => {
+                if is_excluded(attributes, path.as_str(), true, "linguist-vendored", || {
+                    VENDORED_DIRECTORIES.contains(&name)
+                }) {
+                    continue;
+                }
+
+                let subtree = repo.find_tree(entry.id())?;
+                count_tree(repo, &subtree, path.as_str(), attributes, languages)?;
+            }
+            Some(ObjectType::Blob) => {
+                // Symlinks are stored as blobs containing the target path
+                if entry.filemode() == 0o120000 {
+                    continue;
+                }
+
+                let blob = repo.find_blob(entry.id())?;
+                let content = blob.content();
+
+                if let Some(language) = file_language(attributes, path.as_str(), name, content) {
+                    *languages.entry(language).or_default() += content.len() as u64;
+                }
+            }
+            _ => { /* ignore submodules */ }
+        }
+    }
+
+    if attributes_file.is_some() {
+        attributes.pop();
+    }
+
+    Ok(())
+}
+
+/// Returns the language a file gets counted as, or `None` if it does not count towards the statistics
+fn file_language(
+    attributes: &GitAttributes,
+    path: &str,
+    name: &str,
+    content: &[u8],
+) -> Option<String> {
+    if is_excluded(attributes, path, false, "linguist-vendored", || {
+        name.contains(".min.")
+    }) {
+        return None;
+    }
+
+    if is_excluded(attributes, path, false, "linguist-generated", || {
+        is_generated(name, content)
+    }) {
+        return None;
+    }
+
+    let detectable = attributes.get(path, false, "linguist-detectable");
+
+    if let Some(AttributeValue::Value(language)) = attributes.get(path, false, "linguist-language")
+    {
+        if detectable != Some(AttributeValue::Unset) {
+            return Some(language.replace('-', " ").chars().take(64).collect());
+        }
+
+        return None;
+    }
+
+    if is_binary(content) {
+        return None;
+    }
+
+    let content = String::from_utf8_lossy(content);
+    let language = linguist_name(detect_language(name, content.as_ref()).name.as_str());
+
+    let counted = match detectable {
+        Some(AttributeValue::Set) => true,
+        Some(AttributeValue::Unset) => false,
+        _ => !NOT_DETECTABLE.contains(&language),
+    };
+
+    counted.then(|| language.to_owned())
+}
+
+/// Attributes override the heuristic in both directions: `linguist-vendored` includes a file in the vendored
+/// files even if the heuristic does not match, `-linguist-vendored` excludes a file the heuristic would match
+fn is_excluded<F: FnOnce() -> bool>(
+    attributes: &GitAttributes,
+    path: &str,
+    is_directory: bool,
+    attribute: &str,
+    heuristic: F,
+) -> bool {
+    match attributes.get(path, is_directory, attribute) {
+        Some(AttributeValue::Unset) => false,
+        Some(AttributeValue::Value(value)) if value == "false" => false,
+        Some(_) => true,
+        None => heuristic(),
+    }
+}
+
+fn is_generated(name: &str, content: &[u8]) -> bool {
+    if GENERATED_FILE_NAMES.contains(&name) || name.ends_with(".pb.go") || name.ends_with("_pb2.py")
+    {
+        return true;
+    }
+
+    // https://pkg.go.dev/cmd/go#hdr-Generate_Go_files_by_processing_source
+    let head = String::from_utf8_lossy(&content[..content.len().min(1024)]);
+
+    head.lines()
+        .take(5)
+        .any(|line| line.contains("DO NOT EDIT") || line.contains("@generated"))
+}
+
+/// Maps syntect syntax names to the names used by GitHub linguist where they differ
+fn linguist_name(syntax: &str) -> &str {
+    match syntax {
+        "Bourne Again Shell (bash)" => "Shell",
+        "JavaScript (Babel)" => "JavaScript",
+        "LaTeX" => "TeX",
+        "Objective-C++" => "Objective-C",
+        "Batch File" => "Batchfile",
+        other => other,
+    }
+}
@@ -4,6 +4,7 @@ use crate::error::error_renderer_middleware;
 use crate::ipc::Ipc;
 use crate::sse::Broadcaster;
 use crate::utils::admin_panel_layer::AdminPanelLayer;
+use crate::utils::cookie_file::MimeCookie;
 use crate::utils::system::SYSTEM_INFO;
 
 use std::env;
@@ -31,20 +32,33 @@ use time::Duration as TimeDuration;
 use tracing_subscriber::Layer;
 use tracing_unwrap::ResultExt;
 
+mod board;
 mod captcha;
 mod config;
 mod crypto;
 mod error;
 mod git;
+mod highlighting;
+mod incoming_mail;
 mod ipc;
 mod issue;
+mod issue_query;
+mod issue_template;
+mod label;
+mod languages;
 mod licenses;
 mod mail;
+mod markup;
+mod milestone;
 mod prelude;
 mod privileges;
+mod release;
+mod renderers;
 mod repository;
 mod routes;
+mod search;
 mod session;
+mod signatures;
 mod sse;
 mod ssh;
 mod sso;
@@ -76,6 +90,8 @@ async fn main() -> Result<()> {
     sqlx::migrate!().run(&db_pool).await?;
 
     licenses::init().await;
+    highlighting::init().await;
+    incoming_mail::spawn_poller(db_pool.clone());
 
     // read the `Lazy` to initialize it but immediately drop the returned guard to prevent a deadlock
     let _ = SYSTEM_INFO.read().await;
@@ -105,11 +121,19 @@ async fn main() -> Result<()> {
                 .secure(secure),
         );
 
-        let cookie = Arc::new(read_magic_database().expect_or_log("Failed to libmagic database"));
+        let cookie = Arc::new(
+            read_magic_database(CookieFlags::default())
+                .expect_or_log("Failed to libmagic database"),
+        );
+        let mime_cookie = Arc::new(MimeCookie(
+            read_magic_database(CookieFlags::MIME_TYPE | CookieFlags::MIME_ENCODING)
+                .expect_or_log("Failed to libmagic database"),
+        ));
 
         let mut app = App::new()
             .app_data(Data::new(db_pool.clone())) // Pool<Postgres> is just a wrapper around Arc<P> so .clone() is cheap
             .app_data(Data::new(cookie))
+            .app_data(Data::new(mime_cookie))
             .app_data(Data::new(ipc.clone()))
             .app_data(broadcaster.clone())
             .wrap(NormalizePath::new(TrailingSlash::Trim))
@@ -183,8 +207,8 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-fn read_magic_database() -> Result<Cookie> {
-    let cookie = Cookie::open(CookieFlags::default())?;
+fn read_magic_database(flags: CookieFlags) -> Result<Cookie> {
+    let cookie = Cookie::open(flags)?;
 
     // https://man7.org/linux/man-pages/man3/libmagic.3.html
     let database_path = if let Some(magic_env) = env::var_os("MAGIC") {
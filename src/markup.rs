@@ -0,0 +1,525 @@
+use crate::crypto::random_numeric_ascii_string;
+use crate::highlighting::{syntax_set, Highlighter, MAX_HIGHLIGHT_SIZE};
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use ammonia::Builder;
+use anyhow::{anyhow, Result};
+use log::warn;
+use once_cell::sync::Lazy;
+use pulldown_cmark::escape::escape_html;
+use pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser, Tag};
+use regex::{Captures, Regex};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::time::timeout;
+use tracing_unwrap::{OptionExt, ResultExt};
+
+/// Prefix added to all user supplied `id` attributes to prevent them from clobbering ids of the surrounding page
+const ID_PREFIX: &str = "user-content-";
+
+/// Maximum amount of time an external renderer (asciidoctor, pandoc) is allowed to take
+const EXTERNAL_RENDERER_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Matches `#123` and `@username` if they're not part of a word, path or email address
+static REFERENCE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?:^|[^\w/#&@.])(?P<reference>#(?P<issue>\d+)\b|@(?P<user>[A-Za-z0-9_-]{3,32}))")
+        .unwrap_or_log()
+});
+
+// Prepended to links generated by us (e.g. `#123` references) so the sanitizer can tell them apart from links written
+// by users, which always get rewritten. Random so that users cannot forge it in raw HTML
+static GENERATED_LINK_MARKER: Lazy<String> =
+    Lazy::new(|| format!("/.generated-{}", random_numeric_ascii_string(16)));
+
+static LINE_REFERENCE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^L\d+(?:-L\d+)?$").unwrap_or_log());
+
+// Styles emitted by syntect for highlighted code blocks
+static HIGHLIGHT_STYLE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?:(?:color:#[0-9a-fA-F]{6}|font-weight:bold|font-style:italic|text-decoration:underline);)+$")
+        .unwrap_or_log()
+});
+
+/// Location of the document which gets rendered. Used to resolve relative links and `#123` references.
+#[derive(Clone, Debug)]
+pub(crate) struct MarkupContext {
+    pub(crate) owner: String,
+    pub(crate) repo: String,
+    pub(crate) tree: String,
+
+    /// Directory the document is located in, relative to the repository root (empty for the root)
+    pub(crate) directory: String,
+}
+
+impl MarkupContext {
+    pub(crate) fn new(owner: &str, repo: &str, tree: &str, path: &str) -> MarkupContext {
+        let directory = Path::new(path)
+            .parent()
+            .and_then(|parent| parent.to_str())
+            .unwrap_or_default();
+
+        MarkupContext {
+            owner: owner.to_owned(),
+            repo: repo.to_owned(),
+            tree: tree.to_owned(),
+            directory: directory.trim_matches('/').to_owned(),
+        }
+    }
+
+    /// Resolves a path relative to this document to a path relative to the repository root.
+    /// Paths starting with `/` are relative to the repository root.
+    fn resolve(&self, path: &str) -> String {
+        let mut segments = Vec::<&str>::new();
+
+        if !path.starts_with('/') {
+            segments.extend(self.directory.split('/').filter(|s| !s.is_empty()));
+        }
+
+        for segment in path.split('/') {
+            match segment {
+                "" | "." => {}
+                ".." => {
+                    segments.pop();
+                }
+                segment => segments.push(segment),
+            }
+        }
+
+        segments.join("/")
+    }
+
+    fn rewrite_link(&self, url: &str) -> Option<String> {
+        if let Some(fragment) = url.strip_prefix('#') {
+            return if fragment.starts_with(ID_PREFIX) {
+                None
+            } else {
+                Some(format!("#{}{}", ID_PREFIX, fragment))
+            };
+        }
+
+        if !is_relative(url) {
+            return None;
+        }
+
+        let (path, fragment) = url.split_once('#').unwrap_or((url, ""));
+        let path = path.split_once('?').map_or(path, |(path, _)| path);
+
+        // Links ending with a slash can only point to directories
+        let view = if path.ends_with('/') {
+            "directory"
+        } else {
+            "blob"
+        };
+
+        let mut link = format!(
+            "/{}/{}/tree/{}/{}/{}",
+            self.owner,
+            self.repo,
+            self.tree,
+            view,
+            self.resolve(path)
+        );
+
+        // Line references (`#L10`) point to the blob view itself instead of the rendered document
+        if LINE_REFERENCE.is_match(fragment) {
+            link.push_str(&format!("#{}", fragment));
+        } else if !fragment.is_empty() {
+            link.push_str(&format!("#{}{}", ID_PREFIX, fragment));
+        }
+
+        Some(link)
+    }
+
+    fn rewrite_image(&self, url: &str) -> Option<String> {
+        if url.starts_with("data:image/") {
+            return None;
+        }
+
+        if is_relative(url) {
+            let path = url.split(['?', '#']).next().unwrap_or_default();
+
+            return Some(format!(
+                "/{}/{}/tree/{}/~blob/{}",
+                self.owner,
+                self.repo,
+                self.tree,
+                self.resolve(path)
+            ));
+        }
+
+        // External images get loaded through our image proxy to not leak the visitors IP address
+        let url = if url.starts_with("//") {
+            Cow::Owned(format!("https:{}", url))
+        } else {
+            Cow::Borrowed(url)
+        };
+
+        Some(format!("/api/proxy/{}", hex::encode(url.as_bytes())))
+    }
+}
+
+fn is_relative(url: &str) -> bool {
+    !url.starts_with("//")
+        && matches!(
+            url::Url::parse(url),
+            Err(url::ParseError::RelativeUrlWithoutBase)
+        )
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum MarkupFormat {
+    Markdown,
+    AsciiDoc,
+    ReStructuredText,
+    Org,
+    PlainText,
+}
+
+impl MarkupFormat {
+    pub(crate) fn from_file_name(file_name: &str) -> MarkupFormat {
+        let extension = Path::new(file_name)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+
+        match extension.as_deref() {
+            Some("md" | "markdown" | "mdown" | "mkd" | "mkdn") => MarkupFormat::Markdown,
+            Some("adoc" | "asciidoc" | "asc") => MarkupFormat::AsciiDoc,
+            Some("rst" | "rest") => MarkupFormat::ReStructuredText,
+            Some("org") => MarkupFormat::Org,
+            _ => MarkupFormat::PlainText,
+        }
+    }
+
+    /// Returns true if this format gets rendered instead of being displayed as-is
+    pub(crate) fn is_markup(&self) -> bool {
+        !matches!(self, MarkupFormat::PlainText)
+    }
+}
+
+/// Renders a document into sanitized HTML. The format is detected using the file name.
+///
+/// AsciiDoc, reStructuredText and org documents are rendered using `asciidoctor` and `pandoc` respectively.
+/// If these are not installed on the system, the document gets displayed as plain text.
+pub(crate) async fn render(file_name: &str, content: &str, context: &MarkupContext) -> String {
+    let format = MarkupFormat::from_file_name(file_name);
+
+    let html = match format {
+        MarkupFormat::Markdown => markdown_to_html(content, context),
+        MarkupFormat::PlainText => plain_text_to_html(content),
+        external => match render_external(external, content).await {
+            Ok(html) => html,
+            Err(err) => {
+                warn!("Failed to render {}: {}", file_name, err);
+                plain_text_to_html(content)
+            }
+        },
+    };
+
+    sanitize(html.as_str(), context)
+}
+
+/// Renders Markdown into sanitized HTML. Used for issues and comments.
+pub(crate) fn render_markdown(content: &str, context: &MarkupContext) -> String {
+    sanitize(markdown_to_html(content, context).as_str(), context)
+}
+
+fn markdown_to_html(content: &str, context: &MarkupContext) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+
+    let mut events = Parser::new_ext(content, options);
+    let mut output = Vec::<Event>::new();
+
+    let mut slugs = HashMap::<String, usize>::new();
+    let mut link_depth = 0_usize;
+
+    while let Some(event) = events.next() {
+        match event {
+            Event::Start(Tag::Heading(level, _, _)) => {
+                let inner = events
+                    .by_ref()
+                    .take_while(|event| !matches!(event, Event::End(Tag::Heading(..))))
+                    .collect::<Vec<Event>>();
+
+                let text = inner
+                    .iter()
+                    .filter_map(|event| match event {
+                        Event::Text(text) | Event::Code(text) => Some(text.as_ref()),
+                        _ => None,
+                    })
+                    .collect::<String>();
+
+                output.push(Event::Html(
+                    format!("<{} id=\"{}\">", level, slug(text.as_str(), &mut slugs)).into(),
+                ));
+
+                for event in inner {
+                    push_inline(event, &mut output, &mut link_depth, context);
+                }
+
+                output.push(Event::Html(format!("</{}>\n", level).into()));
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let code = events
+                    .by_ref()
+                    .take_while(|event| !matches!(event, Event::End(Tag::CodeBlock(_))))
+                    .fold(String::new(), |mut code, event| {
+                        if let Event::Text(text) = event {
+                            code.push_str(&text);
+                        }
+
+                        code
+                    });
+
+                let language = match &kind {
+                    CodeBlockKind::Fenced(info) => info.split([',', ' ']).next(),
+                    CodeBlockKind::Indented => None,
+                };
+
+                output.push(Event::Html(
+                    highlight_code_block(code.as_str(), language).into(),
+                ));
+            }
+            event => push_inline(event, &mut output, &mut link_depth, context),
+        }
+    }
+
+    let mut html = String::with_capacity(content.len() * 3 / 2);
+    html::push_html(&mut html, output.into_iter());
+
+    html
+}
+
+fn push_inline<'a>(
+    event: Event<'a>,
+    output: &mut Vec<Event<'a>>,
+    link_depth: &mut usize,
+    context: &MarkupContext,
+) {
+    match event {
+        // Links and images get rewritten while sanitizing
+        Event::Start(Tag::Link(..) | Tag::Image(..)) => {
+            *link_depth += 1;
+            output.push(event);
+        }
+        Event::End(Tag::Link(..) | Tag::Image(..)) => {
+            *link_depth = link_depth.saturating_sub(1);
+            output.push(event);
+        }
+        // References inside of links are not linked again
+        Event::Text(text) if *link_depth == 0 && REFERENCE.is_match(&text) => {
+            let mut last = 0;
+
+            for captures in REFERENCE.captures_iter(&text) {
+                let reference = captures.name("reference").unwrap_or_log();
+
+                output.push(Event::Text(text[last..reference.start()].to_owned().into()));
+                output.push(Event::Html(reference_link(&captures, context).into()));
+
+                last = reference.end();
+            }
+
+            output.push(Event::Text(text[last..].to_owned().into()));
+        }
+        event => output.push(event),
+    }
+}
+
+fn reference_link(captures: &Captures, context: &MarkupContext) -> String {
+    if let Some(issue) = captures.name("issue") {
+        format!(
+            "<a href=\"{}/{}/{}/issues/{}\">#{}</a>",
+            GENERATED_LINK_MARKER.as_str(),
+            context.owner,
+            context.repo,
+            issue.as_str(),
+            issue.as_str()
+        )
+    } else {
+        let user = captures.name("user").unwrap_or_log().as_str();
+
+        format!(
+            "<a href=\"{}/{}\">@{}</a>",
+            GENERATED_LINK_MARKER.as_str(),
+            user,
+            user
+        )
+    }
+}
+
+/// Creates a GitHub compatible anchor for a heading, deduplicating anchors which were already used
+fn slug(text: &str, slugs: &mut HashMap<String, usize>) -> String {
+    let base = text
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == ' ' || *c == '-' || *c == '_')
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect::<String>();
+
+    let count = slugs.entry(base.clone()).or_insert(0);
+    let slug = if *count == 0 {
+        base
+    } else {
+        format!("{}-{}", base, count)
+    };
+
+    *count += 1;
+    slug
+}
+
+fn highlight_code_block(code: &str, language: Option<&str>) -> String {
+    let mut html = String::from("<pre class=\"code-block\"><code>");
+
+    let syntax = language
+        .filter(|language| !language.is_empty())
+        .and_then(|language| syntax_set().find_syntax_by_token(language))
+        .filter(|_| code.len() <= MAX_HIGHLIGHT_SIZE);
+
+    let highlighted = syntax.and_then(|syntax| {
+        let mut highlighter = Highlighter::new(syntax);
+
+        code.lines()
+            .map(|line| highlighter.line(line))
+            .collect::<Result<Vec<String>>>()
+            .ok()
+    });
+
+    match highlighted {
+        Some(lines) => html.push_str(lines.join("\n").as_str()),
+        None => {
+            let _ = escape_html(&mut html, code);
+        }
+    }
+
+    html.push_str("</code></pre>\n");
+    html
+}
+
+fn plain_text_to_html(content: &str) -> String {
+    let mut html = String::from("<pre>");
+    let _ = escape_html(&mut html, content);
+    html.push_str("</pre>");
+
+    html
+}
+
+async fn render_external(format: MarkupFormat, content: &str) -> Result<String> {
+    let mut command = match format {
+        MarkupFormat::AsciiDoc => {
+            let mut command = Command::new("asciidoctor");
+
+            // Secure mode prevents documents from including files on our server
+            command.args([
+                "--safe-mode",
+                "secure",
+                "--no-header-footer",
+                "--attribute",
+                "showtitle",
+                "--out-file",
+                "-",
+                "-",
+            ]);
+            command
+        }
+        MarkupFormat::ReStructuredText | MarkupFormat::Org => {
+            let mut command = Command::new("pandoc");
+            let from = if format == MarkupFormat::Org {
+                "org"
+            } else {
+                "rst"
+            };
+
+            // Sandbox prevents documents from including files on our server
+            command.args(["--sandbox", "--from", from, "--to", "html"]);
+            command
+        }
+        _ => unreachable!("{:?} is not rendered by an external renderer", format),
+    };
+
+    let mut child = command
+        .kill_on_drop(true)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("Failed to open stdin of external renderer"))?;
+
+    // stdin is written while reading stdout to not deadlock on documents bigger than the pipe buffer
+    let write = async move {
+        let result = stdin.write_all(content.as_bytes()).await;
+        drop(stdin);
+
+        result
+    };
+
+    let output = timeout(EXTERNAL_RENDERER_TIMEOUT, async move {
+        let (written, output) = tokio::join!(write, child.wait_with_output());
+
+        written.and(output)
+    })
+    .await
+    .map_err(|_| anyhow!("External renderer failed to finish within 10 seconds"))??;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "External renderer exited with non-zero status: {}",
+            output.status
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Sanitizes rendered HTML while rewriting relative links and images to point to the current tree.
+/// Site-absolute links are resolved against the repository root, only links generated by us are kept as-is
+pub(crate) fn sanitize(html: &str, context: &MarkupContext) -> String {
+    let context = context.clone();
+    let mut builder = Builder::default();
+
+    builder
+        .add_tags(&["input"])
+        .add_generic_attributes(&["id"])
+        .add_tag_attributes("input", &["type", "checked", "disabled"])
+        .add_tag_attributes("img", &["loading"])
+        .add_tag_attributes("span", &["style"])
+        .add_allowed_classes("pre", &["code-block"])
+        .set_tag_attribute_value("input", "disabled", "")
+        .set_tag_attribute_value("img", "loading", "lazy")
+        .id_prefix(Some(ID_PREFIX))
+        .attribute_filter(
+            move |element, attribute, value| match (element, attribute) {
+                ("a", "href") if value.starts_with(GENERATED_LINK_MARKER.as_str()) => {
+                    Some(Cow::Borrowed(&value[GENERATED_LINK_MARKER.len()..]))
+                }
+                ("a", "href") => Some(
+                    context
+                        .rewrite_link(value)
+                        .map_or(Cow::Borrowed(value), Cow::Owned),
+                ),
+                ("img", "src") => Some(
+                    context
+                        .rewrite_image(value)
+                        .map_or(Cow::Borrowed(value), Cow::Owned),
+                ),
+                // Task list items are the only inputs we allow
+                ("input", "type") => Some(Cow::Borrowed("checkbox")),
+                ("span", "style") if !HIGHLIGHT_STYLE.is_match(value) => None,
+                _ => Some(Cow::Borrowed(value)),
+            },
+        );
+
+    builder.clean(html).to_string()
+}
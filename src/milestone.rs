@@ -0,0 +1,89 @@
+use crate::issue::visibility_condition;
+use crate::user::User;
+
+use anyhow::Result;
+use chrono::serde::ts_seconds;
+use chrono::{DateTime, NaiveDate, Utc};
+use derive_more::Display;
+use serde::Serialize;
+use sqlx::{Executor, FromRow, Postgres};
+
+#[derive(FromRow, Display, Debug, Serialize)]
+#[display(fmt = "{}", title)]
+pub(crate) struct Milestone {
+    pub(crate) id: i32,
+    pub(crate) repo: i32,
+
+    pub(crate) title: String,
+    pub(crate) description: String,
+    pub(crate) due_date: Option<NaiveDate>,
+    pub(crate) closed: bool,
+
+    #[serde(with = "ts_seconds")]
+    pub(crate) created_at: DateTime<Utc>,
+}
+
+impl Milestone {
+    pub(crate) async fn find<'e, E: Executor<'e, Database = Postgres>>(
+        repo_id: i32,
+        id: i32,
+        executor: E,
+    ) -> Result<Option<Milestone>> {
+        Ok(sqlx::query_as::<_, Milestone>(
+            "select * from milestones where repo = $1 and id = $2 limit 1",
+        )
+        .bind(repo_id)
+        .bind(id)
+        .fetch_optional(executor)
+        .await?)
+    }
+
+    pub(crate) async fn find_by_title<'e, E: Executor<'e, Database = Postgres>>(
+        repo_id: i32,
+        title: &str,
+        executor: E,
+    ) -> Result<Option<Milestone>> {
+        Ok(sqlx::query_as::<_, Milestone>(
+            "select * from milestones where repo = $1 and lower(title) = lower($2) limit 1",
+        )
+        .bind(repo_id)
+        .bind(title)
+        .fetch_optional(executor)
+        .await?)
+    }
+
+    /// Returns all milestones of a repository. Open milestones come first, ordered by their due date
+    pub(crate) async fn all<'e, E: Executor<'e, Database = Postgres>>(
+        repo_id: i32,
+        executor: E,
+    ) -> Result<Vec<Milestone>> {
+        Ok(sqlx::query_as::<_, Milestone>(
+            "select * from milestones where repo = $1 order by closed, due_date nulls last, lower(title)",
+        )
+        .bind(repo_id)
+        .fetch_all(executor)
+        .await?)
+    }
+
+    /// Returns the amount of closed and total issues in this milestone.
+    /// Confidential issues are only counted if they are visible to `viewer`.
+    pub(crate) async fn progress<'e, E: Executor<'e, Database = Postgres>>(
+        &self,
+        viewer: Option<&User>,
+        can_manage_issues: bool,
+        executor: E,
+    ) -> Result<(i64, i64)> {
+        let (closed, total): (i64, i64) = sqlx::query_as(&format!(
+            "select count(*) filter (where closed), count(*) from issues \
+            where milestone = $1 and {}",
+            visibility_condition("$2", "$3")
+        ))
+        .bind(self.id)
+        .bind(can_manage_issues)
+        .bind(viewer.map(|user| user.id))
+        .fetch_one(executor)
+        .await?;
+
+        Ok((closed, total))
+    }
+}
@@ -1,3 +1,4 @@
+use crate::templates::web::{CommitAuthor, GitCommit};
 use crate::user::{User, WebUser};
 
 use actix_web::HttpRequest;
@@ -7,7 +8,10 @@ use awc::http::header::USER_AGENT;
 use awc::{Client, ClientBuilder};
 use bstr::BString;
 use chrono::{DateTime, FixedOffset, LocalResult, TimeZone, Utc};
-use git2::{Signature as LibGit2Signature, Time as LibGit2Time};
+use git2::{
+    message_trailers_strs, Commit as LibGit2Commit, Mailmap, Signature as LibGit2Signature,
+    Time as LibGit2Time,
+};
 use git_repository::actor::{Sign, Signature as GitoxideSignature, Time as GitoxideTime};
 use log::warn;
 use qstring::QString;
@@ -140,8 +144,11 @@ impl LibGit2TimeExtensions for LibGit2Time {
 pub(crate) trait LibGit2SignatureExtensions {
     /// Tries to disassemble this [Signature][signature] as `(Username, User ID, Email)`.
     ///
+    /// If a [Mailmap][mailmap] is provided, it gets applied to this [Signature][signature] first.
+    ///
     /// This will search the database (hence it requires a [Executor](sqlx::Executor)) for the
-    /// email provided by this [Signature][signature] `email()` method.
+    /// email provided by this [Signature][signature] `email()` method. Only verified emails are taken into account,
+    /// so committing using the address of someone else does not link the commit to their profile.
     ///
     /// If an entry is found, the registered `username` and `user id` from the database will be returned.
     /// If no entry is found, this [Signature][signature]s `name()` and `None` will be returned.
@@ -157,13 +164,15 @@ pub(crate) trait LibGit2SignatureExtensions {
     /// # let signature = git2::Signature::now("mellowagain", "mellowagain@example.com");
     /// # let mut transaction = todo!("Find out a way to acquire a Transaction for doc tests");
     ///
-    /// let (name, user_id) = signature.try_disassemble(&mut transaction)?;
+    /// let (name, user_id) = signature.try_disassemble(None, &mut transaction)?;
     /// assert_eq!("mellowagain", name);
     /// ```
     ///
     /// [signature]: git2::Signature
+    /// [mailmap]: git2::Mailmap
     async fn try_disassemble<'e, E: Executor<'e, Database = Postgres>>(
         &self,
+        mailmap: Option<&Mailmap>,
         executor: E,
     ) -> (String, Option<i32>, String);
 }
@@ -172,20 +181,133 @@ pub(crate) trait LibGit2SignatureExtensions {
 impl LibGit2SignatureExtensions for LibGit2Signature<'_> {
     async fn try_disassemble<'e, E: Executor<'e, Database = Postgres>>(
         &self,
+        mailmap: Option<&Mailmap>,
         executor: E,
     ) -> (String, Option<i32>, String) {
-        let email = self.email().unwrap_or("Invalid email address");
-
-        User::find_using_email(email, executor).await.map_or_else(
-            || {
-                (
-                    self.name().unwrap_or("Ghost").to_owned(),
-                    None,
-                    email.to_owned(),
-                )
-            },
-            |user| (user.username, Some(user.id), email.to_owned()),
-        )
+        let resolved = mailmap.and_then(|mailmap| mailmap.resolve_signature(self).ok());
+        let signature: &LibGit2Signature<'_> = resolved.as_ref().unwrap_or(self);
+
+        let email = signature.email().unwrap_or("Invalid email address");
+
+        User::find_using_verified_email(email, executor)
+            .await
+            .map_or_else(
+                || {
+                    (
+                        signature.name().unwrap_or("Ghost").to_owned(),
+                        None,
+                        email.to_owned(),
+                    )
+                },
+                |user| (user.username, Some(user.id), email.to_owned()),
+            )
+    }
+}
+
+pub(crate) trait LibGit2CommitExtensions {
+    /// Returns the co-authors listed in the `Co-authored-by` trailers of this [Commit][commit]'s message.
+    ///
+    /// Same as [try_disassemble](LibGit2SignatureExtensions::try_disassemble), the [Mailmap][mailmap] gets applied
+    /// first. The returned co-authors are not linked to users yet, pass all commits of a page to [link_co_authors]
+    /// afterwards to do so.
+    ///
+    /// [commit]: git2::Commit
+    /// [mailmap]: git2::Mailmap
+    fn co_authors(&self, mailmap: Option<&Mailmap>) -> Vec<CommitAuthor>;
+}
+
+impl LibGit2CommitExtensions for LibGit2Commit<'_> {
+    fn co_authors(&self, mailmap: Option<&Mailmap>) -> Vec<CommitAuthor> {
+        let trailers = match message_trailers_strs(self.message().unwrap_or_default()) {
+            Ok(trailers) => trailers,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut co_authors = Vec::<CommitAuthor>::new();
+
+        for (key, value) in trailers.iter() {
+            if !key.eq_ignore_ascii_case("Co-authored-by") {
+                continue;
+            }
+
+            let (name, email) = match value.rsplit_once('<') {
+                Some((name, email)) => (name.trim(), email.trim_end().trim_end_matches('>')),
+                None => continue,
+            };
+
+            let (name, email) = match mailmap.and_then(|mailmap| {
+                LibGit2Signature::new(name, email, &LibGit2Time::new(0, 0))
+                    .and_then(|signature| mailmap.resolve_signature(&signature))
+                    .ok()
+            }) {
+                Some(resolved) => (
+                    resolved.name().unwrap_or(name).to_owned(),
+                    resolved.email().unwrap_or(email).to_owned(),
+                ),
+                None => (name.to_owned(), email.to_owned()),
+            };
+
+            if email.is_empty()
+                || co_authors
+                    .iter()
+                    .any(|author| author.email.eq_ignore_ascii_case(email.as_str()))
+            {
+                continue;
+            }
+
+            co_authors.push(CommitAuthor {
+                name,
+                uid: None,
+                email,
+            });
+        }
+
+        co_authors
+    }
+}
+
+/// Links the co-authors of all `commits` to users which have the email verified.
+/// Resolves the emails of all commits in a single query, so this should be called once per page.
+pub(crate) async fn link_co_authors<'a, 'e, I, E>(commits: I, executor: E)
+where
+    I: IntoIterator<Item = &'a mut GitCommit>,
+    E: Executor<'e, Database = Postgres>,
+{
+    let mut co_authors = commits
+        .into_iter()
+        .flat_map(|commit| commit.co_authors.iter_mut())
+        .collect::<Vec<_>>();
+
+    if co_authors.is_empty() {
+        return;
+    }
+
+    let mut emails = co_authors
+        .iter()
+        .map(|author| author.email.to_lowercase())
+        .collect::<Vec<_>>();
+    emails.sort_unstable();
+    emails.dedup();
+
+    let users: Vec<(String, i32, String)> = sqlx::query_as(
+        "select lower(emails.email), users.id, users.username from emails \
+        join users on users.id = emails.owner \
+        where lower(emails.email) = any($1) and emails.verified_at is not null",
+    )
+    .bind(&emails)
+    .fetch_all(executor)
+    .await
+    .unwrap_or_default();
+
+    for author in co_authors.iter_mut() {
+        let email = author.email.to_lowercase();
+
+        if let Some((_, id, username)) =
+            users.iter().find(|(user_email, _, _)| *user_email == email)
+        {
+            author.uid = Some(*id);
+            author.name = username.clone();
+        }
     }
 }
 
@@ -1,3 +1 @@
 pub(crate) mod privilege;
-pub(crate) mod repo_access;
-pub(crate) mod repo_visibility;
@@ -0,0 +1,255 @@
+use crate::prelude::LibGit2SignatureExtensions;
+
+use std::fmt::Write as _;
+
+use anyhow::Result;
+use chrono::serde::{ts_seconds, ts_seconds_option};
+use chrono::{DateTime, Utc};
+use derive_more::Display;
+use git2::{Oid, Repository as Git2Repository, Sort};
+use serde::Serialize;
+use sqlx::{Executor, FromRow, Postgres, Transaction};
+
+/// Release attached to a tag. Drafts are only visible to users with push access
+#[derive(FromRow, Display, Debug, Serialize)]
+#[display(fmt = "{}", tag)]
+pub(crate) struct Release {
+    pub(crate) id: i32,
+    pub(crate) repo: i32,
+
+    pub(crate) tag: String,
+    pub(crate) title: String,
+    pub(crate) notes: String,
+
+    pub(crate) author: i32,
+
+    pub(crate) draft: bool,
+    pub(crate) prerelease: bool,
+
+    #[serde(with = "ts_seconds")]
+    pub(crate) created_at: DateTime<Utc>,
+    #[serde(with = "ts_seconds_option")]
+    pub(crate) published_at: Option<DateTime<Utc>>,
+}
+
+impl Release {
+    pub(crate) async fn find<'e, E: Executor<'e, Database = Postgres>>(
+        repo_id: i32,
+        tag: &str,
+        executor: E,
+    ) -> Result<Option<Release>> {
+        Ok(sqlx::query_as::<_, Release>(
+            "select * from releases where repo = $1 and tag = $2 limit 1",
+        )
+        .bind(repo_id)
+        .bind(tag)
+        .fetch_optional(executor)
+        .await?)
+    }
+
+    /// Returns the most recently published release which is neither a draft nor a prerelease
+    pub(crate) async fn latest<'e, E: Executor<'e, Database = Postgres>>(
+        repo_id: i32,
+        executor: E,
+    ) -> Result<Option<Release>> {
+        Ok(sqlx::query_as::<_, Release>(
+            "select * from releases where repo = $1 and draft = false and prerelease = false \
+            order by published_at desc, id desc limit 1",
+        )
+        .bind(repo_id)
+        .fetch_optional(executor)
+        .await?)
+    }
+
+    /// Returns all releases of a repository, newest first. Drafts are listed first if `drafts` is true
+    pub(crate) async fn all<'e, E: Executor<'e, Database = Postgres>>(
+        repo_id: i32,
+        drafts: bool,
+        executor: E,
+    ) -> Result<Vec<Release>> {
+        Ok(sqlx::query_as::<_, Release>(
+            "select * from releases where repo = $1 and (draft = false or $2) \
+            order by published_at desc nulls first, id desc",
+        )
+        .bind(repo_id)
+        .bind(drafts)
+        .fetch_all(executor)
+        .await?)
+    }
+
+    /// Amount of published releases (drafts are not counted)
+    pub(crate) async fn count<'e, E: Executor<'e, Database = Postgres>>(
+        repo_id: i32,
+        executor: E,
+    ) -> Result<i64> {
+        let (count,): (i64,) =
+            sqlx::query_as("select count(*) from releases where repo = $1 and draft = false")
+                .bind(repo_id)
+                .fetch_one(executor)
+                .await?;
+
+        Ok(count)
+    }
+
+    /// Returns the tag of the published release preceding this one, used as the base for generated notes
+    pub(crate) async fn previous_tag<'e, E: Executor<'e, Database = Postgres>>(
+        repo_id: i32,
+        tag: &str,
+        executor: E,
+    ) -> Result<Option<String>> {
+        let option: Option<(String,)> = sqlx::query_as(
+            "select tag from releases where repo = $1 and tag <> $2 and draft = false \
+            order by published_at desc, id desc limit 1",
+        )
+        .bind(repo_id)
+        .bind(tag)
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(option.map(|(tag,)| tag))
+    }
+
+    pub(crate) async fn assets<'e, E: Executor<'e, Database = Postgres>>(
+        &self,
+        executor: E,
+    ) -> Result<Vec<ReleaseAsset>> {
+        Ok(sqlx::query_as::<_, ReleaseAsset>(
+            "select * from release_assets where release = $1 order by lower(name)",
+        )
+        .bind(self.id)
+        .fetch_all(executor)
+        .await?)
+    }
+}
+
+/// Binary file uploaded to a release. The content lives on disk in the directory configured by `releases.dir`
+#[derive(FromRow, Display, Debug, Serialize)]
+#[display(fmt = "{}", name)]
+pub(crate) struct ReleaseAsset {
+    pub(crate) id: i32,
+    pub(crate) release: i32,
+
+    pub(crate) name: String,
+    pub(crate) content_type: String,
+    pub(crate) size: i64,
+    pub(crate) sha256: String,
+    pub(crate) downloads: i64,
+
+    pub(crate) uploader: i32,
+
+    #[serde(with = "ts_seconds")]
+    pub(crate) created_at: DateTime<Utc>,
+}
+
+impl ReleaseAsset {
+    pub(crate) async fn find<'e, E: Executor<'e, Database = Postgres>>(
+        release_id: i32,
+        name: &str,
+        executor: E,
+    ) -> Result<Option<ReleaseAsset>> {
+        Ok(sqlx::query_as::<_, ReleaseAsset>(
+            "select * from release_assets where release = $1 and name = $2 limit 1",
+        )
+        .bind(release_id)
+        .bind(name)
+        .fetch_optional(executor)
+        .await?)
+    }
+
+    /// Path of the asset on disk. Assets are stored by id to not have to trust user supplied file names
+    pub(crate) fn fs_path(&self, releases_dir: &str) -> String {
+        format!("{}/{}/{}", release_dir(releases_dir, self.release), self.id)
+    }
+}
+
+/// Directory containing all assets of a release
+pub(crate) fn release_dir(releases_dir: &str, release_id: i32) -> String {
+    format!("{}/{}", releases_dir, release_id)
+}
+
+/// Returns true if `name` can be used as an asset file name
+pub(crate) fn is_valid_asset_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 256
+        && name != "."
+        && name != ".."
+        && !name.contains(|c: char| c == '/' || c == '\\' || c.is_control())
+}
+
+/// Generates Markdown release notes listing all commits reachable from `tag` but not from `previous_tag`
+pub(crate) async fn generate_notes(
+    repo: &Git2Repository,
+    tag: &str,
+    previous_tag: Option<&str>,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<String> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+
+    revwalk.push(peel_tag(repo, tag)?)?;
+
+    if let Some(previous_tag) = previous_tag {
+        // The previous tag may have been deleted in the meantime, in that case list the whole history
+        if let Ok(oid) = peel_tag(repo, previous_tag) {
+            revwalk.hide(oid)?;
+        }
+    }
+
+    let mut commits = Vec::<Oid>::new();
+
+    for oid in revwalk {
+        let oid = oid?;
+
+        // Merge commits are noise in release notes as the merged commits are already listed
+        if repo.find_commit(oid)?.parent_count() <= 1 {
+            commits.push(oid);
+        }
+    }
+
+    let mut notes = String::from("## What's changed\n\n");
+
+    if commits.is_empty() {
+        notes.push_str("No changes\n");
+    }
+
+    let mailmap = repo.mailmap().ok();
+
+    for oid in commits {
+        let commit = repo.find_commit(oid)?;
+        let (name, _, _) = commit
+            .author()
+            .try_disassemble(mailmap.as_ref(), &mut *transaction)
+            .await;
+        let short_oid = &format!("{}", oid)[..7];
+
+        writeln!(
+            notes,
+            "- {} ({}) by {}",
+            commit.summary().unwrap_or_default(),
+            short_oid,
+            name
+        )?;
+    }
+
+    if let Some(previous_tag) = previous_tag {
+        write!(
+            notes,
+            "\n**Full changelog**: `{}...{}`\n",
+            previous_tag, tag
+        )?;
+    }
+
+    Ok(notes)
+}
+
+/// Resolves a tag name to the commit it points to
+fn peel_tag(repo: &Git2Repository, tag: &str) -> Result<Oid> {
+    let reference = repo.find_reference(format!("refs/tags/{}", tag).as_str())?;
+
+    Ok(reference.peel_to_commit()?.id())
+}
+
+/// Returns true if `tag` exists in the repository and points to a commit
+pub(crate) fn tag_exists(repo: &Git2Repository, tag: &str) -> bool {
+    peel_tag(repo, tag).is_ok()
+}
@@ -0,0 +1,94 @@
+use crate::renderers::{Blob, BlobRenderer, RenderContext, RenderedBlob};
+
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use tracing_unwrap::ResultExt;
+
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "apng", "avif", "bmp", "gif", "ico", "jpeg", "jpg", "png", "webp",
+];
+
+static DANGEROUS_ELEMENTS: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?is)<(script|foreignObject|iframe|embed|object)\b.*?(?:/>|</\s*(script|foreignObject|iframe|embed|object)\s*>)")
+        .unwrap_or_log()
+});
+
+static EVENT_HANDLERS: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)\s+on[a-z]+\s*=\s*(?:"[^"]*"|'[^']*'|[^\s>]+)"#).unwrap_or_log()
+});
+
+static LINKS: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)\s+((?:xlink:)?href|src)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap_or_log()
+});
+
+/// Displays raster images inline by pointing to the raw blob
+pub(super) struct ImageRenderer;
+
+impl BlobRenderer for ImageRenderer {
+    fn matches(&self, blob: &Blob<'_>) -> bool {
+        blob.mime.map_or(false, |mime| {
+            mime.starts_with("image/") && mime != "image/svg+xml"
+        }) || blob.has_extension(IMAGE_EXTENSIONS)
+    }
+
+    fn render(&self, _blob: &Blob<'_>, context: &RenderContext) -> Result<RenderedBlob> {
+        Ok(RenderedBlob::Image {
+            src: context.raw_url.clone(),
+        })
+    }
+}
+
+/// Displays SVG images after removing scripts, event handlers and external references.
+/// The sanitized image is embedded as `<img>` data URL, in which browsers additionally never run scripts or load resources.
+pub(super) struct SvgRenderer;
+
+impl BlobRenderer for SvgRenderer {
+    fn matches(&self, blob: &Blob<'_>) -> bool {
+        blob.has_extension(&["svg"]) || blob.mime == Some("image/svg+xml")
+    }
+
+    fn render(&self, blob: &Blob<'_>, _context: &RenderContext) -> Result<RenderedBlob> {
+        let content = blob
+            .text()
+            .ok_or_else(|| anyhow!("SVG is not valid UTF-8"))?;
+
+        Ok(RenderedBlob::Image {
+            src: svg_data_url(content),
+        })
+    }
+}
+
+/// Sanitizes the SVG and returns it as base64 encoded data URL
+pub(super) fn svg_data_url(svg: &str) -> String {
+    format!(
+        "data:image/svg+xml;base64,{}",
+        base64::encode(sanitize_svg(svg))
+    )
+}
+
+fn sanitize_svg(svg: &str) -> String {
+    let svg = DANGEROUS_ELEMENTS.replace_all(svg, "");
+    let svg = EVENT_HANDLERS.replace_all(&svg, "");
+
+    LINKS
+        .replace_all(&svg, |captures: &Captures| {
+            let value = captures
+                .get(2)
+                .or_else(|| captures.get(3))
+                .map_or("", |value| value.as_str())
+                .trim();
+
+            // Only references within the document and embedded raster images are kept
+            if value.starts_with('#')
+                || value.starts_with("data:image/png")
+                || value.starts_with("data:image/jpeg")
+                || value.starts_with("data:image/gif")
+            {
+                captures[0].to_owned()
+            } else {
+                String::new()
+            }
+        })
+        .into_owned()
+}
@@ -0,0 +1,80 @@
+use crate::renderers::{Blob, BlobRenderer, RenderContext, RenderedBlob};
+
+use anyhow::{anyhow, Result};
+
+const POINTER_VERSION: &[u8] = b"version https://git-lfs.github.com/spec/";
+
+/// Pointer files are tiny, anything bigger is actual content
+const MAX_POINTER_SIZE: usize = 1024;
+
+/// Displays Git LFS pointer files as link to the object stored on the LFS server
+pub(super) struct LfsPointerRenderer;
+
+impl BlobRenderer for LfsPointerRenderer {
+    fn matches(&self, blob: &Blob<'_>) -> bool {
+        blob.content.len() < MAX_POINTER_SIZE && blob.content.starts_with(POINTER_VERSION)
+    }
+
+    fn render(&self, blob: &Blob<'_>, context: &RenderContext) -> Result<RenderedBlob> {
+        let content = blob
+            .text()
+            .ok_or_else(|| anyhow!("LFS pointer is not valid UTF-8"))?;
+
+        let mut oid = None;
+        let mut size = None;
+
+        for line in content.lines() {
+            match line.split_once(' ') {
+                Some(("oid", value)) => oid = value.strip_prefix("sha256:"),
+                Some(("size", value)) => size = value.parse::<u64>().ok(),
+                _ => {}
+            }
+        }
+
+        let oid = oid
+            .filter(|oid| oid.len() == 64 && oid.chars().all(|c| c.is_ascii_hexdigit()))
+            .ok_or_else(|| anyhow!("LFS pointer does not contain a valid oid"))?;
+        let size = size.ok_or_else(|| anyhow!("LFS pointer does not contain a size"))?;
+
+        Ok(RenderedBlob::LfsPointer {
+            oid: oid.to_owned(),
+            size,
+            href: context
+                .lfs_endpoint
+                .as_ref()
+                .map(|endpoint| format!("{}/objects/{}", endpoint, oid)),
+        })
+    }
+}
+
+/// Returns the LFS server of a repository as configured in `lfs.url` of the `.lfsconfig` file in the repository root.
+/// GitArena does not serve LFS objects itself, so `None` is returned if no server is configured.
+pub(crate) fn lfs_endpoint(lfs_config: Option<&str>) -> Option<String> {
+    lfs_config.and_then(|config| {
+        let mut in_lfs_section = false;
+
+        for line in config.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_lfs_section = line.trim_start_matches('[').trim_end_matches(']').trim() == "lfs";
+                continue;
+            }
+
+            if !in_lfs_section {
+                continue;
+            }
+
+            if let Some((key, value)) = line.split_once('=') {
+                let value = value.trim().trim_matches('"');
+
+                // Only allow http(s) URLs to not end up with `javascript:` links
+                if key.trim() == "url"
+                    && (value.starts_with("https://") || value.starts_with("http://"))
+                {
+                    return Some(value.trim_end_matches('/').to_owned());
+                }
+            }
+        }
+
+        None
+    })
+}
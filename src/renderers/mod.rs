@@ -0,0 +1,123 @@
+use crate::markup::MarkupContext;
+use crate::utils::cookie_file::FileType;
+
+use std::path::Path;
+
+use anyhow::Result;
+use log::warn;
+use serde::Serialize;
+
+mod image;
+mod lfs;
+mod notebook;
+mod pdf;
+mod table;
+
+pub(crate) use lfs::lfs_endpoint;
+
+/// Renderers are tried in order, the first one matching a blob renders it.
+/// Blobs no renderer matches are displayed as highlighted text or rendered markup.
+const RENDERERS: &[&dyn BlobRenderer] = &[
+    &lfs::LfsPointerRenderer,
+    &image::SvgRenderer,
+    &image::ImageRenderer,
+    &pdf::PdfRenderer,
+    &table::CsvRenderer,
+    &notebook::NotebookRenderer,
+];
+
+/// Blob which is about to be displayed in the blob view
+pub(crate) struct Blob<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) content: &'a [u8],
+
+    /// Type as detected by libmagic
+    pub(crate) file_type: &'a FileType,
+    /// Mime type detected using the magic number of the content, if any
+    pub(crate) mime: Option<&'static str>,
+}
+
+impl<'a> Blob<'a> {
+    pub(crate) fn new(name: &'a str, content: &'a [u8], file_type: &'a FileType) -> Blob<'a> {
+        Blob {
+            name,
+            content,
+            file_type,
+            mime: infer::get(content).map(|file_type| file_type.mime_type()),
+        }
+    }
+
+    /// Lowercase file extension without the leading dot
+    fn extension(&self) -> Option<String> {
+        Path::new(self.name)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase())
+    }
+
+    fn has_extension(&self, extensions: &[&str]) -> bool {
+        self.extension()
+            .map_or(false, |extension| extensions.contains(&extension.as_str()))
+    }
+
+    fn text(&self) -> Option<&'a str> {
+        std::str::from_utf8(self.content).ok()
+    }
+}
+
+/// Location of the blob which gets rendered
+pub(crate) struct RenderContext {
+    pub(crate) markup: MarkupContext,
+
+    /// URL serving the raw content of the blob, used to embed images and documents
+    pub(crate) raw_url: String,
+
+    /// Git LFS server URL of the repository, without trailing slash. `None` if the repository does not configure one
+    pub(crate) lfs_endpoint: Option<String>,
+}
+
+pub(crate) trait BlobRenderer: Sync {
+    /// Returns true if this renderer is able to display the blob. Should only look at the file name,
+    /// the detected types and at most the start of the content as it is called for every viewed blob.
+    fn matches(&self, blob: &Blob<'_>) -> bool;
+
+    fn render(&self, blob: &Blob<'_>, context: &RenderContext) -> Result<RenderedBlob>;
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum RenderedBlob {
+    Image {
+        src: String,
+    },
+    Pdf {
+        src: String,
+    },
+    Table {
+        headers: Vec<String>,
+        rows: Vec<Vec<String>>,
+        truncated: bool,
+    },
+    Notebook {
+        cells: Vec<notebook::RenderedCell>,
+    },
+    LfsPointer {
+        oid: String,
+        size: u64,
+        href: Option<String>,
+    },
+}
+
+/// Renders the blob using the first matching renderer. Returns `None` if no renderer matches
+/// or the matching renderer failed, in which case the blob should be displayed as text
+pub(crate) fn render(blob: &Blob<'_>, context: &RenderContext) -> Option<RenderedBlob> {
+    let renderer = RENDERERS.iter().find(|renderer| renderer.matches(blob))?;
+
+    match renderer.render(blob, context) {
+        Ok(rendered) => Some(rendered),
+        Err(err) => {
+            warn!("Failed to render {}: {}", blob.name, err);
+            None
+        }
+    }
+}
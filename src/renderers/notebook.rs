@@ -0,0 +1,288 @@
+use crate::highlighting::highlight_lines;
+use crate::markup::{render_markdown, sanitize};
+use crate::renderers::image::svg_data_url;
+use crate::renderers::{Blob, BlobRenderer, RenderContext, RenderedBlob};
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing_unwrap::ResultExt;
+
+// Tracebacks of IPython contain ANSI color codes
+static ANSI_ESCAPE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\x1b\[[0-9;]*[A-Za-z]").unwrap_or_log());
+
+/// Renders Jupyter notebooks (nbformat 4) as list of cells including their outputs
+pub(super) struct NotebookRenderer;
+
+impl BlobRenderer for NotebookRenderer {
+    fn matches(&self, blob: &Blob<'_>) -> bool {
+        blob.has_extension(&["ipynb"])
+    }
+
+    fn render(&self, blob: &Blob<'_>, context: &RenderContext) -> Result<RenderedBlob> {
+        let notebook: Notebook = serde_json::from_slice(blob.content)?;
+
+        if notebook.nbformat < 4 {
+            bail!("Unsupported notebook format version {}", notebook.nbformat);
+        }
+
+        // Code cells are highlighted using a made up file name with the extension of the notebook language
+        let file_name = format!("cell{}", notebook.metadata.file_extension());
+
+        let cells = notebook
+            .cells
+            .into_iter()
+            .map(|cell| cell.render(file_name.as_str(), context))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(RenderedBlob::Notebook { cells })
+    }
+}
+
+#[derive(Deserialize)]
+struct Notebook {
+    nbformat: u32,
+    cells: Vec<Cell>,
+    #[serde(default)]
+    metadata: NotebookMetadata,
+}
+
+#[derive(Default, Deserialize)]
+struct NotebookMetadata {
+    language_info: Option<LanguageInfo>,
+    kernelspec: Option<KernelSpec>,
+}
+
+impl NotebookMetadata {
+    fn file_extension(&self) -> String {
+        if let Some(extension) = self
+            .language_info
+            .as_ref()
+            .and_then(|info| info.file_extension.as_ref())
+        {
+            return extension.to_owned();
+        }
+
+        let language = self
+            .language_info
+            .as_ref()
+            .and_then(|info| info.name.as_deref())
+            .or_else(|| {
+                self.kernelspec
+                    .as_ref()
+                    .and_then(|spec| spec.language.as_deref())
+            })
+            .unwrap_or("python");
+
+        match language.to_lowercase().as_str() {
+            "python" | "python3" => ".py".to_owned(),
+            "julia" => ".jl".to_owned(),
+            "javascript" => ".js".to_owned(),
+            "ruby" => ".rb".to_owned(),
+            "rust" => ".rs".to_owned(),
+            other => format!(".{}", other),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct LanguageInfo {
+    name: Option<String>,
+    file_extension: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct KernelSpec {
+    language: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "cell_type", rename_all = "lowercase")]
+enum Cell {
+    Markdown {
+        source: MultilineString,
+    },
+    Code {
+        source: MultilineString,
+        execution_count: Option<u32>,
+        #[serde(default)]
+        outputs: Vec<Output>,
+    },
+    Raw {
+        source: MultilineString,
+    },
+}
+
+impl Cell {
+    fn render(self, file_name: &str, context: &RenderContext) -> Result<RenderedCell> {
+        Ok(match self {
+            Cell::Markdown { source } => RenderedCell::Markdown {
+                html: render_markdown(source.join().as_str(), &context.markup),
+            },
+            Cell::Code {
+                source,
+                execution_count,
+                outputs,
+            } => RenderedCell::Code {
+                execution_count,
+                lines: highlight_lines(file_name, source.join().as_str())?,
+                outputs: outputs
+                    .into_iter()
+                    .filter_map(|output| output.render(context))
+                    .collect(),
+            },
+            Cell::Raw { source } => RenderedCell::Raw {
+                text: source.join(),
+            },
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "output_type", rename_all = "snake_case")]
+enum Output {
+    Stream {
+        name: String,
+        text: MultilineString,
+    },
+    ExecuteResult {
+        data: HashMap<String, Value>,
+    },
+    DisplayData {
+        data: HashMap<String, Value>,
+    },
+    Error {
+        ename: String,
+        evalue: String,
+        #[serde(default)]
+        traceback: Vec<String>,
+    },
+}
+
+impl Output {
+    fn render(self, context: &RenderContext) -> Option<RenderedOutput> {
+        match self {
+            Output::Stream { name, text } => Some(RenderedOutput::Text {
+                text: text.join(),
+                error: name == "stderr",
+            }),
+            Output::ExecuteResult { data } | Output::DisplayData { data } => {
+                render_mime_bundle(&data, context)
+            }
+            Output::Error {
+                ename,
+                evalue,
+                traceback,
+            } => {
+                let text = if traceback.is_empty() {
+                    format!("{}: {}", ename, evalue)
+                } else {
+                    ANSI_ESCAPE
+                        .replace_all(traceback.join("\n").as_str(), "")
+                        .into_owned()
+                };
+
+                Some(RenderedOutput::Text { text, error: true })
+            }
+        }
+    }
+}
+
+/// Picks the richest representation of an output that can be displayed safely
+fn render_mime_bundle(
+    data: &HashMap<String, Value>,
+    context: &RenderContext,
+) -> Option<RenderedOutput> {
+    let get = |mime: &str| data.get(mime).and_then(|value| mime_value(value).ok());
+
+    for mime in ["image/png", "image/jpeg", "image/gif"] {
+        if let Some(encoded) = get(mime) {
+            let encoded = encoded.split_whitespace().collect::<String>();
+
+            if base64::decode(encoded.as_str()).is_ok() {
+                return Some(RenderedOutput::Image {
+                    src: format!("data:{};base64,{}", mime, encoded),
+                });
+            }
+        }
+    }
+
+    if let Some(svg) = get("image/svg+xml") {
+        return Some(RenderedOutput::Image {
+            src: svg_data_url(svg.as_str()),
+        });
+    }
+
+    if let Some(html) = get("text/html") {
+        return Some(RenderedOutput::Html {
+            html: sanitize(html.as_str(), &context.markup),
+        });
+    }
+
+    if let Some(markdown) = get("text/markdown") {
+        return Some(RenderedOutput::Html {
+            html: render_markdown(markdown.as_str(), &context.markup),
+        });
+    }
+
+    get("text/plain").map(|text| RenderedOutput::Text { text, error: false })
+}
+
+fn mime_value(value: &Value) -> Result<String> {
+    Ok(serde_json::from_value::<MultilineString>(value.clone())?.join())
+}
+
+/// Notebooks store text either as single string or as list of lines
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MultilineString {
+    Single(String),
+    Lines(Vec<String>),
+}
+
+impl MultilineString {
+    fn join(self) -> String {
+        match self {
+            MultilineString::Single(string) => string,
+            MultilineString::Lines(lines) => lines.concat(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum RenderedCell {
+    Markdown {
+        html: String,
+    },
+    Code {
+        execution_count: Option<u32>,
+        lines: Vec<String>,
+        outputs: Vec<RenderedOutput>,
+    },
+    Raw {
+        text: String,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum RenderedOutput {
+    /// Plain text, escaped by the template
+    Text {
+        text: String,
+        error: bool,
+    },
+    Image {
+        src: String,
+    },
+    /// Already sanitized HTML
+    Html {
+        html: String,
+    },
+}
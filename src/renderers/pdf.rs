@@ -0,0 +1,18 @@
+use crate::renderers::{Blob, BlobRenderer, RenderContext, RenderedBlob};
+
+use anyhow::Result;
+
+/// Embeds PDF documents using the PDF viewer of the browser
+pub(super) struct PdfRenderer;
+
+impl BlobRenderer for PdfRenderer {
+    fn matches(&self, blob: &Blob<'_>) -> bool {
+        blob.mime == Some("application/pdf") || blob.has_extension(&["pdf"])
+    }
+
+    fn render(&self, _blob: &Blob<'_>, context: &RenderContext) -> Result<RenderedBlob> {
+        Ok(RenderedBlob::Pdf {
+            src: context.raw_url.clone(),
+        })
+    }
+}
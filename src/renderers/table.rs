@@ -0,0 +1,71 @@
+use crate::renderers::{Blob, BlobRenderer, RenderContext, RenderedBlob};
+use crate::utils::cookie_file::FileType;
+
+use anyhow::Result;
+use csv::ReaderBuilder;
+
+/// Tables with more rows are cut off, the full file can still be viewed raw
+const MAX_ROWS: usize = 1000;
+
+/// Displays comma and tab separated values as a sortable table. The first row is used as header.
+pub(super) struct CsvRenderer;
+
+impl BlobRenderer for CsvRenderer {
+    fn matches(&self, blob: &Blob<'_>) -> bool {
+        blob.has_extension(&["csv", "tsv"]) && !matches!(blob.file_type, FileType::Binary)
+    }
+
+    fn render(&self, blob: &Blob<'_>, _context: &RenderContext) -> Result<RenderedBlob> {
+        let delimiter = if blob.has_extension(&["tsv"]) {
+            b'\t'
+        } else {
+            b','
+        };
+
+        let mut reader = ReaderBuilder::new()
+            .delimiter(delimiter)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(blob.content);
+
+        let mut records = reader.records();
+
+        let headers = match records.next() {
+            Some(record) => record?.iter().map(str::to_owned).collect::<Vec<_>>(),
+            None => Vec::new(),
+        };
+
+        let mut rows = Vec::<Vec<String>>::new();
+        let mut truncated = false;
+
+        for record in records {
+            if rows.len() >= MAX_ROWS {
+                truncated = true;
+                break;
+            }
+
+            rows.push(record?.iter().map(str::to_owned).collect());
+        }
+
+        // Rows may have a different amount of columns, pad them so every column can be sorted
+        let columns = rows
+            .iter()
+            .map(Vec::len)
+            .chain(std::iter::once(headers.len()))
+            .max()
+            .unwrap_or_default();
+
+        let mut headers = headers;
+        headers.resize(columns, String::new());
+
+        for row in rows.iter_mut() {
+            row.resize(columns, String::new());
+        }
+
+        Ok(RenderedBlob::Table {
+            headers,
+            rows,
+            truncated,
+        })
+    }
+}
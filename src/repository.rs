@@ -1,7 +1,8 @@
 use crate::error::{ErrorDisplayType, GitArenaError};
+use crate::git::history::resolve_revision;
 use crate::privileges::privilege;
-use crate::privileges::repo_visibility::RepoVisibility;
 use crate::user::{User, WebUser};
+use crate::utils::oid;
 use crate::{die, err};
 
 use std::future::Future;
@@ -15,9 +16,9 @@ use anyhow::{anyhow, Result};
 use derive_more::{Deref, Display};
 use fs_extra::dir;
 use git2::{Repository as Git2Repository, RepositoryInitOptions};
-use git_repository::refs::file::find::existing::Error as GitoxideFindError;
-use git_repository::refs::file::loose::Reference;
+use git_repository::hash::ObjectId;
 use git_repository::Repository as GitoxideRepository;
+use gitarena_common::database::models::RepoVisibility;
 use serde::Serialize;
 use sqlx::{Executor, FromRow, PgPool, Postgres};
 use tracing_unwrap::OptionExt;
@@ -41,6 +42,9 @@ pub(crate) struct Repository {
 
     pub(crate) archived: bool,
     pub(crate) disabled: bool,
+
+    /// Pushes to branches are rejected unless all new commits carry a verified signature
+    pub(crate) require_signed_commits: bool,
 }
 
 impl Repository {
@@ -217,10 +221,33 @@ pub(crate) struct RepoOwner(pub(crate) String);
 #[display(fmt = "{}", tree)]
 pub(crate) struct Branch {
     pub(crate) gitoxide_repo: GitoxideRepository,
+
+    /// Revision as it was requested, e.g. `main`, `v1.0`, `a1b2c3d` or `HEAD~3`
     pub(crate) tree: String,
-    pub(crate) reference: Reference,
+
+    /// Full name of the reference `tree` resolved to (e.g. `refs/heads/main`).
+    /// `None` if `tree` is a commit id or an expression such as `HEAD~3`.
+    pub(crate) reference: Option<String>,
+
+    /// Commit `tree` resolved to
+    pub(crate) commit: ObjectId,
 }
 
+impl Branch {
+    /// Returns the resolved commit id as a string, which can be passed to the functions in [history](crate::git::history)
+    pub(crate) fn revision(&self) -> String {
+        self.commit.to_string()
+    }
+}
+
+/// Will only be part of [Extensions](actix_web::dev::Extensions) if the requested tree is a full commit id.
+/// Content of such revisions can never change, so handlers serving raw content may send [IMMUTABLE_CACHE_CONTROL].
+#[derive(Debug)]
+pub(crate) struct ImmutableRevision;
+
+/// `Cache-Control` value for raw content of a full commit id, which can be cached forever
+pub(crate) const IMMUTABLE_CACHE_CONTROL: &str = "private, max-age=31536000, immutable";
+
 impl FromRequest for Branch {
     type Error = GitArenaError;
     type Future = Pin<Box<dyn Future<Output = Result<Branch, Self::Error>>>>;
@@ -237,6 +264,9 @@ impl FromRequest for Branch {
 
         let repo_future = Repository::from_request(req, payload);
 
+        // HttpRequest is just a wrapper around `Rc<R>` so .clone() is cheap
+        let request = req.clone();
+
         match req.app_data::<Data<PgPool>>() {
             Some(db_pool) => {
                 // Data<PgPool> is just a wrapper around `Arc<P>` so .clone() is cheap
@@ -246,12 +276,18 @@ impl FromRequest for Branch {
                     // This call exists early if access rights are insufficient, so we don't need to worry about them down the road
                     let repo = repo_future.await?;
 
-                    extract_branch_from_request(db_pool, repo, tree)
+                    let branch = extract_branch_from_request(db_pool, repo, tree)
                         .await
                         .map_err(|err| GitArenaError {
                             source: Arc::new(err),
                             display_type: ErrorDisplayType::Html, // TODO: Check whenever route is err = "html|json|git" etc...
-                        })
+                        })?;
+
+                    if is_commit_id(branch.tree.as_str(), &branch.commit) {
+                        request.extensions_mut().insert(ImmutableRevision);
+                    }
+
+                    Ok(branch)
                 })
             }
             None => Box::pin(async {
@@ -271,13 +307,12 @@ async fn extract_branch_from_request(
 ) -> Result<Branch> {
     let mut transaction = db_pool.begin().await?;
 
+    let libgit2_repo = repo.libgit2(&mut transaction).await?;
     let gitoxide_repo = repo.gitoxide(&mut transaction).await?;
 
-    let reference = match gitoxide_repo.refs.find_loose(tree.as_str()) {
-        Ok(loose_ref) => Ok(loose_ref),
-        Err(GitoxideFindError::Find(err)) => Err(err),
-        Err(GitoxideFindError::NotFound(_)) => die!(NOT_FOUND, "Tree not found"),
-    }?;
+    let (reference, commit) = resolve_revision(&libgit2_repo, tree.as_str())
+        .await
+        .ok_or_else(|| err!(NOT_FOUND, "Tree not found"))?;
 
     transaction.commit().await?;
 
@@ -285,5 +320,11 @@ async fn extract_branch_from_request(
         gitoxide_repo,
         tree,
         reference,
+        commit: oid::from_hex_str(Some(commit.to_string().as_str()))?,
     })
 }
+
+/// Returns true if `tree` is the full (not abbreviated) id of `commit`
+pub(crate) fn is_commit_id(tree: &str, commit: &ObjectId) -> bool {
+    tree.eq_ignore_ascii_case(commit.to_string().as_str())
+}
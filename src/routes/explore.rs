@@ -1,5 +1,6 @@
+use crate::issue::{manage_issues_condition, visibility_condition};
+use crate::languages::popular_languages;
 use crate::prelude::{ContextExtensions, HttpRequestExtensions};
-use crate::privileges::repo_visibility::RepoVisibility;
 use crate::user::WebUser;
 use crate::{err, render_template};
 
@@ -8,6 +9,7 @@ use std::fmt::{Display, Formatter, Result as FmtResult};
 use actix_web::{web, HttpRequest, Responder};
 use anyhow::Result;
 use derive_more::Display;
+use gitarena_common::database::models::RepoVisibility;
 use gitarena_macros::route;
 use qstring::QString;
 use serde::{Deserialize, Serialize};
@@ -39,6 +41,7 @@ pub(crate) async fn explore(
         &get_repositories(&options, &mut transaction).await?,
     )?;
     context.try_insert("options", &options)?;
+    context.try_insert("languages", &popular_languages(20, &mut transaction).await?)?;
     context.try_insert(
         "query_string",
         query_string_without_offset(&query_string).as_str(),
@@ -56,6 +59,16 @@ async fn get_repositories<'e, E: Executor<'e, Database = Postgres>>(
     options: &ExploreOptions<'_>,
     executor: E,
 ) -> Result<Vec<ExploreRepo>> {
+    // User ids are formatted into the query as they are no user input
+    let can_manage_issues = match options.viewer {
+        Some(_) if options.admin => "true".to_owned(),
+        Some(viewer) => manage_issues_condition(viewer.to_string().as_str()),
+        None => "false".to_owned(),
+    };
+    let viewer = options
+        .viewer
+        .map_or_else(|| "null".to_owned(), |viewer| viewer.to_string());
+
     let query = format!("select repositories.id, \
         repositories.name, \
         repositories.description, \
@@ -65,16 +78,21 @@ async fn get_repositories<'e, E: Executor<'e, Database = Postgres>>(
         repositories.archived, \
         repositories.disabled, \
         count(distinct stars.stargazer) as stars, \
-        count(distinct issues.id) filter (where not(issues.closed = true or issues.confidential = true)) as issues \
+        count(distinct issues.id) filter (where issues.closed = false and {}) as issues, \
+        (select language from repository_languages where repo = repositories.id order by bytes desc limit 1) as language \
         from repositories \
         left join stars on repositories.id = stars.repo \
         left join users on repositories.owner = users.id \
         left join issues on repositories.id = issues.repo \
-     {}", options);
+     {}", visibility_condition(can_manage_issues.as_str(), viewer.as_str()), options);
+
+    let mut query = sqlx::query_as::<_, ExploreRepo>(query.as_str());
+
+    if let Some(language) = options.language {
+        query = query.bind(language);
+    }
 
-    Ok(sqlx::query_as::<_, ExploreRepo>(query.as_str())
-        .fetch_all(executor)
-        .await?)
+    Ok(query.fetch_all(executor).await?)
 }
 
 #[derive(FromRow, Serialize, Deserialize, Debug)]
@@ -91,6 +109,8 @@ struct ExploreRepo {
     issues: i64,
     #[sqlx(default)]
     merge_requests: i64,
+    /// Language with the most bytes on the default branch
+    language: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -98,12 +118,19 @@ struct ExploreOptions<'a> {
     archived: bool,
     forked: bool,
     mirrored: bool,
+    language: Option<&'a str>,
     internal: bool,
     disabled: bool,
     sort: &'a str,
     order: Order,
     offset: u32,
     htmx_request: bool,
+
+    // Used to count confidential issues visible to the logged in user
+    #[serde(skip)]
+    viewer: Option<i32>,
+    #[serde(skip)]
+    admin: bool,
 }
 
 impl ExploreOptions<'_> {
@@ -128,6 +155,9 @@ impl ExploreOptions<'_> {
             mirrored: query_string
                 .get("mirror")
                 .map_or_else(|| true, |value| value == "1"),
+            language: query_string
+                .get("language")
+                .filter(|value| !value.is_empty()),
             internal,
             disabled,
             sort,
@@ -136,6 +166,8 @@ impl ExploreOptions<'_> {
                 .get("offset")
                 .map_or_else(|| 0, |value| value.parse::<u32>().unwrap_or(0)),
             htmx_request,
+            viewer: web_user.as_ref().map(|user| user.id),
+            admin: web_user.as_ref().map_or(false, |user| user.admin),
         }
     }
 }
@@ -156,6 +188,11 @@ impl Display for ExploreOptions<'_> {
             f.write_str("repositories.mirrored_from is null and ")?;
         }
 
+        // Bound as the first parameter by `get_repositories`
+        if self.language.is_some() {
+            f.write_str("repositories.id in (select repo from repository_languages where language = $1) and ")?;
+        }
+
         if !self.internal {
             f.write_str("repositories.visibility != 'internal' and ")?;
         }
@@ -6,9 +6,11 @@ mod explore;
 pub(crate) mod not_found;
 pub(crate) mod proxy;
 pub(crate) mod repository;
+mod search;
 pub(crate) mod user;
 
 pub(crate) fn init(config: &mut ServiceConfig) {
     config.service(api::api);
     config.service(explore::explore);
+    config.service(search::search);
 }
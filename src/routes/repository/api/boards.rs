@@ -0,0 +1,551 @@
+use crate::board::{move_card, Board, BoardAutomation, BoardColumn};
+use crate::issue::Issue;
+use crate::label::Label;
+use crate::repository::Repository;
+use crate::routes::repository::issues::check_manage_access;
+use crate::routes::repository::GitRequest;
+use crate::user::WebUser;
+use crate::{die, err};
+
+use actix_web::{web, HttpResponse, Responder};
+use anyhow::Result;
+use gitarena_macros::route;
+use log::info;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+
+/// Columns new boards start with
+const DEFAULT_COLUMNS: [(&str, BoardAutomation); 3] = [
+    ("To do", BoardAutomation::Reopened),
+    ("In progress", BoardAutomation::None),
+    ("Done", BoardAutomation::Closed),
+];
+
+#[route(
+    "/api/repo/{username}/{repository}/boards",
+    method = "GET",
+    err = "json"
+)]
+pub(crate) async fn list_boards(
+    repo: Repository,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let boards = Board::all(repo.id, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(boards))
+}
+
+/// Creates a board with the columns "To do", "In progress" and "Done". Closed issues get moved to "Done"
+#[route(
+    "/api/repo/{username}/{repository}/boards",
+    method = "POST",
+    err = "json"
+)]
+pub(crate) async fn create_board(
+    repo: Repository,
+    uri: web::Path<GitRequest>,
+    web_user: WebUser,
+    body: web::Json<BoardJsonRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let user = check_manage_access(&repo, web_user, &mut transaction).await?;
+
+    let name = body.name.as_deref().map(str::trim).unwrap_or_default();
+    let description = body.description.as_deref().unwrap_or_default();
+    validate_board(repo.id, name, description, None, &mut transaction).await?;
+
+    let board = sqlx::query_as::<_, Board>(
+        "insert into boards (repo, name, description) values ($1, $2, $3) returning *",
+    )
+    .bind(repo.id)
+    .bind(name)
+    .bind(description)
+    .fetch_one(&mut transaction)
+    .await?;
+
+    for (position, (name, automation)) in DEFAULT_COLUMNS.iter().enumerate() {
+        sqlx::query(
+            "insert into board_columns (board, name, position, automation) values ($1, $2, $3, $4)",
+        )
+        .bind(board.id)
+        .bind(name)
+        .bind(position as i32)
+        .bind(automation)
+        .execute(&mut transaction)
+        .await?;
+    }
+
+    info!(
+        "New board {} created in {}/{} by {}",
+        &board.name, &uri.username, &repo.name, &user.username
+    );
+
+    let json = BoardJson::new(board, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Created().json(json))
+}
+
+#[route(
+    "/api/repo/{username}/{repository}/boards/{id}",
+    method = "GET",
+    err = "json"
+)]
+pub(crate) async fn get_board(
+    repo: Repository,
+    uri: web::Path<BoardRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let board = find_board(&repo, uri.id, &mut transaction).await?;
+    let json = BoardJson::new(board, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(json))
+}
+
+#[route(
+    "/api/repo/{username}/{repository}/boards/{id}",
+    method = "PATCH",
+    err = "json"
+)]
+pub(crate) async fn update_board(
+    repo: Repository,
+    uri: web::Path<BoardRequest>,
+    web_user: WebUser,
+    body: web::Json<BoardJsonRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    check_manage_access(&repo, web_user, &mut transaction).await?;
+
+    let board = find_board(&repo, uri.id, &mut transaction).await?;
+
+    let name = body
+        .name
+        .as_deref()
+        .map(str::trim)
+        .unwrap_or(board.name.as_str());
+    let description = body
+        .description
+        .as_deref()
+        .unwrap_or(board.description.as_str());
+    validate_board(repo.id, name, description, Some(board.id), &mut transaction).await?;
+
+    let board = sqlx::query_as::<_, Board>(
+        "update boards set name = $1, description = $2 where id = $3 returning *",
+    )
+    .bind(name)
+    .bind(description)
+    .bind(board.id)
+    .fetch_one(&mut transaction)
+    .await?;
+
+    let json = BoardJson::new(board, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(json))
+}
+
+/// Deletes a board alongside its columns and cards. Issues and labels are kept
+#[route(
+    "/api/repo/{username}/{repository}/boards/{id}",
+    method = "DELETE",
+    err = "json"
+)]
+pub(crate) async fn delete_board(
+    repo: Repository,
+    uri: web::Path<BoardRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    check_manage_access(&repo, web_user, &mut transaction).await?;
+
+    let deleted = sqlx::query("delete from boards where repo = $1 and id = $2")
+        .bind(repo.id)
+        .bind(uri.id)
+        .execute(&mut transaction)
+        .await?
+        .rows_affected();
+
+    if deleted == 0 {
+        die!(NOT_FOUND, "Board not found");
+    }
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Adds a column to the right end of a board
+#[route(
+    "/api/repo/{username}/{repository}/boards/{id}/columns",
+    method = "POST",
+    err = "json"
+)]
+pub(crate) async fn create_column(
+    repo: Repository,
+    uri: web::Path<BoardRequest>,
+    web_user: WebUser,
+    body: web::Json<ColumnJsonRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    check_manage_access(&repo, web_user, &mut transaction).await?;
+
+    let board = find_board(&repo, uri.id, &mut transaction).await?;
+
+    let name = body.name.as_deref().map(str::trim).unwrap_or_default();
+    validate_column(name)?;
+
+    let label = match body.label.as_deref().map(str::trim) {
+        Some("") | None => None,
+        Some(name) => Some(find_label(repo.id, name, &mut transaction).await?),
+    };
+
+    let column = sqlx::query_as::<_, BoardColumn>(
+        "insert into board_columns (board, name, position, label, automation) \
+        values ($1, $2, (select coalesce(max(position) + 1, 0) from board_columns where board = $1), $3, $4) \
+        returning *",
+    )
+    .bind(board.id)
+    .bind(name)
+    .bind(label)
+    .bind(body.automation.unwrap_or_default())
+    .fetch_one(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Created().json(column))
+}
+
+/// Updates a column. `position` moves the column, an empty `label` turns it into a manual column
+#[route(
+    "/api/repo/{username}/{repository}/boards/{id}/columns/{column}",
+    method = "PATCH",
+    err = "json"
+)]
+pub(crate) async fn update_column(
+    repo: Repository,
+    uri: web::Path<ColumnRequest>,
+    web_user: WebUser,
+    body: web::Json<ColumnJsonRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    check_manage_access(&repo, web_user, &mut transaction).await?;
+
+    let board = find_board(&repo, uri.id, &mut transaction).await?;
+    let column = BoardColumn::find(board.id, uri.column, &mut transaction)
+        .await?
+        .ok_or_else(|| err!(NOT_FOUND, "Column not found"))?;
+
+    let name = body
+        .name
+        .as_deref()
+        .map(str::trim)
+        .unwrap_or(column.name.as_str());
+    validate_column(name)?;
+
+    let label = match body.label.as_deref().map(str::trim) {
+        Some("") => None,
+        Some(name) => Some(find_label(repo.id, name, &mut transaction).await?),
+        None => column.label,
+    };
+
+    if let Some(position) = body.position {
+        let mut order = board
+            .columns(&mut transaction)
+            .await?
+            .into_iter()
+            .map(|other| other.id)
+            .filter(|id| *id != column.id)
+            .collect::<Vec<_>>();
+
+        order.insert(position.min(order.len()), column.id);
+
+        sqlx::query(
+            "update board_columns set position = ordered.position - 1 \
+            from unnest($2::integer[]) with ordinality as ordered(id, position) \
+            where board_columns.board = $1 and board_columns.id = ordered.id",
+        )
+        .bind(board.id)
+        .bind(&order)
+        .execute(&mut transaction)
+        .await?;
+    }
+
+    let column = sqlx::query_as::<_, BoardColumn>(
+        "update board_columns set name = $1, label = $2, automation = coalesce($3, automation) \
+        where id = $4 returning *",
+    )
+    .bind(name)
+    .bind(label)
+    .bind(body.automation)
+    .bind(column.id)
+    .fetch_one(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(column))
+}
+
+/// Deletes a column alongside its cards. Label driven columns do not remove their label from the issues
+#[route(
+    "/api/repo/{username}/{repository}/boards/{id}/columns/{column}",
+    method = "DELETE",
+    err = "json"
+)]
+pub(crate) async fn delete_column(
+    repo: Repository,
+    uri: web::Path<ColumnRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    check_manage_access(&repo, web_user, &mut transaction).await?;
+
+    let board = find_board(&repo, uri.id, &mut transaction).await?;
+
+    let deleted = sqlx::query("delete from board_columns where board = $1 and id = $2")
+        .bind(board.id)
+        .bind(uri.column)
+        .execute(&mut transaction)
+        .await?
+        .rows_affected();
+
+    if deleted == 0 {
+        die!(NOT_FOUND, "Column not found");
+    }
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Adds an issue to a board or moves it to another column or position.
+/// Moving an issue into a label driven column adds the label and removes the labels of the other columns
+#[route(
+    "/api/repo/{username}/{repository}/boards/{id}/cards",
+    method = "POST",
+    err = "json"
+)]
+pub(crate) async fn move_issue(
+    repo: Repository,
+    uri: web::Path<BoardRequest>,
+    web_user: WebUser,
+    body: web::Json<CardJsonRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let user = check_manage_access(&repo, web_user, &mut transaction).await?;
+
+    let board = find_board(&repo, uri.id, &mut transaction).await?;
+    let column = BoardColumn::find(board.id, body.column, &mut transaction)
+        .await?
+        .ok_or_else(|| err!(NOT_FOUND, "Column not found"))?;
+    let issue = Issue::find(repo.id, body.issue, &mut transaction)
+        .await?
+        .ok_or_else(|| err!(NOT_FOUND, "Issue not found"))?;
+
+    let issue = move_card(
+        issue,
+        &column,
+        body.position,
+        Some(user.id),
+        &mut transaction,
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(issue))
+}
+
+/// Removes an issue from a board. Issues collected by a label driven column stay on the board until their label is removed
+#[route(
+    "/api/repo/{username}/{repository}/boards/{id}/cards/{index}",
+    method = "DELETE",
+    err = "json"
+)]
+pub(crate) async fn remove_issue(
+    repo: Repository,
+    uri: web::Path<CardRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    check_manage_access(&repo, web_user, &mut transaction).await?;
+
+    let board = find_board(&repo, uri.id, &mut transaction).await?;
+
+    let deleted = sqlx::query(
+        "delete from board_cards where board = $1 \
+        and issue = (select id from issues where repo = $2 and index = $3)",
+    )
+    .bind(board.id)
+    .bind(repo.id)
+    .bind(uri.index)
+    .execute(&mut transaction)
+    .await?
+    .rows_affected();
+
+    if deleted == 0 {
+        die!(NOT_FOUND, "Issue is not on this board");
+    }
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn find_board(
+    repo: &Repository,
+    id: i32,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Board> {
+    Ok(Board::find(repo.id, id, &mut *transaction)
+        .await?
+        .ok_or_else(|| err!(NOT_FOUND, "Board not found"))?)
+}
+
+async fn find_label(
+    repo_id: i32,
+    name: &str,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<i32> {
+    Ok(Label::all(repo_id, &mut *transaction)
+        .await?
+        .into_iter()
+        .find(|label| label.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| err!(NOT_FOUND, "Label {} not found", name))?
+        .id)
+}
+
+async fn validate_board(
+    repo_id: i32,
+    name: &str,
+    description: &str,
+    existing: Option<i32>,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<()> {
+    if name.is_empty() || name.len() > 64 {
+        die!(BAD_REQUEST, "Name must be between 1 and 64 characters long");
+    }
+
+    if description.len() > 256 {
+        die!(
+            BAD_REQUEST,
+            "Description may only be up to 256 characters long"
+        );
+    }
+
+    let (exists,): (bool,) = sqlx::query_as(
+        "select exists(select 1 from boards where repo = $1 and lower(name) = lower($2) and id <> $3 limit 1)",
+    )
+    .bind(repo_id)
+    .bind(name)
+    .bind(existing.unwrap_or_default())
+    .fetch_one(&mut *transaction)
+    .await?;
+
+    if exists {
+        die!(CONFLICT, "Board with this name already exists");
+    }
+
+    Ok(())
+}
+
+fn validate_column(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > 64 {
+        die!(BAD_REQUEST, "Name must be between 1 and 64 characters long");
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct BoardRequest {
+    id: i32,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ColumnRequest {
+    id: i32,
+    column: i32,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CardRequest {
+    id: i32,
+    index: i32,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct BoardJsonRequest {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ColumnJsonRequest {
+    #[serde(default)]
+    name: Option<String>,
+    /// Name of the label driving this column, an empty string turns it into a manual column
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    automation: Option<BoardAutomation>,
+    /// New index of the column from the left
+    #[serde(default)]
+    position: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CardJsonRequest {
+    /// Index (`#42`) of the issue
+    issue: i32,
+    /// Id of the target column
+    column: i32,
+    /// Index within the target column, the issue is added to the end if omitted
+    #[serde(default)]
+    position: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct BoardJson {
+    #[serde(flatten)]
+    board: Board,
+
+    columns: Vec<BoardColumn>,
+}
+
+impl BoardJson {
+    async fn new(board: Board, transaction: &mut Transaction<'_, Postgres>) -> Result<BoardJson> {
+        let columns = board.columns(&mut *transaction).await?;
+
+        Ok(BoardJson { board, columns })
+    }
+}
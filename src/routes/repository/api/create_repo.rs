@@ -2,7 +2,6 @@ use crate::config::get_optional_setting;
 use crate::die;
 use crate::git::write;
 use crate::prelude::HttpRequestExtensions;
-use crate::privileges::repo_visibility::RepoVisibility;
 use crate::repository::Repository;
 use crate::routes::repository::api::CreateJsonResponse;
 use crate::user::{User, WebUser};
@@ -10,6 +9,7 @@ use crate::utils::identifiers::{is_fs_legal, is_reserved_repo_name, is_valid};
 
 use actix_web::{web, HttpRequest, HttpResponse, Responder};
 use anyhow::Result;
+use gitarena_common::database::models::RepoVisibility;
 use gitarena_macros::route;
 use log::info;
 use serde::Deserialize;
@@ -1,6 +1,5 @@
 use crate::config::{get_optional_setting, get_setting};
 use crate::prelude::HttpRequestExtensions;
-use crate::privileges::repo_visibility::RepoVisibility;
 use crate::repository::Repository;
 use crate::routes::repository::api::CreateJsonResponse;
 use crate::user::WebUser;
@@ -10,6 +9,7 @@ use crate::{die, err, Ipc};
 use actix_web::{web, HttpRequest, HttpResponse, Responder};
 use anyhow::{Context, Result};
 use futures_locks::RwLock;
+use gitarena_common::database::models::RepoVisibility;
 use gitarena_common::packets::git::GitImport;
 use gitarena_macros::route;
 use log::info;
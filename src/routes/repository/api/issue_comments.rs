@@ -0,0 +1,244 @@
+use crate::issue::IssueComment;
+use crate::markup::MarkupContext;
+use crate::prelude::HttpRequestExtensions;
+use crate::repository::Repository;
+use crate::routes::repository::issues::{find_visible_issue, timeline};
+use crate::routes::repository::{IssueCommentRequest, IssueRequest};
+use crate::user::WebUser;
+use crate::{die, err};
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use anyhow::Result;
+use gitarena_macros::route;
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres, Transaction};
+
+#[route(
+    "/api/repo/{username}/{repository}/issues/{index}/timeline",
+    method = "GET",
+    err = "json"
+)]
+pub(crate) async fn issue_timeline(
+    repo: Repository,
+    uri: web::Path<IssueRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let (issue, _) = find_visible_issue(&repo, uri.index, &web_user, &mut transaction).await?;
+
+    let markup_context = MarkupContext::new(
+        uri.username.as_str(),
+        repo.name.as_str(),
+        repo.default_branch.as_str(),
+        "",
+    );
+    let timeline = timeline(&issue, &markup_context, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(timeline))
+}
+
+#[route(
+    "/api/repo/{username}/{repository}/issues/{index}/comments",
+    method = "POST",
+    err = "json"
+)]
+pub(crate) async fn create_comment(
+    repo: Repository,
+    uri: web::Path<IssueRequest>,
+    web_user: WebUser,
+    body: web::Json<CommentJsonRequest>,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let (issue, can_manage) =
+        find_visible_issue(&repo, uri.index, &web_user, &mut transaction).await?;
+    let user = web_user.into_user()?;
+
+    if repo.archived {
+        die!(FORBIDDEN, "Repository is archived and thus read-only");
+    }
+
+    if !issue.accepts_comments_from(can_manage) {
+        die!(
+            FORBIDDEN,
+            "Issue is locked, only users which can manage issues may comment"
+        );
+    }
+
+    let text = body.body.trim();
+
+    if text.is_empty() {
+        die!(BAD_REQUEST, "Comment may not be empty");
+    }
+
+    if let Some(parent) = body.parent {
+        let parent = IssueComment::find(issue.id, parent, &mut transaction)
+            .await?
+            .ok_or_else(|| err!(NOT_FOUND, "Parent comment not found"))?;
+
+        if parent.parent.is_some() {
+            die!(
+                BAD_REQUEST,
+                "Replies can only be made to top level comments"
+            );
+        }
+    }
+
+    let comment = IssueComment::create(&issue, body.parent, &user, text, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(if request.is_htmx() {
+        HttpResponse::Ok()
+            .append_header(("hx-refresh", "true"))
+            .finish()
+    } else {
+        HttpResponse::Created().json(comment)
+    })
+}
+
+/// Edits a comment, the previous body is kept as revision. Only the author and moderators may edit comments
+#[route(
+    "/api/repo/{username}/{repository}/issues/{index}/comments/{id}",
+    method = "PATCH",
+    err = "json"
+)]
+pub(crate) async fn update_comment(
+    repo: Repository,
+    uri: web::Path<IssueCommentRequest>,
+    web_user: WebUser,
+    body: web::Json<CommentJsonRequest>,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let (issue, can_manage) =
+        find_visible_issue(&repo, uri.index, &web_user, &mut transaction).await?;
+    let user = web_user.into_user()?;
+
+    if repo.archived {
+        die!(FORBIDDEN, "Repository is archived and thus read-only");
+    }
+
+    let comment = find_comment(issue.id, uri.id, &mut transaction).await?;
+
+    let is_author = comment.author == Some(user.id);
+
+    if !can_manage && !(is_author && issue.accepts_comments_from(can_manage)) {
+        die!(FORBIDDEN, "Insufficient permissions to edit this comment");
+    }
+
+    let text = body.body.trim();
+
+    if text.is_empty() {
+        die!(BAD_REQUEST, "Comment may not be empty");
+    }
+
+    let comment = if text != comment.body {
+        comment.edit(&user, text, &mut transaction).await?
+    } else {
+        comment
+    };
+
+    transaction.commit().await?;
+
+    Ok(if request.is_htmx() {
+        HttpResponse::Ok()
+            .append_header(("hx-refresh", "true"))
+            .finish()
+    } else {
+        HttpResponse::Ok().json(comment)
+    })
+}
+
+/// Soft deletes a comment. Only users which can manage issues may delete comments
+#[route(
+    "/api/repo/{username}/{repository}/issues/{index}/comments/{id}",
+    method = "DELETE",
+    err = "json"
+)]
+pub(crate) async fn delete_comment(
+    repo: Repository,
+    uri: web::Path<IssueCommentRequest>,
+    web_user: WebUser,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let (issue, can_manage) =
+        find_visible_issue(&repo, uri.index, &web_user, &mut transaction).await?;
+    let user = web_user.into_user()?;
+
+    if !can_manage {
+        die!(FORBIDDEN, "Insufficient permissions to delete this comment");
+    }
+
+    let comment = find_comment(issue.id, uri.id, &mut transaction).await?;
+    comment.soft_delete(&user, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(if request.is_htmx() {
+        HttpResponse::Ok()
+            .append_header(("hx-refresh", "true"))
+            .finish()
+    } else {
+        HttpResponse::NoContent().finish()
+    })
+}
+
+/// Returns all revisions of a comment, oldest first. Revisions of deleted comments are only visible to moderators
+#[route(
+    "/api/repo/{username}/{repository}/issues/{index}/comments/{id}/revisions",
+    method = "GET",
+    err = "json"
+)]
+pub(crate) async fn comment_revisions(
+    repo: Repository,
+    uri: web::Path<IssueCommentRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let (issue, can_manage) =
+        find_visible_issue(&repo, uri.index, &web_user, &mut transaction).await?;
+
+    let comment = IssueComment::find(issue.id, uri.id, &mut transaction)
+        .await?
+        .filter(|comment| !comment.is_deleted() || can_manage)
+        .ok_or_else(|| err!(NOT_FOUND, "Comment not found"))?;
+
+    let revisions = comment.revisions(&mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(revisions))
+}
+
+/// Looks up a comment which has not been deleted
+async fn find_comment(
+    issue_id: i32,
+    id: i32,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<IssueComment> {
+    match IssueComment::find(issue_id, id, &mut *transaction).await? {
+        Some(comment) if !comment.is_deleted() => Ok(comment),
+        _ => die!(NOT_FOUND, "Comment not found"),
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CommentJsonRequest {
+    body: String,
+    #[serde(default)]
+    parent: Option<i32>,
+}
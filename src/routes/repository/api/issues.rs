@@ -0,0 +1,754 @@
+use crate::board;
+use crate::config::get_optional_setting;
+use crate::issue::{EventDetails, Issue, IssueEventType};
+use crate::issue_template::{issue_templates, IssueTemplate};
+use crate::label::{exclusive_scope_ids, exclusive_scopes, Label};
+use crate::milestone::Milestone;
+use crate::prelude::HttpRequestExtensions;
+use crate::privileges::privilege;
+use crate::repository::Repository;
+use crate::routes::repository::issues::{find_issues, find_visible_issue, PAGE_SIZE};
+use crate::routes::repository::{GitRequest, IssueRequest};
+use crate::user::{User, WebUser};
+use crate::{die, err};
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use anyhow::Result;
+use gitarena_macros::route;
+use itertools::Itertools;
+use log::info;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+
+/// Maximum amount of issues returned per page
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// Lists issues matching the [query](crate::issue_query::IssueQuery) `q` (all issues if omitted).
+/// Pages are continued using the `next` cursor of the response as `after`.
+#[route(
+    "/api/repo/{username}/{repository}/issues",
+    method = "GET",
+    err = "json"
+)]
+pub(crate) async fn list_issues(
+    repo: Repository,
+    uri: web::Path<GitRequest>,
+    web_user: WebUser,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let query_string = request.q_string();
+
+    let input = query_string.get("q").unwrap_or_default().trim();
+    let limit = query_string
+        .get("limit")
+        .and_then(|value| value.parse::<i64>().ok())
+        .unwrap_or(PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let mut transaction = db_pool.begin().await?;
+
+    let (issues, next) = find_issues(
+        &repo,
+        &web_user,
+        input,
+        query_string.get("after"),
+        limit,
+        &mut transaction,
+    )
+    .await?;
+
+    let domain = get_optional_setting::<String, _>("domain", &mut transaction)
+        .await?
+        .unwrap_or_default();
+
+    let issues = issues
+        .into_iter()
+        .map(|issue| IssueListEntry {
+            url: format!(
+                "{}/{}/{}/issues/{}",
+                domain, &uri.username, &repo.name, issue.index
+            ),
+            issue,
+        })
+        .collect::<Vec<_>>();
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(IssueListJson { issues, next }))
+}
+
+#[route(
+    "/api/repo/{username}/{repository}/issues",
+    method = "POST",
+    err = "json"
+)]
+pub(crate) async fn create_issue(
+    repo: Repository,
+    uri: web::Path<GitRequest>,
+    web_user: WebUser,
+    body: web::Json<CreateJsonRequest>,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let user = web_user.into_user()?;
+
+    if repo.archived {
+        die!(FORBIDDEN, "Repository is archived and thus read-only");
+    }
+
+    let title = body.title.trim();
+    validate_title(title)?;
+
+    let mut transaction = db_pool.begin().await?;
+
+    let issue = Issue::create(
+        repo.id,
+        &user,
+        title,
+        body.body.as_str(),
+        body.confidential,
+        &mut transaction,
+    )
+    .await?;
+
+    let issue = match body.template.as_deref().filter(|name| !name.is_empty()) {
+        Some(file_name) => {
+            let template = issue_templates(&repo, &mut transaction)
+                .await?
+                .into_iter()
+                .find(|template| template.file_name == file_name)
+                .ok_or_else(|| err!(NOT_FOUND, "Issue template {} not found", file_name))?;
+
+            apply_template_defaults(issue, &repo, &user, &template, &mut transaction).await?
+        }
+        None => issue,
+    };
+
+    info!(
+        "New issue #{} created in {}/{} by {}",
+        &issue.index, &uri.username, &repo.name, &user.username
+    );
+
+    let json = IssueJson::new(issue, uri.username.as_str(), &repo, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(if request.is_htmx() {
+        HttpResponse::Ok()
+            .append_header(("hx-redirect", json.url.as_str()))
+            .finish()
+    } else {
+        HttpResponse::Created().json(json)
+    })
+}
+
+#[route(
+    "/api/repo/{username}/{repository}/issues/{index}",
+    method = "GET",
+    err = "json"
+)]
+pub(crate) async fn get_issue(
+    repo: Repository,
+    uri: web::Path<IssueRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let (issue, _) = find_visible_issue(&repo, uri.index, &web_user, &mut transaction).await?;
+    let json = IssueJson::new(issue, uri.username.as_str(), &repo, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(json))
+}
+
+/// Updates an issue. Changing the title or body stores a new revision, `locked`, `confidential`, assignees,
+/// labels and the milestone may only be changed by users which can manage issues
+#[route(
+    "/api/repo/{username}/{repository}/issues/{index}",
+    method = "PATCH",
+    err = "json"
+)]
+pub(crate) async fn update_issue(
+    repo: Repository,
+    uri: web::Path<IssueRequest>,
+    web_user: WebUser,
+    body: web::Json<UpdateJsonRequest>,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let (issue, can_manage) =
+        find_visible_issue(&repo, uri.index, &web_user, &mut transaction).await?;
+    let user = web_user.into_user()?;
+
+    if repo.archived {
+        die!(FORBIDDEN, "Repository is archived and thus read-only");
+    }
+
+    if !issue.is_editable_by(&user, can_manage) {
+        die!(FORBIDDEN, "Insufficient permissions to edit this issue");
+    }
+
+    if (body.locked.is_some()
+        || body.confidential.is_some()
+        || body.assignees.is_some()
+        || body.labels.is_some()
+        || body.milestone.is_some())
+        && !can_manage
+    {
+        die!(
+            FORBIDDEN,
+            "Insufficient permissions to lock, assign, label or change the confidentiality of this issue"
+        );
+    }
+
+    let assignees = match &body.assignees {
+        Some(usernames) => Some(find_assignees(&repo, usernames, &mut transaction).await?),
+        None => None,
+    };
+
+    let labels = match &body.labels {
+        Some(names) => Some(find_labels(repo.id, names, &mut transaction).await?),
+        None => None,
+    };
+
+    // An empty title removes the issue from its milestone
+    let milestone = match body.milestone.as_deref().map(str::trim) {
+        Some("") => Some(None),
+        Some(title) => Some(Some(
+            Milestone::find_by_title(repo.id, title, &mut transaction)
+                .await?
+                .ok_or_else(|| err!(NOT_FOUND, "Milestone {} not found", title))?
+                .id,
+        )),
+        None => None,
+    };
+
+    let title = body.title.as_deref().map(str::trim);
+
+    if let Some(title) = title {
+        validate_title(title)?;
+    }
+
+    if title.is_some() || body.body.is_some() {
+        let current = issue.current_revision(&mut transaction).await?;
+        let current_body = current
+            .as_ref()
+            .map(|revision| revision.body.as_str())
+            .unwrap_or_default();
+
+        let new_title = title.unwrap_or(issue.title.as_str());
+        let new_body = body.body.as_deref().unwrap_or(current_body);
+
+        if new_title != issue.title || new_body != current_body {
+            issue
+                .add_revision(&user, new_title, new_body, &mut transaction)
+                .await?;
+        }
+    }
+
+    if let Some(locked) = body.locked.filter(|locked| *locked != issue.locked) {
+        let event = if locked {
+            IssueEventType::Locked
+        } else {
+            IssueEventType::Unlocked
+        };
+        issue
+            .record_event(
+                Some(user.id),
+                event,
+                EventDetails::default(),
+                &mut transaction,
+            )
+            .await?;
+    }
+
+    if let Some(assignees) = &assignees {
+        let added = assignees
+            .iter()
+            .filter(|id| !issue.assignees.contains(*id))
+            .map(|id| (*id, IssueEventType::Assigned));
+        let removed = issue
+            .assignees
+            .iter()
+            .filter(|id| !assignees.contains(*id))
+            .map(|id| (*id, IssueEventType::Unassigned));
+
+        for (assignee, event) in added.chain(removed) {
+            let details = EventDetails {
+                assignee: Some(assignee),
+                ..Default::default()
+            };
+
+            issue
+                .record_event(Some(user.id), event, details, &mut transaction)
+                .await?;
+        }
+    }
+
+    if let Some(labels) = &labels {
+        let added = labels
+            .iter()
+            .filter(|id| !issue.labels.contains(*id))
+            .map(|id| (*id, IssueEventType::Labeled));
+        let removed = issue
+            .labels
+            .iter()
+            .filter(|id| !labels.contains(*id))
+            .map(|id| (*id, IssueEventType::Unlabeled));
+
+        for (label, event) in added.chain(removed) {
+            let details = EventDetails {
+                label: Some(label),
+                ..Default::default()
+            };
+
+            issue
+                .record_event(Some(user.id), event, details, &mut transaction)
+                .await?;
+        }
+    }
+
+    if let Some(milestone) = milestone.filter(|milestone| *milestone != issue.milestone) {
+        let (event, id) = match milestone {
+            Some(id) => (IssueEventType::Milestoned, id),
+            None => (
+                IssueEventType::Demilestoned,
+                issue.milestone.unwrap_or_default(),
+            ),
+        };
+        let details = EventDetails {
+            milestone: Some(id),
+            ..Default::default()
+        };
+
+        issue
+            .record_event(Some(user.id), event, details, &mut transaction)
+            .await?;
+    }
+
+    let issue = sqlx::query_as::<_, Issue>(
+        "update issues set title = coalesce($1, title), \
+        locked = coalesce($2, locked), \
+        confidential = coalesce($3, confidential), \
+        assignees = coalesce($4, assignees), \
+        labels = coalesce($5, labels), \
+        milestone = case when $6 then $7 else milestone end, \
+        updated_at = current_timestamp \
+        where id = $8 returning *",
+    )
+    .bind(title)
+    .bind(body.locked)
+    .bind(body.confidential)
+    .bind(assignees)
+    .bind(labels)
+    .bind(milestone.is_some())
+    .bind(milestone.flatten())
+    .bind(issue.id)
+    .fetch_one(&mut transaction)
+    .await?;
+
+    let json = IssueJson::new(issue, uri.username.as_str(), &repo, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(if request.is_htmx() {
+        HttpResponse::Ok()
+            .append_header(("hx-redirect", json.url.as_str()))
+            .finish()
+    } else {
+        HttpResponse::Ok().json(json)
+    })
+}
+
+#[route(
+    "/api/repo/{username}/{repository}/issues/{index}/close",
+    method = "POST",
+    err = "json"
+)]
+pub(crate) async fn close_issue(
+    repo: Repository,
+    uri: web::Path<IssueRequest>,
+    web_user: WebUser,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    set_closed(repo, uri, web_user, true, request, db_pool).await
+}
+
+#[route(
+    "/api/repo/{username}/{repository}/issues/{index}/reopen",
+    method = "POST",
+    err = "json"
+)]
+pub(crate) async fn reopen_issue(
+    repo: Repository,
+    uri: web::Path<IssueRequest>,
+    web_user: WebUser,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    set_closed(repo, uri, web_user, false, request, db_pool).await
+}
+
+/// Returns all revisions of the title and body, oldest first
+#[route(
+    "/api/repo/{username}/{repository}/issues/{index}/revisions",
+    method = "GET",
+    err = "json"
+)]
+pub(crate) async fn issue_revisions(
+    repo: Repository,
+    uri: web::Path<IssueRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let (issue, _) = find_visible_issue(&repo, uri.index, &web_user, &mut transaction).await?;
+    let revisions = issue.revisions(&mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(revisions))
+}
+
+async fn set_closed(
+    repo: Repository,
+    uri: web::Path<IssueRequest>,
+    web_user: WebUser,
+    closed: bool,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse> {
+    let mut transaction = db_pool.begin().await?;
+
+    let (issue, can_manage) =
+        find_visible_issue(&repo, uri.index, &web_user, &mut transaction).await?;
+    let user = web_user.into_user()?;
+
+    if repo.archived {
+        die!(FORBIDDEN, "Repository is archived and thus read-only");
+    }
+
+    if !issue.is_editable_by(&user, can_manage) {
+        die!(
+            FORBIDDEN,
+            "Insufficient permissions to close or reopen this issue"
+        );
+    }
+
+    if issue.closed == closed {
+        die!(
+            CONFLICT,
+            "Issue is already {}",
+            if closed { "closed" } else { "open" }
+        );
+    }
+
+    let issue = sqlx::query_as::<_, Issue>(
+        "update issues set closed = $1, updated_at = current_timestamp where id = $2 returning *",
+    )
+    .bind(closed)
+    .bind(issue.id)
+    .fetch_one(&mut transaction)
+    .await?;
+
+    let event = if closed {
+        IssueEventType::Closed
+    } else {
+        IssueEventType::Reopened
+    };
+    issue
+        .record_event(
+            Some(user.id),
+            event,
+            EventDetails::default(),
+            &mut transaction,
+        )
+        .await?;
+
+    let issue = board::automate(issue, event, Some(user.id), &mut transaction).await?;
+
+    let json = IssueJson::new(issue, uri.username.as_str(), &repo, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(if request.is_htmx() {
+        HttpResponse::Ok()
+            .append_header(("hx-refresh", "true"))
+            .finish()
+    } else {
+        HttpResponse::Ok().json(json)
+    })
+}
+
+/// Adds the default labels and assignees of `template` to a newly created issue.
+///
+/// Unlike labels and assignees set by users, unknown labels and users without access to the repository
+/// are skipped instead of failing, as templates may be outdated
+async fn apply_template_defaults(
+    issue: Issue,
+    repo: &Repository,
+    author: &User,
+    template: &IssueTemplate,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Issue> {
+    // Labels added by board automation when the issue was opened come last, so they take precedence
+    let available = Label::all(repo.id, &mut *transaction).await?;
+    let candidates = available
+        .iter()
+        .filter(|label| {
+            template
+                .labels
+                .iter()
+                .any(|name| label.name.eq_ignore_ascii_case(name))
+        })
+        .map(|label| label.id)
+        .filter(|label| !issue.labels.contains(label))
+        .chain(issue.labels.iter().copied())
+        .collect::<Vec<_>>();
+    let labels = exclusive_scope_ids(candidates.as_slice(), &mut *transaction).await?;
+
+    let lowercase = template
+        .assignees
+        .iter()
+        .map(|username| username.to_lowercase())
+        .unique()
+        .collect::<Vec<_>>();
+    let users = sqlx::query_as::<_, User>("select * from users where lower(username) = any($1)")
+        .bind(&lowercase)
+        .fetch_all(&mut *transaction)
+        .await?;
+
+    let mut assignees = Vec::<i32>::with_capacity(users.len());
+
+    for user in users {
+        if !issue.assignees.contains(&user.id)
+            && privilege::check_access(repo, Some(&user), &mut *transaction).await?
+        {
+            assignees.push(user.id);
+        }
+    }
+
+    let added = labels
+        .iter()
+        .filter(|label| !issue.labels.contains(label))
+        .collect::<Vec<_>>();
+
+    if added.is_empty() && assignees.is_empty() {
+        return Ok(issue);
+    }
+
+    for label in added {
+        let details = EventDetails {
+            label: Some(*label),
+            ..Default::default()
+        };
+
+        issue
+            .record_event(
+                Some(author.id),
+                IssueEventType::Labeled,
+                details,
+                &mut *transaction,
+            )
+            .await?;
+    }
+
+    for assignee in assignees.iter() {
+        let details = EventDetails {
+            assignee: Some(*assignee),
+            ..Default::default()
+        };
+
+        issue
+            .record_event(
+                Some(author.id),
+                IssueEventType::Assigned,
+                details,
+                &mut *transaction,
+            )
+            .await?;
+    }
+
+    let assignees = issue
+        .assignees
+        .iter()
+        .copied()
+        .chain(assignees)
+        .collect::<Vec<_>>();
+
+    Ok(sqlx::query_as::<_, Issue>(
+        "update issues set labels = $1, assignees = $2 where id = $3 returning *",
+    )
+    .bind(&labels)
+    .bind(&assignees)
+    .bind(issue.id)
+    .fetch_one(&mut *transaction)
+    .await?)
+}
+
+/// Resolves usernames of assignees to their user ids. Only users with access to the repository can be assigned
+async fn find_assignees(
+    repo: &Repository,
+    usernames: &[String],
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Vec<i32>> {
+    let lowercase = usernames
+        .iter()
+        .map(|username| username.to_lowercase())
+        .unique()
+        .collect::<Vec<_>>();
+
+    let users = sqlx::query_as::<_, User>("select * from users where lower(username) = any($1)")
+        .bind(&lowercase)
+        .fetch_all(&mut *transaction)
+        .await?;
+
+    if let Some(missing) = lowercase.iter().find(|username| {
+        !users
+            .iter()
+            .any(|user| user.username.to_lowercase() == **username)
+    }) {
+        die!(NOT_FOUND, "User {} not found", missing);
+    }
+
+    let mut assignees = Vec::<i32>::with_capacity(users.len());
+
+    for user in users {
+        if !privilege::check_access(repo, Some(&user), &mut *transaction).await? {
+            die!(
+                BAD_REQUEST,
+                "User {} does not have access to this repository",
+                user.username
+            );
+        }
+
+        assignees.push(user.id);
+    }
+
+    Ok(assignees)
+}
+
+/// Resolves label names to their ids. If multiple labels of the same scope are given, only the last one is kept
+async fn find_labels(
+    repo_id: i32,
+    names: &[String],
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Vec<i32>> {
+    let available = Label::all(repo_id, &mut *transaction).await?;
+    let mut labels = Vec::<Label>::with_capacity(names.len());
+
+    for name in names.iter().map(|name| name.trim()).unique() {
+        let label = available
+            .iter()
+            .find(|label| label.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| err!(NOT_FOUND, "Label {} not found", name))?;
+
+        if !labels.iter().any(|other| other.id == label.id) {
+            labels.push(label.clone());
+        }
+    }
+
+    Ok(exclusive_scopes(labels.as_slice()))
+}
+
+fn validate_title(title: &str) -> Result<()> {
+    if title.is_empty() || title.len() > 256 {
+        die!(
+            BAD_REQUEST,
+            "Title must be between 1 and 256 characters long"
+        );
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CreateJsonRequest {
+    title: String,
+    #[serde(default)]
+    body: String,
+    #[serde(default)]
+    confidential: bool,
+    /// File name of the [issue template](crate::issue_template) used, its default labels and assignees get applied
+    #[serde(default)]
+    template: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct UpdateJsonRequest {
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    locked: Option<bool>,
+    #[serde(default)]
+    confidential: Option<bool>,
+    /// Usernames of all assignees, replacing the current ones
+    #[serde(default)]
+    assignees: Option<Vec<String>>,
+    /// Names of all labels, replacing the current ones
+    #[serde(default)]
+    labels: Option<Vec<String>>,
+    /// Title of the milestone, an empty string removes the issue from its milestone
+    #[serde(default)]
+    milestone: Option<String>,
+}
+
+#[derive(Serialize)]
+struct IssueListJson {
+    issues: Vec<IssueListEntry>,
+
+    /// Cursor for the next page, `None` if this is the last page
+    next: Option<String>,
+}
+
+#[derive(Serialize)]
+struct IssueListEntry {
+    #[serde(flatten)]
+    issue: Issue,
+
+    url: String,
+}
+
+#[derive(Serialize)]
+struct IssueJson {
+    #[serde(flatten)]
+    issue: Issue,
+
+    /// Markdown source of the current revision
+    body: String,
+    url: String,
+}
+
+impl IssueJson {
+    async fn new(
+        issue: Issue,
+        owner: &str,
+        repo: &Repository,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<IssueJson> {
+        let domain = get_optional_setting::<String, _>("domain", &mut *transaction)
+            .await?
+            .unwrap_or_default();
+
+        let body = issue
+            .current_revision(&mut *transaction)
+            .await?
+            .map(|revision| revision.body)
+            .unwrap_or_default();
+
+        Ok(IssueJson {
+            url: format!("{}/{}/{}/issues/{}", domain, owner, repo.name, issue.index),
+            issue,
+            body,
+        })
+    }
+}
@@ -0,0 +1,289 @@
+use crate::label::{is_valid_color, Label};
+use crate::privileges::privilege;
+use crate::repository::Repository;
+use crate::routes::repository::issues::check_manage_access;
+use crate::routes::repository::GitRequest;
+use crate::user::{User, WebUser};
+use crate::{die, err};
+
+use actix_web::{web, HttpResponse, Responder};
+use anyhow::Result;
+use gitarena_macros::route;
+use log::info;
+use serde::Deserialize;
+use sqlx::PgPool;
+
+#[route(
+    "/api/repo/{username}/{repository}/labels",
+    method = "GET",
+    err = "json"
+)]
+pub(crate) async fn list_labels(
+    repo: Repository,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let labels = Label::all(repo.id, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(labels))
+}
+
+#[route(
+    "/api/repo/{username}/{repository}/labels",
+    method = "POST",
+    err = "json"
+)]
+pub(crate) async fn create_label(
+    repo: Repository,
+    web_user: WebUser,
+    body: web::Json<LabelJsonRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    check_manage_access(&repo, web_user, &mut transaction).await?;
+
+    let name = body.name.as_deref().map(str::trim).unwrap_or_default();
+    let color = body.color.as_deref().unwrap_or_default();
+    let description = body.description.as_deref().unwrap_or_default();
+
+    validate(name, color, description)?;
+
+    let (exists,): (bool,) = sqlx::query_as(
+        "select exists(select 1 from labels where repo = $1 and lower(name) = lower($2) limit 1)",
+    )
+    .bind(repo.id)
+    .bind(name)
+    .fetch_one(&mut transaction)
+    .await?;
+
+    if exists {
+        die!(CONFLICT, "Label with this name already exists");
+    }
+
+    let label = sqlx::query_as::<_, Label>(
+        "insert into labels (repo, name, color, description) values ($1, $2, $3, $4) returning *",
+    )
+    .bind(repo.id)
+    .bind(name)
+    .bind(color.to_lowercase())
+    .bind(description)
+    .fetch_one(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Created().json(label))
+}
+
+#[route(
+    "/api/repo/{username}/{repository}/labels/{id}",
+    method = "PATCH",
+    err = "json"
+)]
+pub(crate) async fn update_label(
+    repo: Repository,
+    uri: web::Path<LabelRequest>,
+    web_user: WebUser,
+    body: web::Json<LabelJsonRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    check_manage_access(&repo, web_user, &mut transaction).await?;
+
+    let label = Label::find(repo.id, uri.id, &mut transaction)
+        .await?
+        .ok_or_else(|| err!(NOT_FOUND, "Label not found"))?;
+
+    let name = body
+        .name
+        .as_deref()
+        .map(str::trim)
+        .unwrap_or(label.name.as_str());
+    let color = body.color.as_deref().unwrap_or(label.color.as_str());
+    let description = body
+        .description
+        .as_deref()
+        .unwrap_or(label.description.as_str());
+
+    validate(name, color, description)?;
+
+    let (exists,): (bool,) = sqlx::query_as(
+        "select exists(select 1 from labels where repo = $1 and lower(name) = lower($2) and id <> $3 limit 1)",
+    )
+    .bind(repo.id)
+    .bind(name)
+    .bind(label.id)
+    .fetch_one(&mut transaction)
+    .await?;
+
+    if exists {
+        die!(CONFLICT, "Label with this name already exists");
+    }
+
+    let label = sqlx::query_as::<_, Label>(
+        "update labels set name = $1, color = $2, description = $3 where id = $4 returning *",
+    )
+    .bind(name)
+    .bind(color.to_lowercase())
+    .bind(description)
+    .bind(label.id)
+    .fetch_one(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(label))
+}
+
+/// Deletes a label and removes it from all issues
+#[route(
+    "/api/repo/{username}/{repository}/labels/{id}",
+    method = "DELETE",
+    err = "json"
+)]
+pub(crate) async fn delete_label(
+    repo: Repository,
+    uri: web::Path<LabelRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    check_manage_access(&repo, web_user, &mut transaction).await?;
+
+    let label = Label::find(repo.id, uri.id, &mut transaction)
+        .await?
+        .ok_or_else(|| err!(NOT_FOUND, "Label not found"))?;
+
+    sqlx::query("update issues set labels = array_remove(labels, $1) where repo = $2")
+        .bind(label.id)
+        .bind(repo.id)
+        .execute(&mut transaction)
+        .await?;
+
+    sqlx::query("delete from labels where id = $1")
+        .bind(label.id)
+        .execute(&mut transaction)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Copies all labels of another repository into this one. Labels with a name that already exists are skipped
+#[route(
+    "/api/repo/{username}/{repository}/labels/copy",
+    method = "POST",
+    err = "json"
+)]
+pub(crate) async fn copy_labels(
+    repo: Repository,
+    uri: web::Path<GitRequest>,
+    web_user: WebUser,
+    body: web::Json<CopyJsonRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let user = check_manage_access(&repo, web_user, &mut transaction).await?;
+
+    let (owner, name) = body.from.trim().split_once('/').ok_or_else(|| {
+        err!(
+            BAD_REQUEST,
+            "Source repository must be in the format owner/name"
+        )
+    })?;
+
+    let source_owner = User::find_using_name(owner, &mut transaction)
+        .await
+        .ok_or_else(|| err!(NOT_FOUND, "Source repository not found"))?;
+    let source = Repository::open(source_owner, name, &mut transaction)
+        .await
+        .ok_or_else(|| err!(NOT_FOUND, "Source repository not found"))?;
+
+    if !privilege::check_access(&source, Some(&user), &mut transaction).await? {
+        die!(NOT_FOUND, "Source repository not found");
+    }
+
+    if source.id == repo.id {
+        die!(
+            BAD_REQUEST,
+            "Labels can not be copied from the same repository"
+        );
+    }
+
+    let copied = sqlx::query(
+        "insert into labels (repo, name, color, description) \
+        select $1, name, color, description from labels where repo = $2 \
+        on conflict (repo, lower(name)) do nothing",
+    )
+    .bind(repo.id)
+    .bind(source.id)
+    .execute(&mut transaction)
+    .await?
+    .rows_affected();
+
+    info!(
+        "Copied {} labels from {} into {}/{}",
+        copied, &body.from, &uri.username, &repo.name
+    );
+
+    let labels = Label::all(repo.id, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(labels))
+}
+
+fn validate(name: &str, color: &str, description: &str) -> Result<()> {
+    if name.is_empty() || name.len() > 64 {
+        die!(BAD_REQUEST, "Name must be between 1 and 64 characters long");
+    }
+
+    if name.contains(',') || name.contains('"') {
+        die!(BAD_REQUEST, "Name may not contain commas or quotes");
+    }
+
+    if !is_valid_color(color) {
+        die!(
+            BAD_REQUEST,
+            "Color must be a hex color in the format #rrggbb"
+        );
+    }
+
+    if description.len() > 256 {
+        die!(
+            BAD_REQUEST,
+            "Description may only be up to 256 characters long"
+        );
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LabelRequest {
+    id: i32,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LabelJsonRequest {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    color: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CopyJsonRequest {
+    /// Source repository in the format `owner/name`
+    from: String,
+}
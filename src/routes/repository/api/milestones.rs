@@ -0,0 +1,240 @@
+use crate::milestone::Milestone;
+use crate::privileges::privilege;
+use crate::repository::Repository;
+use crate::routes::repository::issues::check_manage_access;
+use crate::user::{User, WebUser};
+use crate::{die, err};
+
+use actix_web::{web, HttpResponse, Responder};
+use anyhow::Result;
+use chrono::NaiveDate;
+use gitarena_macros::route;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+
+#[route(
+    "/api/repo/{username}/{repository}/milestones",
+    method = "GET",
+    err = "json"
+)]
+pub(crate) async fn list_milestones(
+    repo: Repository,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let can_manage =
+        privilege::check_manage_issues(&repo, web_user.as_ref(), &mut transaction).await?;
+    let milestones = Milestone::all(repo.id, &mut transaction).await?;
+
+    let mut results = Vec::<MilestoneJson>::with_capacity(milestones.len());
+
+    for milestone in milestones {
+        results.push(
+            MilestoneJson::new(milestone, web_user.as_ref(), can_manage, &mut transaction).await?,
+        );
+    }
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(results))
+}
+
+#[route(
+    "/api/repo/{username}/{repository}/milestones",
+    method = "POST",
+    err = "json"
+)]
+pub(crate) async fn create_milestone(
+    repo: Repository,
+    web_user: WebUser,
+    body: web::Json<MilestoneJsonRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    check_manage_access(&repo, web_user, &mut transaction).await?;
+
+    let title = body.title.as_deref().map(str::trim).unwrap_or_default();
+    validate_title(repo.id, title, None, &mut transaction).await?;
+
+    let milestone = sqlx::query_as::<_, Milestone>(
+        "insert into milestones (repo, title, description, due_date) values ($1, $2, $3, $4) returning *",
+    )
+    .bind(repo.id)
+    .bind(title)
+    .bind(body.description.as_deref().unwrap_or_default())
+    .bind(body.due_date)
+    .fetch_one(&mut transaction)
+    .await?;
+
+    let json = MilestoneJson::new(milestone, None, true, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Created().json(json))
+}
+
+/// Updates a milestone. Closing a milestone does not close its issues
+#[route(
+    "/api/repo/{username}/{repository}/milestones/{id}",
+    method = "PATCH",
+    err = "json"
+)]
+pub(crate) async fn update_milestone(
+    repo: Repository,
+    uri: web::Path<MilestoneRequest>,
+    web_user: WebUser,
+    body: web::Json<MilestoneJsonRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    check_manage_access(&repo, web_user, &mut transaction).await?;
+
+    let milestone = Milestone::find(repo.id, uri.id, &mut transaction)
+        .await?
+        .ok_or_else(|| err!(NOT_FOUND, "Milestone not found"))?;
+
+    let title = body.title.as_deref().map(str::trim);
+
+    if let Some(title) = title {
+        validate_title(repo.id, title, Some(milestone.id), &mut transaction).await?;
+    }
+
+    let due_date = body
+        .due_date
+        .or(milestone.due_date)
+        .filter(|_| !body.remove_due_date);
+
+    let milestone = sqlx::query_as::<_, Milestone>(
+        "update milestones set title = coalesce($1, title), \
+        description = coalesce($2, description), \
+        due_date = $3, \
+        closed = coalesce($4, closed) \
+        where id = $5 returning *",
+    )
+    .bind(title)
+    .bind(body.description.as_deref())
+    .bind(due_date)
+    .bind(body.closed)
+    .bind(milestone.id)
+    .fetch_one(&mut transaction)
+    .await?;
+
+    let json = MilestoneJson::new(milestone, None, true, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(json))
+}
+
+/// Deletes a milestone. Its issues are kept but no longer part of any milestone
+#[route(
+    "/api/repo/{username}/{repository}/milestones/{id}",
+    method = "DELETE",
+    err = "json"
+)]
+pub(crate) async fn delete_milestone(
+    repo: Repository,
+    uri: web::Path<MilestoneRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    check_manage_access(&repo, web_user, &mut transaction).await?;
+
+    let deleted = sqlx::query("delete from milestones where repo = $1 and id = $2")
+        .bind(repo.id)
+        .bind(uri.id)
+        .execute(&mut transaction)
+        .await?
+        .rows_affected();
+
+    if deleted == 0 {
+        die!(NOT_FOUND, "Milestone not found");
+    }
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn validate_title(
+    repo_id: i32,
+    title: &str,
+    existing: Option<i32>,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<()> {
+    if title.is_empty() || title.len() > 256 {
+        die!(
+            BAD_REQUEST,
+            "Title must be between 1 and 256 characters long"
+        );
+    }
+
+    // Titles are quoted in issue queries (`milestone:"1.0"`)
+    if title.contains('"') {
+        die!(BAD_REQUEST, "Title may not contain quotes");
+    }
+
+    if let Some(milestone) = Milestone::find_by_title(repo_id, title, &mut *transaction).await? {
+        if Some(milestone.id) != existing {
+            die!(CONFLICT, "Milestone with this title already exists");
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct MilestoneRequest {
+    id: i32,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct MilestoneJsonRequest {
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    /// Due date in the format `YYYY-MM-DD`
+    #[serde(default)]
+    due_date: Option<NaiveDate>,
+    /// Removes the due date when updating a milestone
+    #[serde(default)]
+    remove_due_date: bool,
+    #[serde(default)]
+    closed: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct MilestoneJson {
+    #[serde(flatten)]
+    milestone: Milestone,
+
+    closed_issues: i64,
+    total_issues: i64,
+}
+
+impl MilestoneJson {
+    /// Confidential issues are only counted towards the progress if they are visible to `viewer`
+    async fn new(
+        milestone: Milestone,
+        viewer: Option<&User>,
+        can_manage_issues: bool,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<MilestoneJson> {
+        let (closed_issues, total_issues) = milestone
+            .progress(viewer, can_manage_issues, &mut *transaction)
+            .await?;
+
+        Ok(MilestoneJson {
+            milestone,
+            closed_issues,
+            total_issues,
+        })
+    }
+}
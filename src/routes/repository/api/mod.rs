@@ -1,11 +1,20 @@
 use actix_web::web::ServiceConfig;
 use serde::Serialize;
 
+mod boards;
 mod create_repo;
 mod fork_repo;
 mod import_repo;
+mod issue_comments;
+mod issues;
+mod labels;
+mod milestones;
+mod refs;
+mod releases;
+mod repo_history;
 mod repo_meta;
 mod repo_readme;
+mod signatures;
 mod star;
 
 pub(crate) fn init(config: &mut ServiceConfig) {
@@ -13,7 +22,55 @@ pub(crate) fn init(config: &mut ServiceConfig) {
     config.service(import_repo::import);
     config.service(create_repo::create);
     config.service(repo_meta::meta);
+    config.service(repo_meta::update_meta);
+    config.service(repo_history::history);
     config.service(repo_readme::readme);
+    config.service(issues::list_issues);
+    config.service(issues::create_issue);
+    config.service(issues::get_issue);
+    config.service(issues::update_issue);
+    config.service(issues::close_issue);
+    config.service(issues::reopen_issue);
+    config.service(issues::issue_revisions);
+    config.service(issue_comments::issue_timeline);
+    config.service(issue_comments::create_comment);
+    config.service(issue_comments::update_comment);
+    config.service(issue_comments::delete_comment);
+    config.service(issue_comments::comment_revisions);
+    config.service(boards::list_boards);
+    config.service(boards::create_board);
+    config.service(boards::get_board);
+    config.service(boards::update_board);
+    config.service(boards::delete_board);
+    config.service(boards::create_column);
+    config.service(boards::update_column);
+    config.service(boards::delete_column);
+    config.service(boards::move_issue);
+    config.service(boards::remove_issue);
+    config.service(labels::list_labels);
+    config.service(labels::create_label);
+    config.service(labels::copy_labels);
+    config.service(labels::update_label);
+    config.service(labels::delete_label);
+    config.service(milestones::list_milestones);
+    config.service(milestones::create_milestone);
+    config.service(milestones::update_milestone);
+    config.service(milestones::delete_milestone);
+    config.service(refs::list_branches);
+    config.service(refs::list_tags);
+    config.service(refs::get_tag);
+    config.service(signatures::commit_signature);
+    config.service(signatures::tag_signature);
+
+    // latest_release needs to be always above get_release
+    config.service(releases::list_releases);
+    config.service(releases::create_release);
+    config.service(releases::latest_release);
+    config.service(releases::get_release);
+    config.service(releases::update_release);
+    config.service(releases::delete_release);
+    config.service(releases::upload_asset);
+    config.service(releases::delete_asset);
 
     config.service(fork_repo::get_fork_amount);
     config.service(fork_repo::create_fork);
@@ -0,0 +1,90 @@
+use crate::die;
+use crate::prelude::HttpRequestExtensions;
+use crate::repository::Repository;
+use crate::routes::repository::refs::{
+    branch_page, find_tag_entry, tag_page, BranchEntry, RefQuery, TagEntry,
+};
+use crate::routes::repository::ReleaseRequest;
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use anyhow::Result;
+use gitarena_macros::route;
+use serde::Serialize;
+use sqlx::PgPool;
+
+#[route(
+    "/api/repo/{username}/{repository}/branches",
+    method = "GET",
+    err = "json"
+)]
+pub(crate) async fn list_branches(
+    repo: Repository,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let libgit2_repo = repo.libgit2(&mut transaction).await?;
+    let query = RefQuery::parse(&request.q_string());
+
+    let (branches, more) = branch_page(&repo, &libgit2_repo, &query, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(BranchesJsonResponse { branches, more }))
+}
+
+#[route("/api/repo/{username}/{repository}/tags", method = "GET", err = "json")]
+pub(crate) async fn list_tags(
+    repo: Repository,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let libgit2_repo = repo.libgit2(&mut transaction).await?;
+    let query = RefQuery::parse(&request.q_string());
+
+    let (tags, more) = tag_page(&repo, &libgit2_repo, &query, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(TagsJsonResponse { tags, more }))
+}
+
+#[route(
+    "/api/repo/{username}/{repository}/tags/{tag:.*}",
+    method = "GET",
+    err = "json"
+)]
+pub(crate) async fn get_tag(
+    repo: Repository,
+    uri: web::Path<ReleaseRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let libgit2_repo = repo.libgit2(&mut transaction).await?;
+
+    let tag = match find_tag_entry(&repo, &libgit2_repo, uri.tag.as_str(), &mut transaction).await?
+    {
+        Some(tag) => tag,
+        None => die!(NOT_FOUND, "Tag not found"),
+    };
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(tag))
+}
+
+#[derive(Serialize)]
+struct BranchesJsonResponse {
+    branches: Vec<BranchEntry>,
+    more: bool,
+}
+
+#[derive(Serialize)]
+struct TagsJsonResponse {
+    tags: Vec<TagEntry>,
+    more: bool,
+}
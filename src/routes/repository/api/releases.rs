@@ -0,0 +1,614 @@
+use crate::config::get_optional_setting;
+use crate::git::basic_auth;
+use crate::prelude::HttpRequestExtensions;
+use crate::privileges::privilege;
+use crate::release::{
+    generate_notes, is_valid_asset_name, release_dir, tag_exists, Release, ReleaseAsset,
+};
+use crate::repository::Repository;
+use crate::routes::repository::releases::find_visible_release;
+use crate::routes::repository::{GitRequest, ReleaseAssetRequest, ReleaseRequest};
+use crate::user::{User, WebUser};
+use crate::{die, err};
+
+use std::io::ErrorKind;
+
+use actix_multipart::Multipart;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use anyhow::{Context, Result};
+use futures::TryStreamExt;
+use gitarena_macros::{from_config, route};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use tempfile::NamedTempFile;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+#[route(
+    "/api/repo/{username}/{repository}/releases",
+    method = "GET",
+    err = "json"
+)]
+pub(crate) async fn list_releases(
+    repo: Repository,
+    uri: web::Path<GitRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let can_push = privilege::check_push(&repo, web_user.as_ref(), &mut transaction).await?;
+    let releases = Release::all(repo.id, can_push, &mut transaction).await?;
+
+    let mut results = Vec::<ReleaseJson>::with_capacity(releases.len());
+
+    for release in releases {
+        results
+            .push(ReleaseJson::new(release, uri.username.as_str(), &repo, &mut transaction).await?);
+    }
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(results))
+}
+
+#[route(
+    "/api/repo/{username}/{repository}/releases/latest",
+    method = "GET",
+    err = "json"
+)]
+pub(crate) async fn latest_release(
+    repo: Repository,
+    uri: web::Path<GitRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let release = Release::latest(repo.id, &mut transaction)
+        .await?
+        .ok_or_else(|| err!(NOT_FOUND, "No release published yet"))?;
+    let json = ReleaseJson::new(release, uri.username.as_str(), &repo, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(json))
+}
+
+#[route(
+    "/api/repo/{username}/{repository}/releases/tag/{tag}",
+    method = "GET",
+    err = "json"
+)]
+pub(crate) async fn get_release(
+    repo: Repository,
+    uri: web::Path<ReleaseRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let release =
+        find_visible_release(&repo, uri.tag.as_str(), &web_user, &mut transaction).await?;
+    let json = ReleaseJson::new(release, uri.username.as_str(), &repo, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(json))
+}
+
+#[route(
+    "/api/repo/{username}/{repository}/releases",
+    method = "POST",
+    err = "json"
+)]
+pub(crate) async fn create_release(
+    uri: web::Path<GitRequest>,
+    web_user: WebUser,
+    body: web::Json<CreateJsonRequest>,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let (repo, user) = check_api_client_access(
+        uri.username.as_str(),
+        uri.repository.as_str(),
+        web_user,
+        &request,
+        &mut transaction,
+    )
+    .await?;
+    let body = body.into_inner();
+
+    let title = body.title.unwrap_or_else(|| body.tag.clone());
+
+    if title.is_empty() || title.len() > 256 {
+        die!(
+            BAD_REQUEST,
+            "Title must be between 1 and 256 characters long"
+        );
+    }
+
+    if Release::find(repo.id, body.tag.as_str(), &mut transaction)
+        .await?
+        .is_some()
+    {
+        die!(CONFLICT, "Release for this tag already exists");
+    }
+
+    let libgit2_repo = repo.libgit2(&mut transaction).await?;
+
+    if !tag_exists(&libgit2_repo, body.tag.as_str()) {
+        die!(NOT_FOUND, "Tag not found");
+    }
+
+    let mut notes = body.notes.unwrap_or_default();
+
+    if body.generate_notes {
+        let previous_tag =
+            Release::previous_tag(repo.id, body.tag.as_str(), &mut transaction).await?;
+        let generated = generate_notes(
+            &libgit2_repo,
+            body.tag.as_str(),
+            previous_tag.as_deref(),
+            &mut transaction,
+        )
+        .await?;
+
+        if !notes.is_empty() {
+            notes.push_str("\n\n");
+        }
+
+        notes.push_str(generated.as_str());
+    }
+
+    let release = sqlx::query_as::<_, Release>(
+        "insert into releases (repo, tag, title, notes, author, draft, prerelease, published_at) \
+        values ($1, $2, $3, $4, $5, $6, $7, case when $6 then null else current_timestamp end) returning *",
+    )
+    .bind(repo.id)
+    .bind(body.tag.as_str())
+    .bind(title.as_str())
+    .bind(notes.as_str())
+    .bind(user.id)
+    .bind(body.draft)
+    .bind(body.prerelease)
+    .fetch_one(&mut transaction)
+    .await?;
+
+    info!(
+        "New release {} created in {}/{} by {}",
+        &release.tag, &uri.username, &repo.name, &user.username
+    );
+
+    let json = ReleaseJson::new(release, uri.username.as_str(), &repo, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Created().json(json))
+}
+
+/// Updates a release. Publishing a draft (setting `draft` to false) sets the publish date
+#[route(
+    "/api/repo/{username}/{repository}/releases/tag/{tag}",
+    method = "PATCH",
+    err = "json"
+)]
+pub(crate) async fn update_release(
+    repo: Repository,
+    uri: web::Path<ReleaseRequest>,
+    web_user: WebUser,
+    body: web::Json<UpdateJsonRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let user = web_user.into_user()?;
+    check_write_access(&repo, &user, &mut transaction).await?;
+
+    let release = Release::find(repo.id, uri.tag.as_str(), &mut transaction)
+        .await?
+        .ok_or_else(|| err!(NOT_FOUND, "Release not found"))?;
+
+    if let Some(title) = &body.title {
+        if title.is_empty() || title.len() > 256 {
+            die!(
+                BAD_REQUEST,
+                "Title must be between 1 and 256 characters long"
+            );
+        }
+    }
+
+    let release = sqlx::query_as::<_, Release>(
+        "update releases set title = coalesce($1, title), \
+        notes = coalesce($2, notes), \
+        draft = coalesce($3, draft), \
+        prerelease = coalesce($4, prerelease), \
+        published_at = case when coalesce($3, draft) then null else coalesce(published_at, current_timestamp) end \
+        where id = $5 returning *",
+    )
+    .bind(body.title.as_deref())
+    .bind(body.notes.as_deref())
+    .bind(body.draft)
+    .bind(body.prerelease)
+    .bind(release.id)
+    .fetch_one(&mut transaction)
+    .await?;
+
+    let json = ReleaseJson::new(release, uri.username.as_str(), &repo, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(json))
+}
+
+/// Deletes a release including all its assets. The tag itself is kept
+#[route(
+    "/api/repo/{username}/{repository}/releases/tag/{tag}",
+    method = "DELETE",
+    err = "json"
+)]
+pub(crate) async fn delete_release(
+    repo: Repository,
+    uri: web::Path<ReleaseRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let releases_dir: String = from_config!("releases.dir" => String);
+
+    let mut transaction = db_pool.begin().await?;
+
+    let user = web_user.into_user()?;
+    check_write_access(&repo, &user, &mut transaction).await?;
+
+    let release = Release::find(repo.id, uri.tag.as_str(), &mut transaction)
+        .await?
+        .ok_or_else(|| err!(NOT_FOUND, "Release not found"))?;
+
+    sqlx::query("delete from releases where id = $1")
+        .bind(release.id)
+        .execute(&mut transaction)
+        .await?;
+
+    transaction.commit().await?;
+
+    // Files are only removed once the release is gone, a leftover directory is harmless
+    match fs::remove_dir_all(release_dir(releases_dir.as_str(), release.id)).await {
+        Err(err) if err.kind() != ErrorKind::NotFound => warn!(
+            "Failed to remove assets of deleted release id {}: {}",
+            release.id, err
+        ),
+        _ => {}
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Uploads an asset using `multipart/form-data`. The file name can be overridden using the `name` query parameter
+#[route(
+    "/api/repo/{username}/{repository}/releases/tag/{tag}/assets",
+    method = "POST",
+    err = "json"
+)]
+pub(crate) async fn upload_asset(
+    uri: web::Path<ReleaseRequest>,
+    web_user: WebUser,
+    mut payload: Multipart,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let (releases_dir, max_size): (String, i32) = from_config!(
+        "releases.dir" => String,
+        "releases.max_asset_size" => i32
+    );
+
+    let mut transaction = db_pool.begin().await?;
+
+    let (repo, user) = check_api_client_access(
+        uri.username.as_str(),
+        uri.repository.as_str(),
+        web_user,
+        &request,
+        &mut transaction,
+    )
+    .await?;
+
+    let release = Release::find(repo.id, uri.tag.as_str(), &mut transaction)
+        .await?
+        .ok_or_else(|| err!(NOT_FOUND, "Release not found"))?;
+
+    let mut field = match payload.try_next().await {
+        Ok(Some(field)) => field,
+        Ok(None) => die!(BAD_REQUEST, "No multipart field found"),
+        Err(err) => return Err(err.into()),
+    };
+
+    let query_string = request.q_string();
+    let name = match query_string.get("name") {
+        Some(name) => name.to_owned(),
+        None => field
+            .content_disposition()
+            .get_filename()
+            .ok_or_else(|| err!(BAD_REQUEST, "No file name"))?
+            .to_owned(),
+    };
+
+    if !is_valid_asset_name(name.as_str()) {
+        die!(BAD_REQUEST, "Invalid file name");
+    }
+
+    if ReleaseAsset::find(release.id, name.as_str(), &mut transaction)
+        .await?
+        .is_some()
+    {
+        die!(CONFLICT, "Asset with this name already exists");
+    }
+
+    let content_type = field.content_type().to_string();
+
+    let directory = release_dir(releases_dir.as_str(), release.id);
+    fs::create_dir_all(directory.as_str()).await?;
+
+    // Write into a temporary file first so aborted uploads never end up as assets
+    let temp_file = NamedTempFile::new_in(directory.as_str())?;
+    let mut file = fs::File::from_std(temp_file.reopen()?);
+
+    let mut hasher = Sha256::new();
+    let mut size = 0_usize;
+
+    while let Some(chunk) = field
+        .try_next()
+        .await
+        .context("Failed to read multipart data chunk")?
+    {
+        size += chunk.len();
+
+        if size > max_size.max(0) as usize {
+            die!(PAYLOAD_TOO_LARGE, "Asset exceeds the maximum allowed size");
+        }
+
+        hasher.update(chunk.as_ref());
+        file.write_all(chunk.as_ref()).await?;
+    }
+
+    file.flush().await?;
+
+    let asset = sqlx::query_as::<_, ReleaseAsset>(
+        "insert into release_assets (release, name, content_type, size, sha256, uploader) \
+        values ($1, $2, $3, $4, $5, $6) returning *",
+    )
+    .bind(release.id)
+    .bind(name.as_str())
+    .bind(content_type.as_str())
+    .bind(size as i64)
+    .bind(hex::encode(hasher.finalize()))
+    .bind(user.id)
+    .fetch_one(&mut transaction)
+    .await?;
+
+    let domain = get_optional_setting::<String, _>("domain", &mut transaction)
+        .await?
+        .unwrap_or_default();
+
+    let fs_path = asset.fs_path(releases_dir.as_str());
+
+    temp_file
+        .persist(fs_path.as_str())
+        .context("Failed to save release asset")?;
+
+    // Assets without a row would never be cleaned up
+    if let Err(err) = transaction.commit().await {
+        if let Err(err) = fs::remove_file(fs_path.as_str()).await {
+            warn!("Failed to remove orphaned asset {}: {}", &fs_path, err);
+        }
+
+        return Err(err.into());
+    }
+
+    info!(
+        "Asset {} ({} bytes) uploaded to release {} in {}/{} by {}",
+        &asset.name, size, &release.tag, &uri.username, &repo.name, &user.username
+    );
+
+    Ok(HttpResponse::Created().json(AssetJson::new(
+        asset,
+        domain.as_str(),
+        uri.username.as_str(),
+        &repo,
+        release.tag.as_str(),
+    )))
+}
+
+#[route(
+    "/api/repo/{username}/{repository}/releases/tag/{tag}/assets/{name}",
+    method = "DELETE",
+    err = "json"
+)]
+pub(crate) async fn delete_asset(
+    uri: web::Path<ReleaseAssetRequest>,
+    web_user: WebUser,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let releases_dir: String = from_config!("releases.dir" => String);
+
+    let mut transaction = db_pool.begin().await?;
+
+    let (repo, _) = check_api_client_access(
+        uri.username.as_str(),
+        uri.repository.as_str(),
+        web_user,
+        &request,
+        &mut transaction,
+    )
+    .await?;
+
+    let release = Release::find(repo.id, uri.tag.as_str(), &mut transaction)
+        .await?
+        .ok_or_else(|| err!(NOT_FOUND, "Release not found"))?;
+    let asset = ReleaseAsset::find(release.id, uri.name.as_str(), &mut transaction)
+        .await?
+        .ok_or_else(|| err!(NOT_FOUND, "Asset not found"))?;
+
+    sqlx::query("delete from release_assets where id = $1")
+        .bind(asset.id)
+        .execute(&mut transaction)
+        .await?;
+
+    transaction.commit().await?;
+
+    // The file is only removed once the asset is gone, a leftover file is harmless
+    match fs::remove_file(asset.fs_path(releases_dir.as_str())).await {
+        Err(err) if err.kind() != ErrorKind::NotFound => warn!(
+            "Failed to remove file of deleted asset id {}: {}",
+            asset.id, err
+        ),
+        _ => {}
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Validates that `user` is allowed to modify releases of this repository
+async fn check_write_access(
+    repo: &Repository,
+    user: &User,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<()> {
+    if repo.archived {
+        die!(FORBIDDEN, "Repository is archived and thus read-only");
+    }
+
+    if !privilege::check_push(repo, Some(user), &mut *transaction).await? {
+        die!(FORBIDDEN, "Insufficient permissions to manage releases");
+    }
+
+    Ok(())
+}
+
+/// Looks up the repository for endpoints used by CI pipelines and returns it alongside the user if they're allowed
+/// to modify its releases. As these clients have no session, they may authenticate using basic auth instead
+async fn check_api_client_access(
+    username: &str,
+    repository: &str,
+    web_user: WebUser,
+    request: &HttpRequest,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(Repository, User)> {
+    let user = match web_user {
+        WebUser::Authenticated(user) => user,
+        WebUser::Anonymous if basic_auth::is_present(request).await => {
+            basic_auth::authenticate(request, &mut *transaction).await?
+        }
+        WebUser::Anonymous => die!(UNAUTHORIZED, "Not authenticated"),
+    };
+
+    // The repository extractor only knows about the session, so private repositories are looked up here
+    let owner = User::find_using_name(username, &mut *transaction)
+        .await
+        .ok_or_else(|| err!(NOT_FOUND, "Repository not found"))?;
+    let repo = Repository::open(owner, repository, &mut *transaction)
+        .await
+        .ok_or_else(|| err!(NOT_FOUND, "Repository not found"))?;
+
+    if !privilege::check_access(&repo, Some(&user), &mut *transaction).await? {
+        die!(NOT_FOUND, "Repository not found");
+    }
+
+    check_write_access(&repo, &user, transaction).await?;
+
+    Ok((repo, user))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CreateJsonRequest {
+    tag: String,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    notes: Option<String>,
+    #[serde(default)]
+    generate_notes: bool,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct UpdateJsonRequest {
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    notes: Option<String>,
+    #[serde(default)]
+    draft: Option<bool>,
+    #[serde(default)]
+    prerelease: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct ReleaseJson {
+    #[serde(flatten)]
+    release: Release,
+
+    url: String,
+    assets: Vec<AssetJson>,
+}
+
+impl ReleaseJson {
+    async fn new(
+        release: Release,
+        owner: &str,
+        repo: &Repository,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<ReleaseJson> {
+        let domain = get_optional_setting::<String, _>("domain", &mut *transaction)
+            .await?
+            .unwrap_or_default();
+
+        let assets = release
+            .assets(&mut *transaction)
+            .await?
+            .into_iter()
+            .map(|asset| AssetJson::new(asset, domain.as_str(), owner, repo, release.tag.as_str()))
+            .collect();
+
+        Ok(ReleaseJson {
+            url: format!(
+                "{}/{}/{}/releases/tag/{}",
+                domain, owner, repo.name, release.tag
+            ),
+            release,
+            assets,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct AssetJson {
+    #[serde(flatten)]
+    asset: ReleaseAsset,
+
+    download_url: String,
+}
+
+impl AssetJson {
+    fn new(
+        asset: ReleaseAsset,
+        domain: &str,
+        owner: &str,
+        repo: &Repository,
+        tag: &str,
+    ) -> AssetJson {
+        AssetJson {
+            download_url: format!(
+                "{}/{}/{}/releases/download/{}/{}",
+                domain, owner, repo.name, tag, asset.name
+            ),
+            asset,
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use crate::git::history::path_history;
+use crate::prelude::{
+    link_co_authors, HttpRequestExtensions, LibGit2CommitExtensions, LibGit2SignatureExtensions,
+};
+use crate::repository::{Branch, Repository};
+use crate::routes::repository::blobs::{paging_oids, BlobRequest};
+use crate::signatures::{verify_commits, Verification};
+use crate::templates::web::GitCommit;
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use anyhow::Result;
+use gitarena_macros::route;
+use serde::Serialize;
+use sqlx::PgPool;
+
+#[route(
+    "/api/repo/{username}/{repository}/tree/{tree}/history/{blob:.*}",
+    method = "GET",
+    err = "json"
+)]
+pub(crate) async fn history(
+    repo: Repository,
+    branch: Branch,
+    uri: web::Path<BlobRequest>,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let libgit2_repo = repo.libgit2(&mut transaction).await?;
+    let mailmap = libgit2_repo.mailmap().ok();
+    let revision = branch.revision();
+
+    let query_string = request.q_string();
+    let (after, before) = paging_oids(&query_string)?;
+    let follow = query_string.get("follow") != Some("0");
+    let limit = query_string
+        .get("limit")
+        .and_then(|limit| limit.parse::<usize>().ok())
+        .unwrap_or(20)
+        .clamp(1, 100);
+
+    let entries = path_history(
+        &libgit2_repo,
+        revision.as_str(),
+        uri.blob.as_str(),
+        follow,
+        after,
+        before,
+        limit,
+    )
+    .await?;
+
+    let mut results = Vec::<HistoryJsonEntry>::with_capacity(entries.len());
+
+    let commit_ids = entries.iter().map(|entry| entry.commit).collect::<Vec<_>>();
+    let mut signatures = verify_commits(
+        &repo,
+        &libgit2_repo,
+        commit_ids.as_slice(),
+        &mut transaction,
+    )
+    .await?;
+
+    for entry in entries {
+        let commit = libgit2_repo.find_commit(entry.commit)?;
+        let (name, uid, email) = commit
+            .author()
+            .try_disassemble(mailmap.as_ref(), &mut transaction)
+            .await;
+
+        results.push(HistoryJsonEntry {
+            commit: GitCommit {
+                oid: format!("{}", commit.id()),
+                message: commit.message().unwrap_or_default().to_owned(),
+                time: commit.time().seconds(),
+                date: None,
+                author_name: name,
+                author_uid: uid,
+                author_email: email,
+                co_authors: commit.co_authors(mailmap.as_ref()),
+            },
+            signature: signatures.remove(&commit.id().to_string()),
+            path: entry.path,
+            renamed_from: entry.renamed_from,
+        });
+    }
+
+    link_co_authors(
+        results.iter_mut().map(|entry| &mut entry.commit),
+        &mut transaction,
+    )
+    .await;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(results))
+}
+
+#[derive(Serialize)]
+struct HistoryJsonEntry {
+    #[serde(flatten)]
+    commit: GitCommit,
+    /// `None` if the commit is not signed
+    signature: Option<Verification>,
+
+    path: String,
+    renamed_from: Option<String>,
+}
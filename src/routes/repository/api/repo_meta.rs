@@ -1,10 +1,52 @@
+use crate::die;
+use crate::privileges::privilege;
 use crate::repository::Repository;
+use crate::user::WebUser;
 
-use actix_web::{HttpResponse, Responder};
+use actix_web::{web, HttpResponse, Responder};
 use anyhow::Result;
 use gitarena_macros::route;
+use serde::Deserialize;
+use sqlx::PgPool;
 
 #[route("/api/repo/{username}/{repository}", method = "GET", err = "json")]
 pub(crate) async fn meta(repo: Repository) -> Result<impl Responder> {
     Ok(HttpResponse::Ok().json(repo))
 }
+
+#[route("/api/repo/{username}/{repository}", method = "PATCH", err = "json")]
+pub(crate) async fn update_meta(
+    repo: Repository,
+    web_user: WebUser,
+    body: web::Json<UpdateJsonRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let user = web_user.into_user()?;
+    let mut transaction = db_pool.begin().await?;
+
+    if !privilege::check_admin(&repo, Some(&user), &mut transaction).await? {
+        die!(
+            FORBIDDEN,
+            "Insufficient permissions to change repository settings"
+        );
+    }
+
+    let repo = sqlx::query_as::<_, Repository>(
+        "update repositories set require_signed_commits = coalesce($1, require_signed_commits) \
+        where id = $2 returning *",
+    )
+    .bind(body.require_signed_commits)
+    .bind(repo.id)
+    .fetch_one(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(repo))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct UpdateJsonRequest {
+    #[serde(default)]
+    require_signed_commits: Option<bool>,
+}
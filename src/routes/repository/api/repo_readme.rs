@@ -1,8 +1,10 @@
 use crate::err;
-use crate::git::utils::{read_blob_content, repo_files_at_ref};
+use crate::git::utils::{read_blob_content, repo_files_at_commit};
+use crate::markup::{render, MarkupContext};
 use crate::repository::{Branch, Repository};
+use crate::routes::repository::GitTreeRequest;
 
-use actix_web::{HttpResponse, Responder};
+use actix_web::{web, HttpResponse, Responder};
 use anyhow::Result;
 use bstr::ByteSlice;
 use git_repository::objs::Tree;
@@ -14,19 +16,17 @@ use serde_json::json;
     method = "GET",
     err = "json"
 )]
-pub(crate) async fn readme(_repo: Repository, branch: Branch) -> Result<impl Responder> {
+pub(crate) async fn readme(
+    repo: Repository,
+    branch: Branch,
+    uri: web::Path<GitTreeRequest>,
+) -> Result<impl Responder> {
     let gitoxide_repo = branch.gitoxide_repo;
 
     let mut buffer = Vec::<u8>::new();
     let store = gitoxide_repo.objects.clone();
 
-    let tree_ref = repo_files_at_ref(
-        &branch.reference,
-        store.clone(),
-        &gitoxide_repo,
-        &mut buffer,
-    )
-    .await?;
+    let tree_ref = repo_files_at_commit(&branch.commit, store.clone(), &mut buffer).await?;
     let tree = Tree::from(tree_ref);
 
     let entry = tree
@@ -39,8 +39,17 @@ pub(crate) async fn readme(_repo: Repository, branch: Branch) -> Result<impl Res
 
     let content = read_blob_content(entry.oid.as_ref(), store).await?;
 
+    let context = MarkupContext::new(
+        uri.username.as_str(),
+        repo.name.as_str(),
+        uri.tree.as_str(),
+        name,
+    );
+    let html = render(name, content.as_str(), &context).await;
+
     Ok(HttpResponse::Ok().json(json!({
         "file_name": name,
-        "content": content
+        "content": content,
+        "html": html
     })))
 }
@@ -0,0 +1,92 @@
+use crate::die;
+use crate::repository::Repository;
+use crate::signatures::{verify_commits, verify_tag, Verification};
+
+use actix_web::{web, HttpResponse, Responder};
+use anyhow::Result;
+use git2::{ObjectType, Oid};
+use gitarena_macros::route;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+#[route(
+    "/api/repo/{username}/{repository}/commit/{object}/signature",
+    method = "GET",
+    err = "json"
+)]
+pub(crate) async fn commit_signature(
+    repo: Repository,
+    uri: web::Path<ObjectRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let libgit2_repo = repo.libgit2(&mut transaction).await?;
+
+    let oid = match Oid::from_str(uri.object.as_str()) {
+        Ok(oid) if libgit2_repo.find_commit(oid).is_ok() => oid,
+        _ => die!(NOT_FOUND, "Commit not found"),
+    };
+
+    let mut signatures = verify_commits(&repo, &libgit2_repo, &[oid], &mut transaction).await?;
+    let signature = signatures.remove(&oid.to_string());
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(SignatureJsonResponse::new(signature)))
+}
+
+#[route(
+    "/api/repo/{username}/{repository}/tag/{object}/signature",
+    method = "GET",
+    err = "json"
+)]
+pub(crate) async fn tag_signature(
+    repo: Repository,
+    uri: web::Path<ObjectRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let libgit2_repo = repo.libgit2(&mut transaction).await?;
+
+    let target = match libgit2_repo
+        .find_reference(format!("refs/tags/{}", uri.object).as_str())
+        .ok()
+        .and_then(|reference| reference.target())
+    {
+        Some(target) => target,
+        None => die!(NOT_FOUND, "Tag not found"),
+    };
+
+    // Lightweight tags point directly to a commit and cannot be signed
+    let signature = match libgit2_repo.find_object(target, None)?.kind() {
+        Some(ObjectType::Tag) => verify_tag(&repo, &libgit2_repo, target, &mut transaction).await?,
+        _ => None,
+    };
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(SignatureJsonResponse::new(signature)))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ObjectRequest {
+    object: String,
+}
+
+#[derive(Serialize)]
+struct SignatureJsonResponse {
+    signed: bool,
+    #[serde(flatten)]
+    verification: Option<Verification>,
+}
+
+impl SignatureJsonResponse {
+    fn new(verification: Option<Verification>) -> SignatureJsonResponse {
+        SignatureJsonResponse {
+            signed: verification.is_some(),
+            verification,
+        }
+    }
+}
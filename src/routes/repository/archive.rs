@@ -1,205 +1,573 @@
-use crate::git::utils::{read_raw_blob_content, repo_files_at_ref};
-use crate::repository::{Branch, Repository};
+use crate::die;
+use crate::git::archive::ArchiveCommit;
+use crate::git::attributes::GitAttributes;
+use crate::git::utils::{read_raw_blob_content, repo_files_at_commit};
+use crate::repository::{is_commit_id, Branch, Repository, IMMUTABLE_CACHE_CONTROL};
 
-use std::borrow::Borrow;
 use std::io::{Cursor, Write};
-use std::path::Path;
 use std::sync::Arc;
 
-use actix_web::http::header::CONTENT_DISPOSITION;
-use actix_web::{HttpResponse, Responder};
+use actix_web::http::header::{CACHE_CONTROL, CONTENT_DISPOSITION};
+use actix_web::{rt, web, HttpResponse, HttpResponseBuilder, Responder};
 use anyhow::Result;
-use async_compression::tokio::write::GzipEncoder;
+use async_compression::tokio::write::{BzEncoder, GzipEncoder, XzEncoder, ZstdEncoder};
 use async_recursion::async_recursion;
 use bstr::ByteSlice;
+use chrono::{Datelike, Timelike};
+use git2::Oid;
 use git_repository::objs::tree::EntryMode;
 use git_repository::objs::Tree;
 use git_repository::odb::pack::FindExt;
 use git_repository::odb::Store;
 use gitarena_macros::route;
-use tokio_tar::{Builder as TarBuilder, Header as TarHeader};
+use log::warn;
+use sqlx::PgPool;
+use tokio::io::{AsyncWrite, AsyncWriteExt, DuplexStream};
+use tokio_tar::{Builder as TarBuilder, EntryType, Header as TarHeader};
+use tokio_util::io::ReaderStream;
 use zip::write::FileOptions as ZipFileOptions;
-use zip::ZipWriter;
+use zip::{CompressionMethod, DateTime as ZipDateTime, ZipWriter};
+
+/// Size of the in-memory pipe between the archive writer and the response body
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Max total size of all files in a zip archive. Zip files are built in memory, bigger repositories can only be
+/// downloaded as tar archives which get streamed
+const MAX_ZIP_CONTENT_SIZE: usize = 256 * 1024 * 1024;
 
 #[route(
     "/{username}/{repository}/tree/{tree:.*}/archive/targz",
     method = "GET",
     err = "html"
 )]
-pub(crate) async fn tar_gz_file(repo: Repository, branch: Branch) -> Result<impl Responder> {
-    let gitoxide_repo = branch.gitoxide_repo;
-
-    let mut buffer = Vec::<u8>::new();
-
-    let store = gitoxide_repo.objects.clone();
-
-    let tree = repo_files_at_ref(
-        &branch.reference,
-        store.clone(),
-        &gitoxide_repo,
-        &mut buffer,
-    )
-    .await?;
-    let tree = Tree::from(tree);
-
-    let mut builder = TarBuilder::new(Vec::new());
-    write_directory_tar(
-        store.clone(),
-        tree,
-        Path::new("."),
-        &mut builder,
-        &mut buffer,
-    )
-    .await?;
-
-    let tar_data = builder.into_inner().await?;
-
-    let encoder = GzipEncoder::new(tar_data);
-    let gzip_data = encoder.into_inner();
-
-    Ok(HttpResponse::Ok()
+pub(crate) async fn tar_gz_file(
+    repo: Repository,
+    branch: Branch,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    tar_file(repo, branch, TarCompression::Gzip, db_pool).await
+}
+
+#[route(
+    "/{username}/{repository}/tree/{tree:.*}/archive/tarbz2",
+    method = "GET",
+    err = "html"
+)]
+pub(crate) async fn tar_bz2_file(
+    repo: Repository,
+    branch: Branch,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    tar_file(repo, branch, TarCompression::Bzip2, db_pool).await
+}
+
+#[route(
+    "/{username}/{repository}/tree/{tree:.*}/archive/tarxz",
+    method = "GET",
+    err = "html"
+)]
+pub(crate) async fn tar_xz_file(
+    repo: Repository,
+    branch: Branch,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    tar_file(repo, branch, TarCompression::Xz, db_pool).await
+}
+
+#[route(
+    "/{username}/{repository}/tree/{tree:.*}/archive/tarzst",
+    method = "GET",
+    err = "html"
+)]
+pub(crate) async fn tar_zst_file(
+    repo: Repository,
+    branch: Branch,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    tar_file(repo, branch, TarCompression::Zstd, db_pool).await
+}
+
+/// Streams the tar archive to the client while it is being written.
+/// Output only depends on the archived commit, so downloading the same revision twice yields the exact same bytes.
+async fn tar_file(
+    repo: Repository,
+    branch: Branch,
+    compression: TarCompression,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse> {
+    let archive = Archive::new(&repo, branch, db_pool.get_ref()).await?;
+    let file_name = format!("{}.{}", archive.prefix, compression.extension());
+    let mut response = archive.response();
+
+    let (writer, reader) = tokio::io::duplex(STREAM_BUFFER_SIZE);
+
+    // The archive writer is not Send (gitoxide trees are walked using `async_recursion(?Send)`),
+    // so it gets spawned onto the current worker instead of the multi-threaded runtime
+    rt::spawn(async move {
+        if let Err(err) = archive.write_tar(compression.encoder(writer)).await {
+            // The client receives a truncated archive which will fail to decompress
+            warn!("Failed to write archive {}: {}", archive.prefix, err);
+        }
+    });
+
+    Ok(response
+        .content_type(compression.content_type())
+        .append_header((
+            CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", file_name),
+        ))
+        .streaming(ReaderStream::new(reader)))
+}
+
+/// The zip format requires seeking back to write the central directory, so unlike tar archives
+/// zip files are built in memory before being sent. Repositories bigger than [MAX_ZIP_CONTENT_SIZE] are rejected
+#[route(
+    "/{username}/{repository}/tree/{tree:.*}/archive/zip",
+    method = "GET",
+    err = "html"
+)]
+pub(crate) async fn zip_file(
+    repo: Repository,
+    branch: Branch,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let archive = Archive::new(&repo, branch, db_pool.get_ref()).await?;
+
+    let data = archive.write_zip().await?;
+
+    Ok(archive
+        .response()
+        .content_type("application/zip")
         .append_header((
             CONTENT_DISPOSITION,
-            format!("attachment; filename=\"{}.tar.gz\"", &repo.name),
+            format!("attachment; filename=\"{}.zip\"", archive.prefix),
         ))
-        .body(gzip_data))
+        .body(data))
+}
+
+#[derive(Clone, Copy)]
+enum TarCompression {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl TarCompression {
+    fn extension(self) -> &'static str {
+        match self {
+            TarCompression::Gzip => "tar.gz",
+            TarCompression::Bzip2 => "tar.bz2",
+            TarCompression::Xz => "tar.xz",
+            TarCompression::Zstd => "tar.zst",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            TarCompression::Gzip => "application/gzip",
+            TarCompression::Bzip2 => "application/x-bzip2",
+            TarCompression::Xz => "application/x-xz",
+            TarCompression::Zstd => "application/zstd",
+        }
+    }
+
+    /// Uses the default compression level of each algorithm. The level must not change between
+    /// requests as it affects the output and thus the checksum of the archive
+    fn encoder(self, writer: DuplexStream) -> Box<dyn AsyncWrite + Unpin + Send> {
+        match self {
+            TarCompression::Gzip => Box::new(GzipEncoder::new(writer)),
+            TarCompression::Bzip2 => Box::new(BzEncoder::new(writer)),
+            TarCompression::Xz => Box::new(XzEncoder::new(writer)),
+            TarCompression::Zstd => Box::new(ZstdEncoder::new(writer)),
+        }
+    }
 }
 
-#[async_recursion(?Send)]
-async fn write_directory_tar(
+/// Everything required to write an archive of a single commit, detached from the request
+struct Archive {
     store: Arc<Store>,
     tree: Tree,
-    path: &Path,
-    builder: &mut TarBuilder<Vec<u8>>,
-    buffer: &mut Vec<u8>,
-) -> Result<()> {
-    for entry in tree.entries {
-        let filename = entry.filename.to_str()?;
-        let path = path.join(filename);
-
-        match entry.mode {
-            EntryMode::Tree => {
-                let (tree_ref, _) = store.to_cache_arc().find_tree(entry.oid.as_ref(), buffer)?;
-                let tree = Tree::from(tree_ref);
-
-                write_directory_tar(store.clone(), tree, path.as_path(), builder, buffer).await?;
-            }
-            EntryMode::Blob | EntryMode::BlobExecutable | EntryMode::Link => {
-                let content = read_raw_blob_content(entry.oid.as_ref(), store.clone()).await?;
+    commit: ArchiveCommit,
+
+    /// Name of the directory all files are placed in, e.g. `gitarena-v1.0`. Also used as the file name
+    prefix: String,
+
+    /// Whether the archive was requested using a full commit id, in which case it can be cached forever
+    immutable: bool,
+}
 
-                let mut header = TarHeader::new_gnu();
-                header.set_size(content.len() as u64);
+impl Archive {
+    async fn new(repo: &Repository, branch: Branch, db_pool: &PgPool) -> Result<Archive> {
+        let mut transaction = db_pool.begin().await?;
 
-                header.set_mode(if matches!(entry.mode, EntryMode::BlobExecutable) {
-                    0o775
-                } else {
-                    0o664
-                });
+        let libgit2_repo = repo.libgit2(&mut transaction).await?;
+        let commit = libgit2_repo.find_commit(Oid::from_str(branch.revision().as_str())?)?;
+        let commit = ArchiveCommit::new(&commit)?;
 
-                header.set_uid(0);
-                header.set_gid(0);
-                header.set_username("gitarena")?;
-                header.set_groupname("gitarena")?;
+        transaction.commit().await?;
+
+        let store = branch.gitoxide_repo.objects.clone();
+
+        let mut buffer = Vec::<u8>::new();
+        let tree =
+            Tree::from(repo_files_at_commit(&branch.commit, store.clone(), &mut buffer).await?);
+
+        Ok(Archive {
+            store,
+            tree,
+            commit,
+            prefix: archive_prefix(repo.name.as_str(), branch.tree.as_str()),
+            immutable: is_commit_id(branch.tree.as_str(), &branch.commit),
+        })
+    }
 
-                header.set_device_major(0)?;
-                header.set_device_minor(0)?;
+    fn response(&self) -> HttpResponseBuilder {
+        let mut response = HttpResponse::Ok();
 
-                header.set_mtime(0); // TODO: Unix timestamp of last commit to this file
+        if self.immutable {
+            response.insert_header((CACHE_CONTROL, IMMUTABLE_CACHE_CONTROL));
+        }
+
+        response
+    }
+
+    async fn write_tar(&self, writer: Box<dyn AsyncWrite + Unpin + Send>) -> Result<()> {
+        let mut builder = TarBuilder::new(writer);
+        let mut attributes = GitAttributes::default();
+        let mut buffer = Vec::<u8>::new();
+
+        self.append_tar_directory(&mut builder, self.prefix.as_str())
+            .await?;
+        self.write_tar_tree(
+            self.tree.clone(),
+            "",
+            &mut builder,
+            &mut attributes,
+            &mut buffer,
+        )
+        .await?;
+
+        let mut writer = builder.into_inner().await?;
+        writer.shutdown().await?;
+
+        Ok(())
+    }
+
+    #[async_recursion(?Send)]
+    async fn write_tar_tree(
+        &self,
+        tree: Tree,
+        directory: &str,
+        builder: &mut TarBuilder<Box<dyn AsyncWrite + Unpin + Send>>,
+        attributes: &mut GitAttributes,
+        buffer: &mut Vec<u8>,
+    ) -> Result<()> {
+        let has_attributes = self.push_attributes(&tree, directory, attributes).await?;
+
+        for entry in &tree.entries {
+            let path = join_path(directory, entry.filename.to_str()?);
+            let is_directory = matches!(entry.mode, EntryMode::Tree | EntryMode::Commit);
+
+            if attributes.is_set(path.as_str(), is_directory, "export-ignore") {
+                continue;
+            }
 
-                if matches!(entry.mode, EntryMode::Link) {
-                    let cow = String::from_utf8_lossy(&content[..]);
-                    let borrow: &str = cow.borrow();
+            let archive_path = format!("{}/{}", self.prefix, path);
 
-                    header.set_link_name(Path::new(borrow))?;
+            match entry.mode {
+                EntryMode::Tree => {
+                    let (tree_ref, _) = self
+                        .store
+                        .to_cache_arc()
+                        .find_tree(entry.oid.as_ref(), buffer)?;
+                    let tree = Tree::from(tree_ref);
+
+                    self.append_tar_directory(builder, archive_path.as_str())
+                        .await?;
+                    self.write_tar_tree(tree, path.as_str(), builder, attributes, buffer)
+                        .await?;
+                }
+                EntryMode::Blob | EntryMode::BlobExecutable => {
+                    let content =
+                        read_raw_blob_content(entry.oid.as_ref(), self.store.clone()).await?;
+                    let content = if attributes.is_set(path.as_str(), false, "export-subst") {
+                        self.commit.substitute(&content[..]).into_owned()
+                    } else {
+                        content
+                    };
+
+                    let mut header = self.tar_header(EntryType::Regular)?;
+                    header.set_size(content.len() as u64);
+                    header.set_mode(if matches!(entry.mode, EntryMode::BlobExecutable) {
+                        0o775
+                    } else {
+                        0o664
+                    });
+
+                    builder
+                        .append_data(&mut header, archive_path.as_str(), &content[..])
+                        .await?;
                 }
+                EntryMode::Link => {
+                    let target =
+                        read_raw_blob_content(entry.oid.as_ref(), self.store.clone()).await?;
 
-                header.set_cksum();
+                    let target = target.to_str_lossy();
 
-                builder
-                    .append_data(&mut header, path.as_path(), &content[..])
-                    .await?;
+                    let mut header = self.tar_header(EntryType::Symlink)?;
+                    header.set_size(0);
+                    header.set_mode(0o777);
+
+                    if target.len() <= header.as_old().linkname.len() {
+                        header.set_link_name(target.as_ref())?;
+                    } else {
+                        self.append_tar_long_link(builder, target.as_ref()).await?;
+                    }
+
+                    builder
+                        .append_data(&mut header, archive_path.as_str(), tokio::io::empty())
+                        .await?;
+                }
+                EntryMode::Commit => {
+                    // Submodules are not part of this repository, so like `git archive` an empty directory is written
+                    self.append_tar_directory(builder, archive_path.as_str())
+                        .await?;
+                }
             }
-            EntryMode::Commit => { /* TODO: implement submodules */ }
         }
+
+        if has_attributes {
+            attributes.pop();
+        }
+
+        Ok(())
     }
 
-    Ok(())
-}
+    async fn append_tar_directory(
+        &self,
+        builder: &mut TarBuilder<Box<dyn AsyncWrite + Unpin + Send>>,
+        path: &str,
+    ) -> Result<()> {
+        let mut header = self.tar_header(EntryType::Directory)?;
+        header.set_size(0);
+        header.set_mode(0o775);
 
-#[route(
-    "/{username}/{repository}/tree/{tree:.*}/archive/zip",
-    method = "GET",
-    err = "html"
-)]
-pub(crate) async fn zip_file(repo: Repository, branch: Branch) -> Result<impl Responder> {
-    let gitoxide_repo = branch.gitoxide_repo;
+        builder
+            .append_data(&mut header, format!("{}/", path), tokio::io::empty())
+            .await?;
 
-    let mut buffer = Vec::<u8>::new();
-    let store = gitoxide_repo.objects.clone();
+        Ok(())
+    }
 
-    let tree = repo_files_at_ref(
-        &branch.reference,
-        store.clone(),
-        &gitoxide_repo,
-        &mut buffer,
-    )
-    .await?;
-    let tree = Tree::from(tree);
+    /// Writes a GNU long link entry (`././@LongLink`) which sets the link target of the entry following it.
+    /// Used for symlink targets which do not fit into the header
+    async fn append_tar_long_link(
+        &self,
+        builder: &mut TarBuilder<Box<dyn AsyncWrite + Unpin + Send>>,
+        target: &str,
+    ) -> Result<()> {
+        let data = [target.as_bytes(), b"\0"].concat(); // Null terminated
 
-    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
-    write_directory_zip(store.clone(), tree, Path::new(""), &mut writer, &mut buffer).await?;
+        let mut header = self.tar_header(EntryType::GNULongLink)?;
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
 
-    let cursor = writer.finish()?;
-    let data = cursor.into_inner();
+        builder
+            .append_data(&mut header, "././@LongLink", &data[..])
+            .await?;
 
-    Ok(HttpResponse::Ok()
-        .append_header((
-            CONTENT_DISPOSITION,
-            format!("attachment; filename=\"{}.zip\"", &repo.name),
-        ))
-        .body(data))
-}
+        Ok(())
+    }
 
-#[async_recursion(?Send)]
-async fn write_directory_zip(
-    store: Arc<Store>,
-    tree: Tree,
-    path: &Path,
-    writer: &mut ZipWriter<Cursor<Vec<u8>>>,
-    buffer: &mut Vec<u8>,
-) -> Result<()> {
-    for entry in tree.entries {
-        let filename = entry.filename.to_str()?;
-        let path_buffer = path.join(filename);
-        let path = path_buffer.as_path();
-
-        match entry.mode {
-            EntryMode::Tree => {
-                let (tree_ref, _) = store.to_cache_arc().find_tree(entry.oid.as_ref(), buffer)?;
-                let tree = Tree::from(tree_ref);
-
-                writer.add_directory(format!("{}", path.display()), ZipFileOptions::default())?;
-
-                write_directory_zip(store.clone(), tree, path, writer, buffer).await?;
+    /// Header with all fields which do not depend on the entry set to fixed values in order to be reproducible
+    fn tar_header(&self, entry_type: EntryType) -> Result<TarHeader> {
+        let mut header = TarHeader::new_gnu();
+
+        header.set_entry_type(entry_type);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_mtime(self.commit.time().timestamp().max(0) as u64);
+
+        header.set_username("gitarena")?;
+        header.set_groupname("gitarena")?;
+        header.set_device_major(0)?;
+        header.set_device_minor(0)?;
+
+        Ok(header)
+    }
+
+    async fn write_zip(&self) -> Result<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let mut attributes = GitAttributes::default();
+        let mut buffer = Vec::<u8>::new();
+        let mut size = 0_usize;
+
+        writer.add_directory(format!("{}/", self.prefix), self.zip_options(0o775))?;
+        self.write_zip_tree(
+            self.tree.clone(),
+            "",
+            &mut writer,
+            &mut attributes,
+            &mut buffer,
+            &mut size,
+        )
+        .await?;
+
+        Ok(writer.finish()?.into_inner())
+    }
+
+    #[async_recursion(?Send)]
+    async fn write_zip_tree(
+        &self,
+        tree: Tree,
+        directory: &str,
+        writer: &mut ZipWriter<Cursor<Vec<u8>>>,
+        attributes: &mut GitAttributes,
+        buffer: &mut Vec<u8>,
+        size: &mut usize,
+    ) -> Result<()> {
+        let has_attributes = self.push_attributes(&tree, directory, attributes).await?;
+
+        for entry in &tree.entries {
+            let path = join_path(directory, entry.filename.to_str()?);
+            let is_directory = matches!(entry.mode, EntryMode::Tree | EntryMode::Commit);
+
+            if attributes.is_set(path.as_str(), is_directory, "export-ignore") {
+                continue;
             }
-            EntryMode::Blob | EntryMode::BlobExecutable => {
-                let content = read_raw_blob_content(entry.oid.as_ref(), store.clone()).await?;
 
-                let options = ZipFileOptions::default()
-                    .unix_permissions(if matches!(entry.mode, EntryMode::BlobExecutable) {
-                        0o775
+            let archive_path = format!("{}/{}", self.prefix, path);
+
+            match entry.mode {
+                EntryMode::Tree => {
+                    let (tree_ref, _) = self
+                        .store
+                        .to_cache_arc()
+                        .find_tree(entry.oid.as_ref(), buffer)?;
+                    let tree = Tree::from(tree_ref);
+
+                    writer.add_directory(format!("{}/", archive_path), self.zip_options(0o775))?;
+
+                    self.write_zip_tree(tree, path.as_str(), writer, attributes, buffer, size)
+                        .await?;
+                }
+                EntryMode::Blob | EntryMode::BlobExecutable => {
+                    let content =
+                        read_raw_blob_content(entry.oid.as_ref(), self.store.clone()).await?;
+                    let content = if attributes.is_set(path.as_str(), false, "export-subst") {
+                        self.commit.substitute(&content[..]).into_owned()
                     } else {
-                        0o664
-                    })
-                    .large_file(content.len() >= 4294967000); // 4 GiB
-                                                              //.last_modified_time(...) TODO: DateTime of last commit to this file
+                        content
+                    };
+
+                    *size += content.len();
+
+                    if *size > MAX_ZIP_CONTENT_SIZE {
+                        die!(
+                            FORBIDDEN,
+                            "Repository is too large to be downloaded as zip, please download a tar archive instead"
+                        );
+                    }
+
+                    let options = self
+                        .zip_options(if matches!(entry.mode, EntryMode::BlobExecutable) {
+                            0o775
+                        } else {
+                            0o664
+                        })
+                        .compression_method(CompressionMethod::Deflated)
+                        .large_file(content.len() >= 4294967000); // 4 GiB
+
+                    writer.start_file(archive_path, options)?;
+                    writer.write_all(&content[..])?;
+                }
+                EntryMode::Link => {
+                    let target =
+                        read_raw_blob_content(entry.oid.as_ref(), self.store.clone()).await?;
+
+                    writer.add_symlink(
+                        archive_path,
+                        target.to_str_lossy().as_ref(),
+                        self.zip_options(0o777),
+                    )?;
+                }
+                EntryMode::Commit => {
+                    writer.add_directory(format!("{}/", archive_path), self.zip_options(0o775))?;
+                }
+            }
+        }
+
+        if has_attributes {
+            attributes.pop();
+        }
+
+        Ok(())
+    }
 
-                writer.start_file(format!("{}", path.display()), options)?;
-                writer.write_all(&content[..])?;
+    fn zip_options(&self, permissions: u32) -> ZipFileOptions {
+        // Zip timestamps do not have a time zone, so the time is stored as seen by the committer.
+        // Dates before 1980 cannot be represented and fall back to the earliest possible date
+        let time = self.commit.time();
+        let last_modified = ZipDateTime::from_date_and_time(
+            time.year().clamp(1980, 2107) as u16,
+            time.month() as u8,
+            time.day() as u8,
+            time.hour() as u8,
+            time.minute() as u8,
+            time.second() as u8,
+        )
+        .unwrap_or_default();
+
+        ZipFileOptions::default()
+            .last_modified_time(last_modified)
+            .unix_permissions(permissions)
+    }
+
+    /// Reads the `.gitattributes` file of `tree` if it exists. Returns true if attributes have been pushed onto the stack
+    async fn push_attributes(
+        &self,
+        tree: &Tree,
+        directory: &str,
+        attributes: &mut GitAttributes,
+    ) -> Result<bool> {
+        let entry = tree.entries.iter().find(|entry| {
+            entry.filename == ".gitattributes"
+                && matches!(entry.mode, EntryMode::Blob | EntryMode::BlobExecutable)
+        });
+
+        match entry {
+            Some(entry) => {
+                let content = read_raw_blob_content(entry.oid.as_ref(), self.store.clone()).await?;
+                attributes.push(directory, &content[..]);
+
+                Ok(true)
             }
-            EntryMode::Link | EntryMode::Commit => { /* TODO: implement symlinks and submodules */ }
+            None => Ok(false),
         }
     }
+}
+
+fn join_path(directory: &str, name: &str) -> String {
+    if directory.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{}/{}", directory, name)
+    }
+}
+
+/// Directory name used inside the archive, e.g. `gitarena-v1.0` or `gitarena-feature-login` for `feature/login`
+fn archive_prefix(repo_name: &str, tree: &str) -> String {
+    let tree = tree
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
 
-    Ok(())
+    format!("{}-{}", repo_name, tree.trim_matches('-'))
 }
@@ -0,0 +1,174 @@
+use crate::git::blame::{blame_file, ignore_revs_at};
+use crate::git::history::{all_branches, all_tags};
+use crate::highlighting::highlight_lines;
+use crate::prelude::{
+    link_co_authors, ContextExtensions, HttpRequestExtensions, LibGit2CommitExtensions,
+    LibGit2SignatureExtensions,
+};
+use crate::repository::{Branch, Repository};
+use crate::routes::repository::blobs::BlobRequest;
+use crate::templates::web::{GitCommit, RepoBlameHunk};
+use crate::user::WebUser;
+use crate::utils::cookie_file::{CookieExtensions, FileType};
+use crate::{die, err, render_template};
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use actix_web::{web, HttpRequest, Responder};
+use anyhow::Result;
+use git2::{ObjectType, Oid};
+use gitarena_macros::route;
+use magic::Cookie;
+use sqlx::PgPool;
+use tera::Context;
+
+#[route(
+    "/{username}/{repository}/tree/{tree}/blame/{blob:.*}",
+    method = "GET",
+    err = "html"
+)]
+pub(crate) async fn view_blame(
+    repo: Repository,
+    branch: Branch,
+    uri: web::Path<BlobRequest>,
+    web_user: WebUser,
+    request: HttpRequest,
+    cookie: web::Data<Arc<Cookie>>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let libgit2_repo = repo.libgit2(&mut transaction).await?;
+    let mailmap = libgit2_repo.mailmap().ok();
+    let revision = branch.revision();
+
+    let query_string = request.q_string();
+
+    // "Blame prior to this commit" starts blaming at the parent of the requested commit
+    let commit = match query_string.get("before") {
+        Some(before) => {
+            let oid = Oid::from_str(before).map_err(|_| err!(BAD_REQUEST, "Invalid commit id"))?;
+
+            libgit2_repo
+                .find_commit(oid)
+                .map_err(|_| err!(NOT_FOUND, "Commit not found"))?
+                .parents()
+                .next()
+                .ok_or_else(|| err!(NOT_FOUND, "Commit does not have any parents"))?
+        }
+        None => libgit2_repo.find_commit(Oid::from_str(revision.as_str())?)?,
+    };
+
+    let entry = commit
+        .tree()?
+        .get_path(Path::new(uri.blob.as_str()))
+        .map_err(|_| err!(NOT_FOUND, "Not found"))?;
+
+    if entry.kind() != Some(ObjectType::Blob) {
+        die!(BAD_REQUEST, "Only blobs can be viewed in blame view");
+    }
+
+    let blob = libgit2_repo.find_blob(entry.id())?;
+    let size = blob.size();
+
+    if size >= 2_000_000 {
+        die!(
+            BAD_REQUEST,
+            "GitArena does only blame files which are smaller than 2 MB"
+        );
+    }
+
+    if !matches!(cookie.probe(blob.content())?, FileType::Text) && size > 0 {
+        die!(BAD_REQUEST, "Only text files can be blamed");
+    }
+
+    let content = String::from_utf8_lossy(blob.content());
+    let lines = highlight_lines(uri.blob.as_str(), content.as_ref())?;
+
+    // `?ignore_revs=0` allows to see the real blame even if the repository has a `.git-blame-ignore-revs` file
+    let ignore_revs = if query_string.get("ignore_revs") == Some("0") {
+        None
+    } else {
+        ignore_revs_at(&libgit2_repo, &commit).await?
+    };
+
+    let hunks = blame_file(
+        &libgit2_repo,
+        &commit,
+        uri.blob.as_str(),
+        ignore_revs
+            .as_ref()
+            .map(|(oid, revs)| (*oid, revs.as_slice())),
+    )
+    .await?;
+
+    let mut commits = HashMap::<String, GitCommit>::new();
+    let mut blame = Vec::<RepoBlameHunk>::with_capacity(hunks.len());
+
+    for hunk in hunks.iter() {
+        let oid = format!("{}", hunk.commit);
+        let hunk_commit = libgit2_repo.find_commit(hunk.commit)?;
+
+        if !commits.contains_key(&oid) {
+            let (author_name, author_uid, author_email) = hunk_commit
+                .author()
+                .try_disassemble(mailmap.as_ref(), &mut transaction)
+                .await;
+
+            commits.insert(
+                oid.clone(),
+                GitCommit {
+                    oid: oid.clone(),
+                    message: hunk_commit.message().unwrap_or_default().to_owned(),
+                    time: hunk_commit.time().seconds(),
+                    date: None,
+                    author_name,
+                    author_uid,
+                    author_email,
+                    co_authors: hunk_commit.co_authors(mailmap.as_ref()),
+                },
+            );
+        }
+
+        blame.push(RepoBlameHunk {
+            commit: oid,
+            start_line: hunk.start_line,
+            lines: lines
+                .iter()
+                .skip(hunk.start_line.saturating_sub(1))
+                .take(hunk.lines)
+                .map(String::as_str)
+                .collect(),
+            orig_path: hunk.orig_path.as_deref().unwrap_or(uri.blob.as_str()),
+            has_parent: hunk_commit.parent_count() > 0,
+        });
+    }
+
+    link_co_authors(commits.values_mut(), &mut transaction).await;
+
+    let mut context = Context::new();
+
+    context.insert_web_user(&web_user)?;
+    context.try_insert("repo_owner_name", uri.username.as_str())?;
+    context.try_insert("repo", &repo)?;
+
+    context.try_insert("tree", uri.tree.as_str())?;
+    context.try_insert("branches", &all_branches(&libgit2_repo).await?)?;
+    context.try_insert("tags", &all_tags(&libgit2_repo, None).await?)?;
+
+    context.try_insert("name", entry.name().unwrap_or("Invalid file name"))?;
+    context.try_insert("full_path", uri.blob.as_str())?;
+    context.try_insert("size", &size)?;
+
+    context.try_insert("blame", &blame)?;
+    context.try_insert("commits", &commits)?;
+    context.try_insert("ignore_revs", &ignore_revs.is_some())?;
+
+    if let Some(before) = query_string.get("before") {
+        context.try_insert("before", before)?;
+    }
+
+    render_template!("repo/blob/blame.html", context, transaction)
+}
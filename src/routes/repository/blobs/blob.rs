@@ -1,29 +1,48 @@
+use crate::config::get_optional_setting;
 use crate::git::history::{all_branches, all_tags, last_commit_for_blob};
-use crate::git::utils::{read_blob_content, repo_files_at_ref};
-use crate::prelude::{ContextExtensions, LibGit2SignatureExtensions};
-use crate::repository::{Branch, Repository};
+use crate::git::utils::{read_blob_content, read_raw_blob_content, repo_files_at_commit};
+use crate::highlighting::highlight_lines;
+use crate::markup::{render, MarkupContext, MarkupFormat};
+use crate::prelude::{
+    link_co_authors, ContextExtensions, LibGit2CommitExtensions, LibGit2SignatureExtensions,
+};
+use crate::renderers::{self, lfs_endpoint, Blob, RenderContext};
+use crate::repository::{Branch, ImmutableRevision, Repository, IMMUTABLE_CACHE_CONTROL};
 use crate::routes::repository::blobs::BlobRequest;
 use crate::templates::web::{GitCommit, RepoFile};
 use crate::user::WebUser;
-use crate::utils::cookie_file::{CookieExtensions, FileType};
+use crate::utils::cookie_file::{CookieExtensions, FileType, MimeCookie};
 use crate::{die, err, render_template};
 
 use std::sync::Arc;
 
-use actix_web::http::header::CONTENT_TYPE;
-use actix_web::{web, HttpResponse, Responder};
+use actix_files::HttpRange;
+use actix_web::http::header::{
+    ContentRange, ContentRangeSpec, ETag, EntityTag, Header, IfNoneMatch, IfRange, ACCEPT_RANGES,
+    CACHE_CONTROL, CONTENT_SECURITY_POLICY, CONTENT_TYPE, IF_RANGE, LOCATION, RANGE,
+    X_CONTENT_TYPE_OPTIONS,
+};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
 use anyhow::Result;
 use async_recursion::async_recursion;
 use bstr::ByteSlice;
+use git_repository::hash::ObjectId;
 use git_repository::objs::tree::EntryMode;
 use git_repository::objs::{Tree, TreeRef};
 use git_repository::odb::pack::FindExt;
 use git_repository::odb::Store;
+use gitarena_common::database::models::RepoVisibility;
 use gitarena_macros::route;
 use magic::Cookie;
 use sqlx::PgPool;
 use tera::Context;
 use tracing_unwrap::OptionExt;
+use url::Url;
+
+/// Untrusted documents may not run scripts, submit forms or load anything but inline styles and embedded images
+const SANDBOX_POLICY: &str =
+    "sandbox; default-src 'none'; img-src data:; style-src 'unsafe-inline'";
 
 #[route(
     "/{username}/{repository}/tree/{tree}/blob/{blob:.*}",
@@ -42,35 +61,43 @@ pub(crate) async fn view_blob(
 
     let gitoxide_repo = branch.gitoxide_repo;
     let libgit2_repo = repo.libgit2(&mut transaction).await?;
+    let mailmap = libgit2_repo.mailmap().ok();
 
-    let full_tree_name = branch.reference.name.as_bstr().to_str()?;
+    let revision = branch.revision();
 
     let mut buffer = Vec::<u8>::new();
     let mut blob_buffer = Vec::<u8>::new();
 
     let store = gitoxide_repo.objects.clone();
 
-    let tree_ref = repo_files_at_ref(
-        &branch.reference,
-        store.clone(),
-        &gitoxide_repo,
-        &mut buffer,
-    )
-    .await?;
-    let (name, content, mode) = recursively_visit_blob_content(
-        tree_ref,
-        uri.blob.as_str(),
-        store.clone(),
-        &mut blob_buffer,
-    )
-    .await?;
+    let tree_ref = repo_files_at_commit(&branch.commit, store.clone(), &mut buffer).await?;
+    let lfs_config = read_root_file(&tree_ref, ".lfsconfig", store.clone()).await?;
+    let (name, blob_oid, mode) =
+        recursively_visit_blob(tree_ref, uri.blob.as_str(), store.clone(), &mut blob_buffer)
+            .await?;
+    let content = read_raw_blob_content(&blob_oid, store.clone()).await?;
 
-    let oid = last_commit_for_blob(&libgit2_repo, full_tree_name, uri.blob.as_str())
+    let oid = last_commit_for_blob(&libgit2_repo, revision.as_str(), uri.blob.as_str())
         .await?
         .unwrap_or_log();
     let commit = libgit2_repo.find_commit(oid)?;
-    let (author_name, author_uid, author_email) =
-        commit.author().try_disassemble(&mut transaction).await;
+    let (author_name, author_uid, author_email) = commit
+        .author()
+        .try_disassemble(mailmap.as_ref(), &mut transaction)
+        .await;
+
+    let mut git_commit = GitCommit {
+        oid: format!("{}", oid),
+        message: commit.message().unwrap_or_default().to_owned(),
+        time: commit.time().seconds(),
+        date: None,
+        author_name,
+        author_uid,
+        author_email,
+        co_authors: commit.co_authors(mailmap.as_ref()),
+    };
+
+    link_co_authors(Some(&mut git_commit), &mut transaction).await;
 
     let mut context = Context::new();
 
@@ -80,27 +107,46 @@ pub(crate) async fn view_blob(
             file_type: mode as u16,
             file_name: name.as_str(),
             submodule_target_oid: None,
-            commit: GitCommit {
-                oid: format!("{}", oid),
-                message: commit.message().unwrap_or_default().to_owned(),
-                time: commit.time().seconds(),
-                date: None,
-                author_name,
-                author_uid,
-                author_email,
-            },
+            commit: Some(git_commit),
         },
     )?;
 
     let size = content.len();
-    let file_type = cookie.probe(content.as_bytes())?;
+    let file_type = cookie.probe(&content[..])?;
 
     context.try_insert("type", &file_type)?;
     context.try_insert("size", &size)?;
 
-    // We only display text files which are less than 2 MB
-    if matches!(file_type, FileType::Text) && size < 2_000_000 {
-        context.try_insert("content", content.as_str())?;
+    let render_context = RenderContext {
+        markup: MarkupContext::new(
+            uri.username.as_str(),
+            repo.name.as_str(),
+            uri.tree.as_str(),
+            uri.blob.as_str(),
+        ),
+        raw_url: format!(
+            "/{}/{}/tree/{}/~blob/{}",
+            uri.username, repo.name, uri.tree, uri.blob
+        ),
+        lfs_endpoint: lfs_endpoint(lfs_config.as_deref()),
+    };
+
+    let blob = Blob::new(name.as_str(), &content[..], &file_type);
+    let content = String::from_utf8_lossy(&content[..]);
+
+    if let Some(rendered) = renderers::render(&blob, &render_context) {
+        context.try_insert("renderer", &rendered)?;
+    } else if matches!(file_type, FileType::Text) && size < 2_000_000 {
+        // We only display text files which are less than 2 MB
+        // Markup files (such as Markdown) get rendered instead of highlighted
+        if MarkupFormat::from_file_name(name.as_str()).is_markup() {
+            context.try_insert(
+                "rendered",
+                &render(name.as_str(), content.as_ref(), &render_context.markup).await,
+            )?;
+        } else {
+            context.try_insert("lines", &highlight_lines(name.as_str(), content.as_ref())?)?;
+        }
     }
 
     context.insert_web_user(&web_user)?;
@@ -117,19 +163,25 @@ pub(crate) async fn view_blob(
     render_template!("repo/blob/blob.html", context, transaction)
 }
 
+/// Serves the blob as-is. Responses carry a strong `ETag` derived from the blob id, support conditional
+/// and partial requests and are typed using libmagic.
+///
+/// HTML and SVG files of public repositories are redirected to `raw.download_domain` if configured, so untrusted
+/// content never runs on the main origin. They are additionally always served with a sandboxing CSP.
 #[route(
     "/{username}/{repository}/tree/{tree}/~blob/{blob:.*}",
     method = "GET",
     err = "text"
 )]
 pub(crate) async fn view_raw_blob(
-    _repo: Repository,
+    repo: Repository,
     branch: Branch,
     uri: web::Path<BlobRequest>,
-    cookie: web::Data<Arc<Cookie>>,
+    mime_cookie: web::Data<Arc<MimeCookie>>,
+    request: HttpRequest,
     db_pool: web::Data<PgPool>,
-) -> Result<impl Responder> {
-    let transaction = db_pool.begin().await?;
+) -> Result<HttpResponse> {
+    let mut transaction = db_pool.begin().await?;
 
     let gitoxide_repo = branch.gitoxide_repo;
 
@@ -138,44 +190,188 @@ pub(crate) async fn view_raw_blob(
 
     let store = gitoxide_repo.objects.clone();
 
-    let tree_ref = repo_files_at_ref(
-        &branch.reference,
-        store.clone(),
-        &gitoxide_repo,
-        &mut buffer,
-    )
-    .await?;
-    let (_, content, _) = recursively_visit_blob_content(
-        tree_ref,
-        uri.blob.as_str(),
-        store.clone(),
-        &mut blob_buffer,
-    )
-    .await?;
+    let tree_ref = repo_files_at_commit(&branch.commit, store.clone(), &mut buffer).await?;
+    let (_, blob_oid, _) =
+        recursively_visit_blob(tree_ref, uri.blob.as_str(), store.clone(), &mut blob_buffer)
+            .await?;
+
+    let etag = EntityTag::new_strong(blob_oid.to_hex().to_string());
+
+    // Blobs are content addressed, so a matching ETag means the client already has this exact content
+    if let Ok(if_none_match) = IfNoneMatch::parse(&request) {
+        let matches = match if_none_match {
+            IfNoneMatch::Any => true,
+            IfNoneMatch::Items(tags) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        };
+
+        if matches {
+            transaction.commit().await?;
+
+            return Ok(HttpResponse::NotModified()
+                .insert_header(ETag(etag))
+                .finish());
+        }
+    }
+
+    let content = read_raw_blob_content(&blob_oid, store).await?;
+    let mime = mime_cookie.mime_type(&content[..])?;
+    let untrusted = is_untrusted_mime(mime.as_str());
+
+    if untrusted && repo.visibility == RepoVisibility::Public {
+        let download_domain =
+            get_optional_setting::<String, _>("raw.download_domain", &mut transaction).await?;
+
+        if let Some(download_domain) = download_domain {
+            let download_url = Url::parse(download_domain.as_str())?;
+
+            if !is_download_origin(&request, &download_url) {
+                let location = download_url.join(
+                    request
+                        .uri()
+                        .path_and_query()
+                        .map_or_else(|| request.path(), |path| path.as_str()),
+                )?;
+
+                transaction.commit().await?;
+
+                return Ok(HttpResponse::TemporaryRedirect()
+                    .insert_header((LOCATION, location.as_str()))
+                    .finish());
+            }
+        }
+    }
+
+    transaction.commit().await?;
+
+    let length = content.len() as u64;
+
+    let mut response = HttpResponse::Ok();
+    response
+        .insert_header((CONTENT_TYPE, mime.as_str()))
+        .insert_header((X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .insert_header((ACCEPT_RANGES, "bytes"))
+        .insert_header(ETag(etag.clone()));
+
+    if untrusted {
+        response.insert_header((CONTENT_SECURITY_POLICY, SANDBOX_POLICY));
+    }
 
-    let mime = if let Some(file_type) = infer::get(content.as_bytes()) {
-        file_type.mime_type()
+    if request.extensions().get::<ImmutableRevision>().is_some() {
+        response.insert_header((CACHE_CONTROL, IMMUTABLE_CACHE_CONTROL));
     } else {
-        match cookie.probe(content.as_bytes())? {
-            FileType::Text => "text/plain",
-            _ => "application/octet-stream",
+        // Branches move, so clients have to revalidate using the ETag
+        response.insert_header((CACHE_CONTROL, "no-cache"));
+    }
+
+    // Range units other than `bytes` are unknown to us and get ignored as per RFC 7233
+    let range = match request
+        .headers()
+        .get(RANGE)
+        .and_then(|range| range.to_str().ok())
+        .and_then(byte_ranges)
+    {
+        Some(ranges) if if_range_matches(&request, &etag) => ranges,
+        _ => return Ok(response.body(content)),
+    };
+
+    let ranges = match HttpRange::parse(format!("bytes={}", range).as_str(), length) {
+        Ok(ranges) => ranges,
+        _ => {
+            return Ok(response
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .insert_header(ContentRange(ContentRangeSpec::Bytes {
+                    range: None,
+                    instance_length: Some(length),
+                }))
+                .finish());
         }
     };
 
-    transaction.commit().await?;
+    // Multiple ranges would require a multipart/byteranges body, serving the whole blob is allowed instead
+    let range = match ranges.as_slice() {
+        [range] => range,
+        _ => return Ok(response.body(content)),
+    };
+
+    let start = range.start;
+    let end = range.start + range.length;
+
+    Ok(response
+        .status(StatusCode::PARTIAL_CONTENT)
+        .insert_header(ContentRange(ContentRangeSpec::Bytes {
+            range: Some((start, end - 1)),
+            instance_length: Some(length),
+        }))
+        .body(content[start as usize..end as usize].to_vec()))
+}
+
+/// Mime types which browsers may execute scripts in
+fn is_untrusted_mime(mime: &str) -> bool {
+    let essence = mime.split(';').next().unwrap_or_default().trim();
 
-    Ok(HttpResponse::Ok()
-        .insert_header((CONTENT_TYPE, mime))
-        .body(content))
+    matches!(
+        essence,
+        "text/html" | "image/svg+xml" | "application/xhtml+xml" | "text/xml" | "application/xml"
+    )
+}
+
+fn is_download_origin(request: &HttpRequest, download_url: &Url) -> bool {
+    let connection_info = request.connection_info();
+    let host = connection_info.host();
+
+    match (download_url.host_str(), download_url.port()) {
+        (Some(domain), Some(port)) => host == format!("{}:{}", domain, port),
+        (Some(domain), None) => host == domain,
+        (None, _) => true,
+    }
+}
+
+/// `If-Range` only allows a partial response if the client's copy is still current.
+/// Blobs have no modification date, so only entity tags are considered.
+fn if_range_matches(request: &HttpRequest, etag: &EntityTag) -> bool {
+    if request.headers().get(IF_RANGE).is_none() {
+        return true;
+    }
+
+    match IfRange::parse(request) {
+        Ok(IfRange::EntityTag(tag)) => tag.strong_eq(etag),
+        _ => false,
+    }
+}
+
+/// Returns the range set of a `Range` header using the `bytes` unit, the only one we support.
+/// Range units are case-insensitive
+fn byte_ranges(range: &str) -> Option<&str> {
+    range
+        .split_once('=')
+        .filter(|(unit, _)| unit.trim().eq_ignore_ascii_case("bytes"))
+        .map(|(_, ranges)| ranges)
+}
+
+/// Reads a file located in the root of the tree, returns `None` if it does not exist
+async fn read_root_file(
+    tree_ref: &TreeRef<'_>,
+    file_name: &str,
+    store: Arc<Store>,
+) -> Result<Option<String>> {
+    let entry = tree_ref.entries.iter().find(|entry| {
+        entry.filename == file_name
+            && matches!(entry.mode, EntryMode::Blob | EntryMode::BlobExecutable)
+    });
+
+    Ok(match entry {
+        Some(entry) => Some(read_blob_content(entry.oid, store).await?),
+        None => None,
+    })
 }
 
 #[async_recursion(?Send)]
-async fn recursively_visit_blob_content<'a>(
+async fn recursively_visit_blob<'a>(
     tree_ref: TreeRef<'a>,
     path: &str,
     store: Arc<Store>,
     buffer: &'a mut Vec<u8>,
-) -> Result<(String, String, EntryMode)> {
+) -> Result<(String, ObjectId, EntryMode)> {
     let tree = Tree::from(tree_ref);
     let (search, remaining) = path
         .split_once('/')
@@ -199,7 +395,7 @@ async fn recursively_visit_blob_content<'a>(
                 .map(|(tree, _)| tree)?;
             let mut buffer = Vec::<u8>::new();
 
-            recursively_visit_blob_content(tree_ref, remaining, store, &mut buffer).await
+            recursively_visit_blob(tree_ref, remaining, store, &mut buffer).await
         }
         None => {
             if entry.mode != EntryMode::Blob && entry.mode != EntryMode::BlobExecutable {
@@ -208,11 +404,7 @@ async fn recursively_visit_blob_content<'a>(
 
             let file_name = entry.filename.to_str().unwrap_or("Invalid file name");
 
-            Ok((
-                file_name.to_owned(),
-                read_blob_content(entry.oid.as_ref(), store).await?,
-                entry.mode,
-            ))
+            Ok((file_name.to_owned(), entry.oid, entry.mode))
         }
     }
 }
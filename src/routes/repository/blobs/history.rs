@@ -0,0 +1,135 @@
+use crate::git::history::{all_branches, all_tags, path_history};
+use crate::prelude::{
+    link_co_authors, ContextExtensions, HttpRequestExtensions, LibGit2CommitExtensions,
+    LibGit2SignatureExtensions, LibGit2TimeExtensions,
+};
+use crate::repository::{Branch, Repository};
+use crate::routes::repository::blobs::{paging_oids, BlobRequest};
+use crate::signatures::verify_commits;
+use crate::templates::web::GitCommit;
+use crate::user::WebUser;
+use crate::{die, render_template};
+
+use std::collections::HashMap;
+
+use actix_web::{web, HttpRequest, Responder};
+use anyhow::Result;
+use gitarena_macros::route;
+use sqlx::PgPool;
+use tera::Context;
+
+#[route(
+    "/{username}/{repository}/tree/{tree}/history/{blob:.*}",
+    method = "GET",
+    err = "htmx+html"
+)]
+pub(crate) async fn view_history(
+    repo: Repository,
+    branch: Branch,
+    uri: web::Path<BlobRequest>,
+    web_user: WebUser,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let libgit2_repo = repo.libgit2(&mut transaction).await?;
+    let mailmap = libgit2_repo.mailmap().ok();
+    let revision = branch.revision();
+
+    let query_string = request.q_string();
+    let (after, before) = paging_oids(&query_string)?;
+    let follow = query_string.get("follow") != Some("0");
+
+    let entries = path_history(
+        &libgit2_repo,
+        revision.as_str(),
+        uri.blob.as_str(),
+        follow,
+        after,
+        before,
+        20,
+    )
+    .await?;
+
+    if entries.is_empty() && after.is_none() && before.is_none() {
+        die!(NOT_FOUND, "Not found");
+    }
+
+    let mut commits = Vec::<GitCommit>::with_capacity(entries.len());
+    let mut renames = HashMap::<String, String>::new();
+
+    let commit_ids = entries.iter().map(|entry| entry.commit).collect::<Vec<_>>();
+    let signatures = verify_commits(
+        &repo,
+        &libgit2_repo,
+        commit_ids.as_slice(),
+        &mut transaction,
+    )
+    .await?;
+
+    for entry in entries {
+        let commit = libgit2_repo.find_commit(entry.commit)?;
+        let (name, uid, email) = commit
+            .author()
+            .try_disassemble(mailmap.as_ref(), &mut transaction)
+            .await;
+
+        let chrono_time = commit.time().try_as_chrono()?;
+        let chrono_date = chrono_time.date();
+        let chrono_time_only_date = chrono_date.and_hms_opt(0, 0, 0);
+
+        let oid = format!("{}", commit.id());
+
+        if let Some(renamed_from) = entry.renamed_from {
+            renames.insert(oid.clone(), renamed_from);
+        }
+
+        commits.push(GitCommit {
+            oid,
+            message: commit.message().unwrap_or_default().to_owned(),
+            time: commit.time().seconds(),
+            date: chrono_time_only_date,
+            author_name: name,
+            author_uid: uid,
+            author_email: email,
+            co_authors: commit.co_authors(mailmap.as_ref()),
+        });
+    }
+
+    link_co_authors(commits.iter_mut(), &mut transaction).await;
+
+    let mut context = Context::new();
+
+    context.insert_web_user(&web_user)?;
+    context.try_insert("repo_owner_name", uri.username.as_str())?;
+    context.try_insert("repo", &repo)?;
+    context.try_insert("tree", uri.tree.as_str())?;
+
+    context.try_insert("full_path", uri.blob.as_str())?;
+    context.try_insert("commits", &commits)?;
+    context.try_insert("renames", &renames)?;
+    context.try_insert("signatures", &signatures)?;
+
+    // Base URL for the next and previous pages, needs to end with either `?` or `&`
+    let page_url = format!(
+        "/{}/{}/tree/{}/history/{}?{}",
+        uri.username,
+        repo.name,
+        uri.tree,
+        uri.blob,
+        if follow { "" } else { "follow=0&" }
+    );
+    context.try_insert("page_url", &page_url)?;
+
+    // Only send a partial result (only the components) if it's a request by htmx
+    if request.is_htmx() {
+        return render_template!("repo/commit_list_component.html", context, transaction);
+    }
+
+    context.try_insert("branches", &all_branches(&libgit2_repo).await?)?;
+    context.try_insert("tags", &all_tags(&libgit2_repo, None).await?)?;
+    context.try_insert("follow", &follow)?;
+
+    render_template!("repo/history.html", context, transaction)
+}
@@ -0,0 +1,224 @@
+use crate::git::last_commits::{cached_last_commits, last_commits, store_last_commits};
+use crate::git::utils::{read_blob_content, repo_files_at_commit};
+use crate::git::GIT_HASH_KIND;
+use crate::prelude::ContextExtensions;
+use crate::render_template;
+use crate::repository::{Branch, Repository};
+use crate::routes::repository::blobs::directory::recursively_visit_tree;
+use crate::routes::repository::GitTreeRequest;
+use crate::templates::web::{GitCommit, RepoFile};
+use crate::user::WebUser;
+
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use actix_web::{web, Responder};
+use anyhow::Result;
+use bstr::ByteSlice;
+use git2::{Oid, Repository as Git2Repository};
+use git_repository::objs::tree::EntryMode;
+use git_repository::objs::Tree;
+use git_repository::odb::Store;
+use git_repository::ObjectId;
+use gitarena_macros::route;
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use tera::Context;
+
+/// Listings show at most this many entries
+const MAX_ENTRIES: usize = 1000;
+
+/// Builds the file listing of `tree`, which is located at `directory` (empty for the root directory) at `revision`.
+///
+/// The last commit of every entry is taken from the cache. If it is cold and `compute` is false, the entries are
+/// returned without commits and the second value is true; the template then loads them using htmx.
+/// Otherwise the last commits get computed and stored in the cache.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn list_files<'a>(
+    repo: &Repository,
+    libgit2_repo: &Git2Repository,
+    store: Arc<Store>,
+    tree: &'a Tree,
+    directory: &str,
+    revision: &str,
+    compute: bool,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(Vec<RepoFile<'a>>, bool)> {
+    let entries = tree.entries.iter().take(MAX_ENTRIES).collect::<Vec<_>>();
+
+    let paths = entries
+        .iter()
+        .map(|entry| {
+            let name = entry.filename.to_str_lossy();
+
+            if directory.is_empty() {
+                name.into_owned()
+            } else {
+                format!("{}/{}", directory, name)
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut commits = cached_last_commits(repo.id, revision, &paths, &mut *transaction).await?;
+
+    if commits.is_none() && compute {
+        let repo_path = repo.get_fs_path(&mut *transaction).await?;
+        let start = Oid::from_str(revision)?;
+        let directory_owned = directory.to_owned();
+
+        let computed = tokio::task::spawn_blocking(move || {
+            let git2_repo = Git2Repository::open(repo_path)?;
+            last_commits(&git2_repo, start, directory_owned.as_str(), false)
+        })
+        .await??;
+
+        store_last_commits(repo.id, revision, &computed, transaction).await?;
+
+        commits = Some(computed);
+    }
+
+    let pending = commits.is_none();
+    let commits = commits.unwrap_or_default();
+
+    let mut files = Vec::<RepoFile>::with_capacity(entries.len());
+
+    for (entry, path) in entries.iter().zip(paths.iter()) {
+        let name = entry.filename.to_str().unwrap_or("Invalid file name");
+
+        let commit = match commits.get(path) {
+            Some(oid) => {
+                let commit = libgit2_repo.find_commit(*oid)?;
+
+                Some(GitCommit {
+                    oid: format!("{}", oid),
+                    message: commit.message().unwrap_or_default().to_owned(),
+                    time: commit.time().seconds(),
+                    date: None,
+                    author_name: String::new(),  // Unused for file listing
+                    author_uid: None,            // Unused for file listing
+                    author_email: String::new(), // Unused for file listing
+                    co_authors: Vec::new(),      // Unused for file listing
+                })
+            }
+            None => None,
+        };
+
+        let submodule_target_oid = if matches!(entry.mode, EntryMode::Commit) {
+            Some(
+                read_blob_content(entry.oid.as_ref(), store.clone())
+                    .await
+                    .unwrap_or_else(|_| ObjectId::null(GIT_HASH_KIND).to_string()),
+            )
+        } else {
+            None
+        };
+
+        files.push(RepoFile {
+            file_type: entry.mode as u16,
+            file_name: name,
+            submodule_target_oid,
+            commit,
+        });
+    }
+
+    files.sort_by(|lhs, rhs| {
+        // 1. Directory
+        // 2. Submodules
+        // 3. Rest
+
+        if lhs.file_type == EntryMode::Tree as u16 && rhs.file_type != EntryMode::Tree as u16 {
+            Ordering::Less
+        } else if lhs.file_type != EntryMode::Tree as u16 && rhs.file_type == EntryMode::Tree as u16
+        {
+            Ordering::Greater
+        } else if lhs.file_type == EntryMode::Tree as u16 && rhs.file_type == EntryMode::Tree as u16
+        {
+            lhs.file_name.cmp(rhs.file_name)
+        } else if lhs.file_type == EntryMode::Commit as u16
+            && rhs.file_type != EntryMode::Commit as u16
+        {
+            Ordering::Less
+        } else if lhs.file_type != EntryMode::Commit as u16
+            && rhs.file_type == EntryMode::Commit as u16
+        {
+            Ordering::Greater
+        } else {
+            lhs.file_name.cmp(rhs.file_name)
+        }
+    });
+
+    Ok((files, pending))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LastCommitsQuery {
+    #[serde(default)]
+    path: String,
+}
+
+/// Renders the rows of a file listing including the last commit of every entry.
+/// Requested by listings which were rendered while the last commit cache was cold.
+#[route(
+    "/{username}/{repository}/tree/{tree}/last-commits",
+    method = "GET",
+    err = "htmx+html"
+)]
+pub(crate) async fn view_last_commits(
+    repo: Repository,
+    branch: Branch,
+    uri: web::Path<GitTreeRequest>,
+    query: web::Query<LastCommitsQuery>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let libgit2_repo = repo.libgit2(&mut transaction).await?;
+    let store = branch.gitoxide_repo.objects.clone();
+    let revision = branch.revision();
+
+    let directory = query.path.trim_matches('/');
+
+    let mut buffer = Vec::<u8>::new();
+    let root = repo_files_at_commit(&branch.commit, store.clone(), &mut buffer).await?;
+
+    let mut tree_buffer = Vec::<u8>::new();
+    let tree = if directory.is_empty() {
+        Tree::from(root)
+    } else {
+        recursively_visit_tree(
+            root,
+            format!("{}/", directory).as_str(),
+            store.clone(),
+            &mut tree_buffer,
+        )
+        .await?
+    };
+
+    let (files, pending) = list_files(
+        &repo,
+        &libgit2_repo,
+        store,
+        &tree,
+        directory,
+        revision.as_str(),
+        true,
+        &mut transaction,
+    )
+    .await?;
+
+    let mut context = Context::new();
+
+    context.insert_web_user(&web_user)?;
+    context.try_insert("repo", &repo)?;
+    context.try_insert("repo_owner_name", uri.username.as_str())?;
+    context.try_insert("tree", uri.tree.as_str())?;
+    context.try_insert("files", &files)?;
+    context.try_insert("pending", &pending)?;
+
+    if !directory.is_empty() {
+        context.try_insert("name", directory)?;
+    }
+
+    render_template!("repo/file_list_component.html", context, transaction)
+}
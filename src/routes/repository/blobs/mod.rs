@@ -1,13 +1,24 @@
+use crate::err;
+
 use actix_web::web::ServiceConfig;
+use anyhow::Result;
+use git2::Oid;
+use qstring::QString;
 use serde::Deserialize;
 
+mod blame;
 mod blob;
 mod directory;
+mod history;
+pub(crate) mod listing;
 
 pub(crate) fn init(config: &mut ServiceConfig) {
+    config.service(blame::view_blame);
     config.service(blob::view_blob);
     config.service(blob::view_raw_blob);
     config.service(directory::view_dir);
+    config.service(history::view_history);
+    config.service(listing::view_last_commits);
 }
 
 #[derive(Deserialize)]
@@ -21,3 +32,16 @@ pub(crate) struct BlobRequest {
     pub(crate) tree: String,
     pub(crate) blob: String,
 }
+
+/// Parses the `after` and `before` query parameters used for paging
+pub(crate) fn paging_oids(query_string: &QString) -> Result<(Option<Oid>, Option<Oid>)> {
+    let parse = |key: &str| {
+        query_string
+            .get(key)
+            .map(Oid::from_str)
+            .transpose()
+            .map_err(|_| err!(BAD_REQUEST, "Invalid commit id"))
+    };
+
+    Ok((parse("after")?, parse("before")?))
+}
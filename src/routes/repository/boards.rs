@@ -0,0 +1,174 @@
+use crate::board::{Board, BoardColumn};
+use crate::issue::{visibility_condition, Issue};
+use crate::label::Label;
+use crate::prelude::ContextExtensions;
+use crate::privileges::privilege;
+use crate::repository::Repository;
+use crate::routes::repository::GitRequest;
+use crate::user::WebUser;
+use crate::{err, render_template};
+
+use std::collections::HashMap;
+
+use actix_web::{web, Responder};
+use anyhow::Result;
+use gitarena_macros::route;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tera::Context;
+
+#[route("/{username}/{repository}/boards", method = "GET", err = "html")]
+pub(crate) async fn all_boards(
+    repo: Repository,
+    uri: web::Path<GitRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let can_manage =
+        privilege::check_manage_issues(&repo, web_user.as_ref(), &mut transaction).await?;
+    let boards = Board::all(repo.id, &mut transaction).await?;
+
+    let mut context = Context::new();
+
+    context.insert_web_user(&web_user)?;
+    context.try_insert("repo_owner_name", uri.username.as_str())?;
+    context.try_insert("repo", &repo)?;
+    context.try_insert("boards", &boards)?;
+    context.try_insert("can_manage", &(can_manage && !repo.archived))?;
+
+    render_template!("repo/boards.html", context, transaction)
+}
+
+#[route("/{username}/{repository}/boards/{id}", method = "GET", err = "html")]
+pub(crate) async fn view_board(
+    repo: Repository,
+    uri: web::Path<BoardRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let board = Board::find(repo.id, uri.id, &mut transaction)
+        .await?
+        .ok_or_else(|| err!(NOT_FOUND, "Board not found"))?;
+
+    let can_manage =
+        privilege::check_manage_issues(&repo, web_user.as_ref(), &mut transaction).await?;
+
+    let columns = board.columns(&mut transaction).await?;
+    let cards = board.cards(&mut transaction).await?;
+
+    let column_labels = columns
+        .iter()
+        .filter_map(|column| column.label)
+        .collect::<Vec<_>>();
+
+    // Issues with a card and open issues collected by label driven columns
+    let issues = sqlx::query_as::<_, Issue>(&format!(
+        "select * from issues where repo = $1 and {} \
+        and (id in (select issue from board_cards where board = $4) or (closed = false and labels && $5)) \
+        order by id",
+        visibility_condition("$2", "$3")
+    ))
+    .bind(repo.id)
+    .bind(can_manage)
+    .bind(web_user.as_ref().map(|user| user.id))
+    .bind(board.id)
+    .bind(&column_labels)
+    .fetch_all(&mut transaction)
+    .await?;
+
+    let user_ids = issues
+        .iter()
+        .flat_map(|issue| std::iter::once(issue.author).chain(issue.assignees.iter().copied()))
+        .unique()
+        .collect::<Vec<_>>();
+
+    let usernames =
+        sqlx::query_as::<_, (i32, String)>("select id, username from users where id = any($1)")
+            .bind(&user_ids)
+            .fetch_all(&mut transaction)
+            .await?
+            .into_iter()
+            .map(|(id, username)| (format!("u{}", id), username))
+            .collect::<HashMap<_, _>>();
+
+    let labels = Label::all(repo.id, &mut transaction)
+        .await?
+        .into_iter()
+        .map(|label| (format!("l{}", label.id), label))
+        .collect::<HashMap<_, _>>();
+
+    let mut remaining = issues
+        .into_iter()
+        .map(|issue| (issue.id, issue))
+        .collect::<HashMap<_, _>>();
+    let mut entries = columns
+        .into_iter()
+        .map(|column| ColumnEntry {
+            column_label: column
+                .label
+                .and_then(|id| labels.get(&format!("l{}", id)).cloned()),
+            column,
+            issues: Vec::new(),
+        })
+        .collect::<Vec<_>>();
+
+    for card in cards {
+        let issue = match remaining.remove(&card.issue) {
+            Some(issue) => issue,
+            None => continue, // Not visible to the user
+        };
+
+        if let Some(entry) = entries
+            .iter_mut()
+            .find(|entry| entry.column.id == card.board_column)
+        {
+            entry.issues.push(issue);
+        }
+    }
+
+    // Issues without a card go into the leftmost column with one of their labels
+    for issue in remaining.into_values().sorted_by_key(|issue| issue.id) {
+        if let Some(entry) = entries.iter_mut().find(|entry| {
+            entry
+                .column
+                .label
+                .map_or(false, |label| issue.labels.contains(&label))
+        }) {
+            entry.issues.push(issue);
+        }
+    }
+
+    let mut context = Context::new();
+
+    context.insert_web_user(&web_user)?;
+    context.try_insert("repo_owner_name", uri.username.as_str())?;
+    context.try_insert("repo", &repo)?;
+    context.try_insert("board", &board)?;
+    context.try_insert("columns", &entries)?;
+    context.try_insert("usernames", &usernames)?;
+    context.try_insert("labels", &labels)?;
+    context.try_insert("can_manage", &(can_manage && !repo.archived))?;
+
+    render_template!("repo/board.html", context, transaction)
+}
+
+#[derive(Deserialize)]
+pub(crate) struct BoardRequest {
+    username: String,
+    id: i32,
+}
+
+#[derive(Serialize)]
+struct ColumnEntry {
+    #[serde(flatten)]
+    column: BoardColumn,
+
+    /// Label driving this column, `label` of the column itself is only its id
+    column_label: Option<Label>,
+    issues: Vec<Issue>,
+}
@@ -0,0 +1,100 @@
+use crate::die;
+use crate::git::basic_auth;
+use crate::git::history::resolve_revision;
+use crate::routes::repository::GitTreeRequest;
+
+use std::process::Stdio;
+
+use actix_web::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use actix_web::{web, Either, HttpRequest, HttpResponse, Responder};
+use anyhow::{anyhow, Result};
+use gitarena_macros::route;
+use log::warn;
+use sqlx::PgPool;
+use tokio::process::Command;
+use tokio_util::io::ReaderStream;
+
+/// Serves a `git bundle` containing the full history of a single reference, for transferring
+/// repositories to machines without network access. Access is checked the same way as for `git clone`.
+#[route(
+    "/{username}/{repository}/bundle/{tree:.*}",
+    method = "GET",
+    err = "text"
+)]
+pub(crate) async fn bundle(
+    uri: web::Path<GitTreeRequest>,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let (_, repo) = match basic_auth::validate_read_access(
+        uri.username.as_str(),
+        uri.repository.as_str(),
+        "text/plain",
+        &request,
+        &mut transaction,
+    )
+    .await?
+    {
+        Either::Left(tuple) => tuple,
+        Either::Right(response) => return Ok(response),
+    };
+
+    let libgit2_repo = repo.libgit2(&mut transaction).await?;
+
+    // Bundles without references cannot be cloned from, so commit ids and expressions such as `HEAD~3` are rejected.
+    // Passing the full reference name also ensures that user input never ends up as a command line option
+    let reference = match resolve_revision(&libgit2_repo, uri.tree.as_str()).await {
+        Some((Some(reference), _)) => reference,
+        Some((None, _)) => die!(
+            BAD_REQUEST,
+            "Bundles can only be created for branches and tags"
+        ),
+        None => die!(NOT_FOUND, "Reference not found"),
+    };
+
+    let repo_dir = repo.get_fs_path(&mut transaction).await?;
+
+    transaction.commit().await?;
+
+    let mut child = Command::new("git")
+        .args(["bundle", "create", "--quiet", "-", reference.as_str()])
+        .current_dir(repo_dir.as_str())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| anyhow!("Failed to open stdout of git bundle"))?;
+
+    tokio::spawn(async move {
+        match child.wait().await {
+            Ok(status) if !status.success() => {
+                warn!("git bundle exited with non-zero status: {}", status)
+            }
+            Err(err) => warn!("Failed to wait for git bundle: {}", err),
+            _ => {}
+        }
+    });
+
+    let short_name = reference
+        .strip_prefix("refs/heads/")
+        .or_else(|| reference.strip_prefix("refs/tags/"))
+        .unwrap_or(reference.as_str())
+        .replace('/', "-");
+
+    Ok(HttpResponse::Ok()
+        .append_header((CONTENT_TYPE, "application/x-git-bundle"))
+        .append_header((
+            CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"{}-{}.bundle\"",
+                repo.name, short_name
+            ),
+        ))
+        .streaming(ReaderStream::new(stdout)))
+}
@@ -1,13 +1,13 @@
 use crate::git::history::{all_branches, all_commits, all_tags};
 use crate::prelude::*;
 use crate::repository::{Branch, RepoOwner, Repository};
+use crate::signatures::verify_commits;
 use crate::templates::web::GitCommit;
 use crate::user::WebUser;
 use crate::{die, render_template};
 
 use actix_web::{web, HttpMessage, HttpRequest, Responder};
 use anyhow::{anyhow, Result};
-use bstr::ByteSlice;
 use gitarena_macros::route;
 use sqlx::PgPool;
 use tera::Context;
@@ -26,7 +26,7 @@ pub(crate) async fn commits(
 ) -> Result<impl Responder> {
     let mut transaction = db_pool.begin().await?;
 
-    let full_tree_name = branch.reference.name.as_bstr().to_str()?;
+    let revision = branch.revision();
 
     let query_string = request.q_string();
     let after_oid = query_string.get("after");
@@ -44,18 +44,30 @@ pub(crate) async fn commits(
     context.try_insert("tree", branch.tree.as_str())?;
 
     let libgit2_repo = repo.libgit2(&mut transaction).await?;
+    let mailmap = libgit2_repo.mailmap().ok();
 
     context.try_insert("branches", &all_branches(&libgit2_repo).await?)?;
     context.try_insert("tags", &all_tags(&libgit2_repo, None).await?)?;
 
-    let searching_ref = after_oid.unwrap_or(full_tree_name);
+    let searching_ref = after_oid.unwrap_or(revision.as_str());
 
     let commit_ids = all_commits(&libgit2_repo, searching_ref, 20).await?;
     let mut commits = Vec::<GitCommit>::with_capacity(commit_ids.len());
 
+    let signatures = verify_commits(
+        &repo,
+        &libgit2_repo,
+        commit_ids.as_slice(),
+        &mut transaction,
+    )
+    .await?;
+
     for oid in commit_ids {
         let commit = libgit2_repo.find_commit(oid)?;
-        let (name, uid, email) = commit.author().try_disassemble(&mut transaction).await;
+        let (name, uid, email) = commit
+            .author()
+            .try_disassemble(mailmap.as_ref(), &mut transaction)
+            .await;
 
         let chrono_time = commit.time().try_as_chrono()?;
         let chrono_date = chrono_time.date();
@@ -69,9 +81,12 @@ pub(crate) async fn commits(
             author_name: name,
             author_uid: uid,
             author_email: email,
+            co_authors: commit.co_authors(mailmap.as_ref()),
         });
     }
 
+    link_co_authors(commits.iter_mut(), &mut transaction).await;
+
     if commits.is_empty() {
         // TODO: Render empty repo skeleton template showing how to push files to this repository
         die!(NOT_FOUND, "Not found");
@@ -82,6 +97,7 @@ pub(crate) async fn commits(
     }
 
     context.try_insert("commits", &commits)?;
+    context.try_insert("signatures", &signatures)?;
     context.insert_web_user(&web_user)?;
 
     // Only send a partial result (only the components) if it's a request by htmx
@@ -1,15 +1,17 @@
 use crate::die;
-use crate::git::hooks::post_update;
+use crate::git::hooks::{issue_references, post_update};
 use crate::git::io::band::Band;
 use crate::git::io::reader::read_data_lines;
 use crate::git::io::writer::GitWriter;
-use crate::git::receive_pack::{process_create_update, process_delete};
+use crate::git::receive_pack::{process_create_update, process_delete, report_status, write_pack};
 use crate::git::ref_update::{RefUpdate, RefUpdateType};
 use crate::git::{basic_auth, pack, ref_update};
 use crate::prelude::*;
 use crate::privileges::privilege;
 use crate::repository::Repository;
 use crate::routes::repository::GitRequest;
+use crate::search;
+use crate::signatures::check_signed_commits;
 
 use std::path::Path;
 use std::process::Stdio;
@@ -130,37 +132,90 @@ pub(crate) async fn git_receive_pack(
 
     let mut output_writer = GitWriter::new();
 
+    // Ref updates which have been applied successfully
+    let mut applied = Vec::<RefUpdate>::with_capacity(updates.len());
+
     let searcher = TwoWaySearcher::new(b"PACK");
 
     match searcher.search_in(vec) {
         Some(pos) => {
-            let (index_path, pack_path, _temp_dir) =
-                pack::read(&vec[pos..], &repo, &mut transaction).await?;
+            let raw_pack = &vec[pos..];
+            let (index_path, pack_path, temp_dir) =
+                pack::read(raw_pack, &repo, &mut transaction).await?;
 
             output_writer
                 .write_text_sideband_pktline(Band::Data, "unpack ok")
                 .await?;
 
+            // New commits are read from the received pack in order to check their signatures,
+            // so rejected pushes do not leave their objects behind in the repository
+            let signature_repo = if repo.require_signed_commits {
+                let libgit2_repo = repo.libgit2(&mut transaction).await?;
+                libgit2_repo
+                    .odb()?
+                    .add_disk_alternate(temp_dir.path().to_string_lossy().as_ref())?;
+
+                Some(libgit2_repo)
+            } else {
+                None
+            };
+
+            // Creations and updates which passed the signature check
+            let mut accepted = Vec::<RefUpdate>::with_capacity(updates.len());
+
             for update in updates {
                 match RefUpdateType::determinate(&update.old, &update.new).await? {
                     RefUpdateType::Create | RefUpdateType::Update => {
-                        process_create_update(
-                            &update,
-                            &repo,
-                            store.clone(),
-                            &db_pool,
-                            &mut output_writer,
-                            index_path.as_ref(),
-                            pack_path.as_ref(),
-                            &vec[pos..],
-                        )
-                        .await?
+                        if let (Some(libgit2_repo), Some(new)) = (&signature_repo, &update.new) {
+                            if update.target_ref.starts_with("refs/heads/") {
+                                if let Some(reason) = check_signed_commits(
+                                    &repo,
+                                    libgit2_repo,
+                                    update.old.as_deref(),
+                                    new.as_str(),
+                                    &mut transaction,
+                                )
+                                .await?
+                                {
+                                    report_status(
+                                        &update,
+                                        Some(reason.as_str()),
+                                        &mut output_writer,
+                                    )
+                                    .await?;
+                                    continue;
+                                }
+                            }
+                        }
+
+                        accepted.push(update);
                     }
                     RefUpdateType::Delete => {
                         process_delete(&update, &repo, &mut transaction, &mut output_writer).await?
                     }
                 };
             }
+
+            // Nothing gets written if all updates have been rejected
+            if !accepted.is_empty() {
+                let libgit2_repo = repo.libgit2(&mut transaction).await?;
+                write_pack(&libgit2_repo, raw_pack)?;
+            }
+
+            for update in accepted {
+                process_create_update(
+                    &update,
+                    &repo,
+                    store.clone(),
+                    &db_pool,
+                    &mut output_writer,
+                    index_path.as_ref(),
+                    pack_path.as_ref(),
+                )
+                .await?;
+
+                applied.push(update);
+            }
         }
         None => {
             if !ref_update::is_only_deletions(updates.as_slice()).await? {
@@ -208,7 +263,7 @@ pub(crate) async fn git_receive_pack(
     output_writer.flush().await?;
 
     // Run post update hooks
-    post_update::run(store, &mut repo, &mut transaction)
+    post_update::run(store, &mut repo, db_pool.get_ref(), &mut transaction)
         .await
         .with_context(|| {
             format!(
@@ -225,6 +280,9 @@ pub(crate) async fn git_receive_pack(
 
     transaction.commit().await?;
 
+    issue_references::schedule(db_pool.get_ref().clone(), repo.id, user.id, applied);
+    search::schedule_index(db_pool.get_ref().clone(), repo.id);
+
     Ok(HttpResponse::Ok()
         .append_header((CONTENT_TYPE, accept_header))
         .body(output_writer.serialize().await?))
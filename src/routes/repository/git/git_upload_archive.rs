@@ -0,0 +1,113 @@
+use crate::die;
+use crate::git::basic_auth;
+use crate::prelude::*;
+use crate::routes::repository::GitRequest;
+
+use std::process::Stdio;
+
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::{web, Either, HttpRequest, HttpResponse, Responder};
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use gitarena_macros::route;
+use log::warn;
+use sqlx::PgPool;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio_util::io::ReaderStream;
+
+/// Maximum size of the `argument` pkt-lines sent by the client. Git itself limits upload-archive to 64 arguments
+/// of at most one pkt-line (65520 bytes) each, real requests are only a few hundred bytes.
+const MAX_ARGUMENTS_SIZE: usize = 64 * 1024;
+
+/// `git archive --remote` over smart HTTP. The request body contains the `argument` pkt-lines sent by the client
+/// and is passed to `git upload-archive`, which validates the arguments and only allows archiving reachable objects.
+///
+/// The `git` command line client only supports `--remote` over SSH and the git protocol. This endpoint is used by
+/// clients which speak the upload-archive protocol over HTTP themselves, e.g. tooling in environments blocking SSH.
+#[route(
+    "/{username}/{repository}.git/git-upload-archive",
+    method = "POST",
+    err = "git"
+)]
+pub(crate) async fn git_upload_archive(
+    uri: web::Path<GitRequest>,
+    mut body: web::Payload,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let content_type = request.get_header("content-type").unwrap_or_default();
+
+    if content_type != "application/x-git-upload-archive-request" {
+        die!(BAD_REQUEST);
+    }
+
+    let mut transaction = db_pool.begin().await?;
+
+    let (_, repo) = match basic_auth::validate_read_access(
+        uri.username.as_str(),
+        uri.repository.as_str(),
+        "application/x-git-upload-archive-result",
+        &request,
+        &mut transaction,
+    )
+    .await?
+    {
+        Either::Left(tuple) => tuple,
+        Either::Right(response) => return Ok(response),
+    };
+
+    let repo_dir = repo.get_fs_path(&mut transaction).await?;
+
+    transaction.commit().await?;
+
+    let mut bytes = web::BytesMut::new();
+
+    while let Some(item) = body.next().await {
+        let item = item?;
+
+        if bytes.len() + item.len() > MAX_ARGUMENTS_SIZE {
+            die!(PAYLOAD_TOO_LARGE, "Too many archive arguments");
+        }
+
+        bytes.extend_from_slice(&item);
+    }
+
+    let mut child = Command::new("git")
+        .arg("upload-archive")
+        .arg(repo_dir.as_str())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("Failed to open stdin of git upload-archive"))?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| anyhow!("Failed to open stdout of git upload-archive"))?;
+
+    tokio::spawn(async move {
+        // Arguments are small, but upload-archive only starts writing once all of them have been read
+        if let Err(err) = stdin.write_all(&bytes[..]).await {
+            warn!("Failed to pass arguments to git upload-archive: {}", err);
+        }
+
+        drop(stdin);
+
+        match child.wait().await {
+            Ok(status) if !status.success() => {
+                warn!("git upload-archive exited with non-zero status: {}", status)
+            }
+            Err(err) => warn!("Failed to wait for git upload-archive: {}", err),
+            _ => {}
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .append_header((CONTENT_TYPE, "application/x-git-upload-archive-result"))
+        .streaming(ReaderStream::new(stdout)))
+}
@@ -1,8 +1,16 @@
-use crate::issue::Issue;
-use crate::prelude::ContextExtensions;
-use crate::render_template;
+use crate::incoming_mail::{reply_address, MailTarget};
+use crate::issue::{Issue, IssueComment, IssueEvent};
+use crate::issue_query::{search_issues, IssueCursor, IssueQuery};
+use crate::issue_template::issue_templates;
+use crate::label::Label;
+use crate::markup::{render_markdown, MarkupContext};
+use crate::milestone::Milestone;
+use crate::prelude::{ContextExtensions, HttpRequestExtensions};
+use crate::privileges::privilege;
 use crate::repository::{RepoOwner, Repository};
-use crate::user::WebUser;
+use crate::routes::repository::{GitRequest, IssueRequest};
+use crate::user::{User, WebUser};
+use crate::{die, err, render_template};
 
 use std::collections::HashMap;
 
@@ -10,70 +18,78 @@ use actix_web::{web, HttpMessage, HttpRequest, Responder};
 use anyhow::{anyhow, Result};
 use gitarena_macros::route;
 use itertools::Itertools;
-use sqlx::PgPool;
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, Transaction};
 use tera::Context;
 
-#[route("/{username}/{repository}/issues", method = "GET", err = "html")]
+/// Amount of issues shown per page
+pub(crate) const PAGE_SIZE: i64 = 25;
+
+#[route("/{username}/{repository}/issues", method = "GET", err = "htmx+html")]
 pub(crate) async fn all_issues(
     repo: Repository,
     web_user: WebUser,
     request: HttpRequest,
     db_pool: web::Data<PgPool>,
 ) -> Result<impl Responder> {
-    let mut transaction = db_pool.begin().await?;
+    let query_string = request.q_string();
 
-    let confidential = if web_user
-        .as_ref()
-        .map_or_else(|| false, |user| user.id == repo.owner)
-    {
-        "1 = 1"
-    } else {
-        "confidential = false"
-    };
+    // Without a query only open issues are shown, an empty query shows all issues
+    let input = query_string.get("q").unwrap_or("is:open").trim();
+    let after = query_string.get("after");
 
-    let issues = sqlx::query_as::<_, Issue>(
-        format!(
-            "select * from issues where repo = $1 and {} order by id desc",
-            confidential
-        )
-        .as_str(),
-    )
-    .bind(repo.id)
-    .fetch_all(&mut transaction)
-    .await?;
+    let mut transaction = db_pool.begin().await?;
 
-    // This is really ugly and needs to be changed
-    // TODO: Is there a way to map the original Issue struct to include these infos?
-    let mut usernames = HashMap::new();
+    let (issues, next) =
+        find_issues(&repo, &web_user, input, after, PAGE_SIZE, &mut transaction).await?;
 
-    for issue in issues.iter() {
-        let (username,): (String,) =
-            sqlx::query_as("select username from users where id = $1 limit 1")
-                .bind(issue.author)
-                .fetch_one(&mut transaction)
-                .await?;
+    let user_ids = issues
+        .iter()
+        .flat_map(|issue| std::iter::once(issue.author).chain(issue.assignees.iter().copied()))
+        .unique()
+        .collect::<Vec<_>>();
 
-        usernames.insert(format!("u{}", issue.author), username);
+    let usernames =
+        sqlx::query_as::<_, (i32, String)>("select id, username from users where id = any($1)")
+            .bind(&user_ids)
+            .fetch_all(&mut transaction)
+            .await?
+            .into_iter()
+            .map(|(id, username)| (format!("u{}", id), username))
+            .collect::<HashMap<_, _>>();
 
-        // TODO: Serialize milestone and label strings here as well
-        if !issue.assignees.is_empty() {
-            // This workaround can be removed once Vec can be passed directly: https://github.com/launchbadge/sqlx/issues/875
-            let haystack = &issue.assignees.iter().join(",");
-            let query = format!("select id, username from users where id in ({})", haystack);
+    let issue_ids = issues.iter().map(|issue| issue.id).collect::<Vec<_>>();
+    let counts: HashMap<i32, i64> = sqlx::query_as::<_, (i32, i64)>(
+        "select issue, count(*) from issue_comments where issue = any($1) and deleted_at is null group by issue",
+    )
+    .bind(&issue_ids)
+    .fetch_all(&mut transaction)
+    .await?
+    .into_iter()
+    .collect();
 
-            let db_usernames: Vec<(i32, String)> = sqlx::query_as(query.as_str())
-                .fetch_all(&mut transaction)
-                .await?;
+    let comment_counts = issue_ids
+        .iter()
+        .map(|id| (format!("i{}", id), counts.get(id).copied().unwrap_or(0)))
+        .collect::<HashMap<_, _>>();
 
-            for (id, username) in db_usernames.into_iter() {
-                usernames.insert(format!("u{}", id), username);
-            }
-        }
-    }
+    let labels = Label::all(repo.id, &mut transaction)
+        .await?
+        .into_iter()
+        .map(|label| (format!("l{}", label.id), label))
+        .collect::<HashMap<_, _>>();
+    let milestones = Milestone::all(repo.id, &mut transaction)
+        .await?
+        .into_iter()
+        .map(|milestone| (format!("m{}", milestone.id), milestone.title))
+        .collect::<HashMap<_, _>>();
 
     let mut context = Context::new();
 
     context.try_insert("usernames", &usernames)?;
+    context.try_insert("comment_counts", &comment_counts)?;
+    context.try_insert("labels", &labels)?;
+    context.try_insert("milestones", &milestones)?;
 
     context.try_insert("repo", &repo)?;
 
@@ -84,8 +100,431 @@ pub(crate) async fn all_issues(
     context.try_insert("repo_owner_name", &repo_owner.0)?;
 
     context.try_insert("issues", &issues)?;
+    context.try_insert("input", input)?;
+    context.try_insert("next", &next)?;
     context.insert_web_user(&web_user)?;
 
-    // TODO: Change this to be infinite scrolling like commit list and explore?
+    // Only send a partial result (only the component) if it's a request by htmx
+    if request.is_htmx() {
+        return render_template!("repo/issue_list_component.html", context, transaction);
+    }
+
+    if let Some(user) = web_user.as_ref().filter(|_| !repo.archived) {
+        let address =
+            reply_address(MailTarget::Repository(repo.id), user, &mut transaction).await?;
+        context.try_insert("mail_address", &address)?;
+    }
+
     render_template!("repo/issues.html", context, transaction)
 }
+
+#[route("/{username}/{repository}/issues/new", method = "GET", err = "html")]
+pub(crate) async fn new_issue(
+    repo: Repository,
+    uri: web::Path<GitRequest>,
+    web_user: WebUser,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let user = web_user.into_user()?;
+
+    if repo.archived {
+        die!(FORBIDDEN, "Repository is archived and thus read-only");
+    }
+
+    let mut transaction = db_pool.begin().await?;
+
+    let mut context = Context::new();
+
+    context.insert_user(&user)?;
+    context.try_insert("repo_owner_name", uri.username.as_str())?;
+    context.try_insert("repo", &repo)?;
+
+    let templates = issue_templates(&repo, &mut transaction).await?;
+    let query_string = request.q_string();
+
+    // Users choose a template first, an empty `template` opens a blank issue
+    match query_string.get("template") {
+        None if !templates.is_empty() => context.try_insert("templates", &templates)?,
+        Some(file_name) => {
+            if let Some(mut template) = templates
+                .into_iter()
+                .find(|template| template.file_name == file_name)
+            {
+                let markup_context = MarkupContext::new(
+                    uri.username.as_str(),
+                    repo.name.as_str(),
+                    repo.default_branch.as_str(),
+                    "",
+                );
+                template.render_markdown(&markup_context);
+
+                context.try_insert("template", &template)?;
+            }
+        }
+        None => {}
+    }
+
+    render_template!("repo/issue_edit.html", context, transaction)
+}
+
+#[route(
+    "/{username}/{repository}/issues/{index}",
+    method = "GET",
+    err = "html"
+)]
+pub(crate) async fn view_issue(
+    repo: Repository,
+    uri: web::Path<IssueRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let (issue, can_manage) =
+        find_visible_issue(&repo, uri.index, &web_user, &mut transaction).await?;
+
+    let revisions = issue.revisions(&mut transaction).await?;
+    let body = revisions
+        .last()
+        .map(|revision| revision.body.as_str())
+        .unwrap_or_default();
+
+    let markup_context = MarkupContext::new(
+        uri.username.as_str(),
+        repo.name.as_str(),
+        repo.default_branch.as_str(),
+        "",
+    );
+    let html = render_markdown(body, &markup_context);
+
+    let (author_name,): (String,) =
+        sqlx::query_as("select username from users where id = $1 limit 1")
+            .bind(issue.author)
+            .fetch_one(&mut transaction)
+            .await?;
+
+    let can_edit = web_user.as_ref().map_or(false, |user| {
+        !repo.archived && issue.is_editable_by(user, can_manage)
+    });
+    let can_comment =
+        web_user.as_ref().is_some() && !repo.archived && issue.accepts_comments_from(can_manage);
+
+    let timeline = timeline(&issue, &markup_context, &mut transaction).await?;
+
+    let labels = Label::find_all(&issue.labels, &mut transaction).await?;
+    let milestone = match issue.milestone {
+        Some(id) => Milestone::find(repo.id, id, &mut transaction).await?,
+        None => None,
+    };
+
+    let mut context = Context::new();
+
+    context.insert_web_user(&web_user)?;
+    context.try_insert("repo_owner_name", uri.username.as_str())?;
+    context.try_insert("repo", &repo)?;
+    context.try_insert("issue", &issue)?;
+    context.try_insert("author_name", &author_name)?;
+    context.try_insert("html", &html)?;
+    context.try_insert("edits", &revisions.len().saturating_sub(1))?;
+    context.try_insert("timeline", &timeline)?;
+    context.try_insert("can_edit", &can_edit)?;
+    context.try_insert("can_comment", &can_comment)?;
+    context.try_insert("can_manage", &(can_manage && !repo.archived))?;
+    context.try_insert("labels", &labels)?;
+    context.try_insert("milestone", &milestone)?;
+
+    if let Some(user) = web_user.as_ref().filter(|_| can_comment) {
+        let address = reply_address(MailTarget::Issue(issue.id), user, &mut transaction).await?;
+        context.try_insert("mail_address", &address)?;
+    }
+
+    // Choices for the label and milestone selection
+    if can_manage && !repo.archived {
+        context.try_insert("all_labels", &Label::all(repo.id, &mut transaction).await?)?;
+        context.try_insert(
+            "all_milestones",
+            &Milestone::all(repo.id, &mut transaction).await?,
+        )?;
+    }
+
+    render_template!("repo/issue.html", context, transaction)
+}
+
+#[route(
+    "/{username}/{repository}/issues/{index}/edit",
+    method = "GET",
+    err = "html"
+)]
+pub(crate) async fn edit_issue(
+    repo: Repository,
+    uri: web::Path<IssueRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let (issue, can_manage) =
+        find_visible_issue(&repo, uri.index, &web_user, &mut transaction).await?;
+    let user = web_user.into_user()?;
+
+    if repo.archived {
+        die!(FORBIDDEN, "Repository is archived and thus read-only");
+    }
+
+    if !issue.is_editable_by(&user, can_manage) {
+        die!(FORBIDDEN, "Insufficient permissions to edit this issue");
+    }
+
+    let body = issue
+        .current_revision(&mut transaction)
+        .await?
+        .map(|revision| revision.body)
+        .unwrap_or_default();
+
+    let mut context = Context::new();
+
+    context.insert_user(&user)?;
+    context.try_insert("repo_owner_name", uri.username.as_str())?;
+    context.try_insert("repo", &repo)?;
+    context.try_insert("issue", &issue)?;
+    context.try_insert("body", &body)?;
+
+    render_template!("repo/issue_edit.html", context, transaction)
+}
+
+/// Looks up an issue by its index. Confidential issues are only returned if the user is allowed to see them.
+///
+/// Also returns whether the user is allowed to manage issues of this repository.
+pub(crate) async fn find_visible_issue(
+    repo: &Repository,
+    index: i32,
+    web_user: &WebUser,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(Issue, bool)> {
+    let issue = Issue::find(repo.id, index, &mut *transaction)
+        .await?
+        .ok_or_else(|| err!(NOT_FOUND, "Issue not found"))?;
+
+    let can_manage =
+        privilege::check_manage_issues(repo, web_user.as_ref(), &mut *transaction).await?;
+
+    if !issue.is_visible_to(web_user.as_ref(), can_manage) {
+        die!(NOT_FOUND, "Issue not found");
+    }
+
+    Ok((issue, can_manage))
+}
+
+/// Returns a page of issues matching the [query](IssueQuery) `input`, starting after the cursor `after`.
+///
+/// Also returns the cursor of the next page if there might be more issues.
+pub(crate) async fn find_issues(
+    repo: &Repository,
+    web_user: &WebUser,
+    input: &str,
+    after: Option<&str>,
+    limit: i64,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(Vec<Issue>, Option<String>)> {
+    let cursor = match after {
+        Some(after) => {
+            Some(IssueCursor::parse(after).ok_or_else(|| err!(BAD_REQUEST, "Invalid cursor"))?)
+        }
+        None => None,
+    };
+
+    let query = IssueQuery::parse(input);
+
+    let filter = match query
+        .resolve(repo.id, web_user.as_ref(), &mut *transaction)
+        .await?
+    {
+        Some(filter) => filter,
+        None => return Ok((Vec::new(), None)),
+    };
+
+    let can_manage =
+        privilege::check_manage_issues(repo, web_user.as_ref(), &mut *transaction).await?;
+
+    let issues = search_issues(
+        repo.id,
+        &filter,
+        web_user.as_ref(),
+        can_manage,
+        cursor,
+        limit,
+        &mut *transaction,
+    )
+    .await?;
+
+    let next = issues
+        .last()
+        .filter(|_| issues.len() as i64 == limit)
+        .map(|issue| IssueCursor::after(issue, filter.sort).to_string());
+
+    Ok((issues, next))
+}
+
+/// Returns the user if they're allowed to manage issues, labels and milestones of this repository
+pub(crate) async fn check_manage_access(
+    repo: &Repository,
+    web_user: WebUser,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<User> {
+    let user = web_user.into_user()?;
+
+    if repo.archived {
+        die!(FORBIDDEN, "Repository is archived and thus read-only");
+    }
+
+    if !privilege::check_manage_issues(repo, Some(&user), &mut *transaction).await? {
+        die!(FORBIDDEN, "Insufficient permissions to manage issues");
+    }
+
+    Ok(user)
+}
+
+/// Builds the timeline of an issue: Events and top level comments (with their replies), oldest first
+pub(crate) async fn timeline(
+    issue: &Issue,
+    markup_context: &MarkupContext,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Vec<TimelineEntry>> {
+    let events = issue.events(&mut *transaction).await?;
+    let comments = issue.comments(&mut *transaction).await?;
+
+    let user_ids = events
+        .iter()
+        .flat_map(|event| [event.actor, event.assignee])
+        .chain(
+            comments
+                .iter()
+                .flat_map(|comment| [comment.author, comment.deleted_by]),
+        )
+        .flatten()
+        .unique()
+        .collect::<Vec<_>>();
+
+    let usernames: HashMap<i32, String> =
+        sqlx::query_as::<_, (i32, String)>("select id, username from users where id = any($1)")
+            .bind(&user_ids)
+            .fetch_all(&mut *transaction)
+            .await?
+            .into_iter()
+            .collect();
+
+    let label_ids = events
+        .iter()
+        .filter_map(|event| event.label)
+        .unique()
+        .collect::<Vec<_>>();
+    let labels: HashMap<i32, Label> = Label::find_all(&label_ids, &mut *transaction)
+        .await?
+        .into_iter()
+        .map(|label| (label.id, label))
+        .collect();
+
+    let milestone_ids = events
+        .iter()
+        .filter_map(|event| event.milestone)
+        .unique()
+        .collect::<Vec<_>>();
+    let milestone_titles: HashMap<i32, String> =
+        sqlx::query_as::<_, (i32, String)>("select id, title from milestones where id = any($1)")
+            .bind(&milestone_ids)
+            .fetch_all(&mut *transaction)
+            .await?
+            .into_iter()
+            .collect();
+
+    let username = |id: Option<i32>| id.and_then(|id| usernames.get(&id).cloned());
+
+    let mut replies = HashMap::<i32, Vec<CommentEntry>>::new();
+    let mut entries = Vec::<TimelineEntry>::with_capacity(events.len() + comments.len());
+
+    for event in events {
+        entries.push(TimelineEntry::Event(EventEntry {
+            actor_name: username(event.actor),
+            assignee_name: username(event.assignee),
+            label: event.label.and_then(|id| labels.get(&id).cloned()),
+            milestone_title: event
+                .milestone
+                .and_then(|id| milestone_titles.get(&id).cloned()),
+            event,
+        }));
+    }
+
+    for mut comment in comments {
+        let deleted = comment.is_deleted();
+
+        if deleted {
+            comment.body = String::new();
+        }
+
+        let entry = CommentEntry {
+            author_name: username(comment.author),
+            deleted_by_name: username(comment.deleted_by),
+            html: render_markdown(comment.body.as_str(), markup_context),
+            edited: comment.updated_at > comment.created_at,
+            deleted,
+            replies: Vec::new(),
+            comment,
+        };
+
+        match entry.comment.parent {
+            Some(parent) => replies.entry(parent).or_default().push(entry),
+            None => entries.push(TimelineEntry::Comment(entry)),
+        }
+    }
+
+    for entry in entries.iter_mut() {
+        if let TimelineEntry::Comment(comment) = entry {
+            comment.replies = replies.remove(&comment.comment.id).unwrap_or_default();
+        }
+    }
+
+    // Stable sort, so events which happened at the same time as a comment stay in front of it
+    entries.sort_by_key(|entry| match entry {
+        TimelineEntry::Event(event) => event.event.created_at,
+        TimelineEntry::Comment(comment) => comment.comment.created_at,
+    });
+
+    Ok(entries)
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum TimelineEntry {
+    Event(EventEntry),
+    Comment(CommentEntry),
+}
+
+#[derive(Serialize)]
+pub(crate) struct EventEntry {
+    #[serde(flatten)]
+    event: IssueEvent,
+
+    actor_name: Option<String>,
+    assignee_name: Option<String>,
+
+    /// `None` if the label or milestone has since been deleted
+    label: Option<Label>,
+    milestone_title: Option<String>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct CommentEntry {
+    /// Body is empty if the comment has been deleted
+    #[serde(flatten)]
+    comment: IssueComment,
+
+    author_name: Option<String>,
+    deleted_by_name: Option<String>,
+
+    html: String,
+    edited: bool,
+    deleted: bool,
+
+    replies: Vec<CommentEntry>,
+}
@@ -0,0 +1,114 @@
+use crate::issue::visibility_condition;
+use crate::label::Label;
+use crate::milestone::Milestone;
+use crate::prelude::ContextExtensions;
+use crate::privileges::privilege;
+use crate::render_template;
+use crate::repository::Repository;
+use crate::routes::repository::GitRequest;
+use crate::user::WebUser;
+
+use std::collections::HashMap;
+
+use actix_web::{web, Responder};
+use anyhow::Result;
+use gitarena_macros::route;
+use serde::Serialize;
+use sqlx::PgPool;
+use tera::Context;
+
+#[route("/{username}/{repository}/labels", method = "GET", err = "html")]
+pub(crate) async fn all_labels(
+    repo: Repository,
+    uri: web::Path<GitRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let can_manage =
+        privilege::check_manage_issues(&repo, web_user.as_ref(), &mut transaction).await?;
+    let user_id = web_user.as_ref().map(|user| user.id);
+
+    let labels = Label::all(repo.id, &mut transaction).await?;
+
+    // Confidential issues are only counted if they are visible to the user
+    let counts: HashMap<String, i64> = sqlx::query_as::<_, (i32, i64)>(&format!(
+        "select unnest(labels) as label, count(*) from issues \
+        where repo = $1 and closed = false and {} \
+        group by label",
+        visibility_condition("$2", "$3")
+    ))
+    .bind(repo.id)
+    .bind(can_manage)
+    .bind(user_id)
+    .fetch_all(&mut transaction)
+    .await?
+    .into_iter()
+    .map(|(id, count)| (format!("l{}", id), count))
+    .collect();
+
+    let mut context = Context::new();
+
+    context.insert_web_user(&web_user)?;
+    context.try_insert("repo_owner_name", uri.username.as_str())?;
+    context.try_insert("repo", &repo)?;
+    context.try_insert("labels", &labels)?;
+    context.try_insert("open_issues", &counts)?;
+    context.try_insert("can_manage", &(can_manage && !repo.archived))?;
+
+    render_template!("repo/labels.html", context, transaction)
+}
+
+#[route("/{username}/{repository}/milestones", method = "GET", err = "html")]
+pub(crate) async fn all_milestones(
+    repo: Repository,
+    uri: web::Path<GitRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let can_manage =
+        privilege::check_manage_issues(&repo, web_user.as_ref(), &mut transaction).await?;
+
+    let milestones = Milestone::all(repo.id, &mut transaction).await?;
+    let mut entries = Vec::<MilestoneEntry>::with_capacity(milestones.len());
+
+    for milestone in milestones {
+        let (closed_issues, total_issues) = milestone
+            .progress(web_user.as_ref(), can_manage, &mut transaction)
+            .await?;
+
+        entries.push(MilestoneEntry {
+            percentage: if total_issues > 0 {
+                closed_issues * 100 / total_issues
+            } else {
+                0
+            },
+            milestone,
+            closed_issues,
+            total_issues,
+        });
+    }
+
+    let mut context = Context::new();
+
+    context.insert_web_user(&web_user)?;
+    context.try_insert("repo_owner_name", uri.username.as_str())?;
+    context.try_insert("repo", &repo)?;
+    context.try_insert("milestones", &entries)?;
+    context.try_insert("can_manage", &(can_manage && !repo.archived))?;
+
+    render_template!("repo/milestones.html", context, transaction)
+}
+
+#[derive(Serialize)]
+struct MilestoneEntry {
+    #[serde(flatten)]
+    milestone: Milestone,
+
+    closed_issues: i64,
+    total_issues: i64,
+    percentage: i64,
+}
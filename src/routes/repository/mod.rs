@@ -4,10 +4,15 @@ use serde::Deserialize;
 mod api;
 mod archive;
 mod blobs;
+mod boards;
+mod bundle;
 mod commits;
 mod git;
 mod import;
 mod issues;
+mod labels;
+mod refs;
+mod releases;
 mod repo_create;
 mod repo_view;
 
@@ -18,8 +23,27 @@ pub(crate) fn init(config: &mut ServiceConfig) {
 
     config.service(commits::commits);
     config.service(archive::tar_gz_file);
+    config.service(archive::tar_bz2_file);
+    config.service(archive::tar_xz_file);
+    config.service(archive::tar_zst_file);
     config.service(archive::zip_file);
+    config.service(boards::all_boards);
+    config.service(boards::view_board);
+    config.service(bundle::bundle);
     config.service(issues::all_issues);
+    config.service(issues::new_issue);
+    config.service(issues::view_issue);
+    config.service(issues::edit_issue);
+    config.service(labels::all_labels);
+    config.service(labels::all_milestones);
+    config.service(refs::all_branches);
+    config.service(refs::all_tags);
+    config.service(refs::view_tag);
+    config.service(releases::all_releases);
+    config.service(releases::latest_release);
+    config.service(releases::latest_asset);
+    config.service(releases::view_release);
+    config.service(releases::download_asset);
     config.service(import::import_repo);
     config.service(repo_create::new_repo);
     config.service(repo_view::view_repo);
@@ -38,3 +62,33 @@ pub(crate) struct GitTreeRequest {
     pub(crate) repository: String,
     pub(crate) tree: String,
 }
+
+#[derive(Deserialize)]
+pub(crate) struct IssueRequest {
+    pub(crate) username: String,
+    pub(crate) repository: String,
+    pub(crate) index: i32,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct IssueCommentRequest {
+    pub(crate) username: String,
+    pub(crate) repository: String,
+    pub(crate) index: i32,
+    pub(crate) id: i32,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ReleaseRequest {
+    pub(crate) username: String,
+    pub(crate) repository: String,
+    pub(crate) tag: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ReleaseAssetRequest {
+    pub(crate) username: String,
+    pub(crate) repository: String,
+    pub(crate) tag: String,
+    pub(crate) name: String,
+}
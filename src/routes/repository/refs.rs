@@ -0,0 +1,342 @@
+use crate::git::history::last_commit_for_ref;
+use crate::git::refs::{ahead_behind, find_tag, list_branches, list_tags, RefSummary};
+use crate::prelude::{
+    link_co_authors, ContextExtensions, HttpRequestExtensions, LibGit2CommitExtensions,
+    LibGit2SignatureExtensions,
+};
+use crate::release::Release;
+use crate::repository::Repository;
+use crate::routes::repository::{GitRequest, ReleaseRequest};
+use crate::signatures::{verify_tag, Verification};
+use crate::templates::web::GitCommit;
+use crate::user::WebUser;
+use crate::{die, render_template};
+
+use actix_web::{web, HttpRequest, Responder};
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use git2::{Mailmap, Oid, Repository as Git2Repository};
+use gitarena_macros::route;
+use qstring::QString;
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use tera::Context;
+
+/// Amount of branches or tags shown per page
+pub(crate) const PAGE_SIZE: usize = 20;
+
+/// Branches without commits in this many days are considered stale
+const STALE_AFTER_DAYS: i64 = 90;
+
+#[derive(Serialize)]
+pub(crate) struct BranchEntry {
+    pub(crate) name: String,
+    pub(crate) commit: GitCommit,
+
+    /// Commits on this branch which are not on the default branch
+    pub(crate) ahead: usize,
+    /// Commits on the default branch which are not on this branch
+    pub(crate) behind: usize,
+
+    pub(crate) default: bool,
+    pub(crate) stale: bool,
+}
+
+#[derive(Serialize)]
+pub(crate) struct TagEntry {
+    pub(crate) name: String,
+    /// Commit the tag points to
+    pub(crate) commit: GitCommit,
+
+    /// `None` for lightweight tags
+    pub(crate) annotation: Option<TagAnnotation>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct TagAnnotation {
+    pub(crate) oid: String,
+    pub(crate) message: String,
+    pub(crate) time: i64, // Unix timestamp
+
+    pub(crate) tagger_name: String,
+    pub(crate) tagger_uid: Option<i32>,
+    pub(crate) tagger_email: String,
+
+    /// `None` if the tag is not signed
+    pub(crate) signature: Option<Verification>,
+}
+
+/// Filters of the branches and tags lists, parsed from the query string
+pub(crate) struct RefQuery {
+    pub(crate) search: String,
+    pub(crate) offset: usize,
+    /// Either `all`, `active` or `stale`. Only applies to branches.
+    pub(crate) state: String,
+}
+
+impl RefQuery {
+    pub(crate) fn parse(query_string: &QString) -> RefQuery {
+        RefQuery {
+            search: query_string.get("q").unwrap_or_default().trim().to_owned(),
+            offset: query_string
+                .get("offset")
+                .and_then(|offset| offset.parse::<usize>().ok())
+                .unwrap_or(0),
+            state: match query_string.get("state") {
+                Some(state @ ("active" | "stale")) => state.to_owned(),
+                _ => "all".to_owned(),
+            },
+        }
+    }
+}
+
+/// Returns a page of branches and whether there are more pages
+pub(crate) async fn branch_page(
+    repo: &Repository,
+    libgit2_repo: &Git2Repository,
+    query: &RefQuery,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(Vec<BranchEntry>, bool)> {
+    let stale_before = (Utc::now() - Duration::days(STALE_AFTER_DAYS)).timestamp();
+
+    let branches = list_branches(libgit2_repo, query.search.as_str())?
+        .into_iter()
+        .filter(|branch| match query.state.as_str() {
+            "active" => branch.time >= stale_before,
+            "stale" => branch.time < stale_before,
+            _ => true,
+        })
+        .collect::<Vec<_>>();
+
+    let more = branches.len() > query.offset + PAGE_SIZE;
+
+    let default_commit = last_commit_for_ref(libgit2_repo, repo.default_branch.as_str()).await?;
+    let mailmap = libgit2_repo.mailmap().ok();
+
+    let mut entries = Vec::<BranchEntry>::with_capacity(PAGE_SIZE);
+
+    for branch in branches.into_iter().skip(query.offset).take(PAGE_SIZE) {
+        let (ahead, behind) = match default_commit {
+            Some(default_commit) => ahead_behind(libgit2_repo, branch.commit, default_commit)?,
+            None => (0, 0),
+        };
+
+        entries.push(BranchEntry {
+            default: branch.name == repo.default_branch,
+            stale: branch.time < stale_before,
+            commit: git_commit(libgit2_repo, branch.commit, mailmap.as_ref(), transaction).await?,
+            name: branch.name,
+            ahead,
+            behind,
+        });
+    }
+
+    link_co_authors(
+        entries.iter_mut().map(|entry| &mut entry.commit),
+        &mut *transaction,
+    )
+    .await;
+
+    Ok((entries, more))
+}
+
+/// Returns a page of tags and whether there are more pages
+pub(crate) async fn tag_page(
+    repo: &Repository,
+    libgit2_repo: &Git2Repository,
+    query: &RefQuery,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(Vec<TagEntry>, bool)> {
+    let tags = list_tags(libgit2_repo, query.search.as_str())?;
+    let more = tags.len() > query.offset + PAGE_SIZE;
+
+    let mut entries = Vec::<TagEntry>::with_capacity(PAGE_SIZE);
+
+    for tag in tags.into_iter().skip(query.offset).take(PAGE_SIZE) {
+        entries.push(tag_entry(repo, libgit2_repo, tag, transaction).await?);
+    }
+
+    link_co_authors(
+        entries.iter_mut().map(|entry| &mut entry.commit),
+        &mut *transaction,
+    )
+    .await;
+
+    Ok((entries, more))
+}
+
+/// Looks up a single tag, returns `None` if it does not exist
+pub(crate) async fn find_tag_entry(
+    repo: &Repository,
+    libgit2_repo: &Git2Repository,
+    name: &str,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Option<TagEntry>> {
+    let mut entry = match find_tag(libgit2_repo, name)? {
+        Some(tag) => tag_entry(repo, libgit2_repo, tag, transaction).await?,
+        None => return Ok(None),
+    };
+
+    link_co_authors(Some(&mut entry.commit), &mut *transaction).await;
+
+    Ok(Some(entry))
+}
+
+async fn tag_entry(
+    repo: &Repository,
+    libgit2_repo: &Git2Repository,
+    tag: RefSummary,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<TagEntry> {
+    let mailmap = libgit2_repo.mailmap().ok();
+
+    let annotation = match tag.tag {
+        Some(oid) => {
+            let tag_object = libgit2_repo.find_tag(oid)?;
+
+            let (tagger_name, tagger_uid, tagger_email) = match tag_object.tagger() {
+                Some(tagger) => {
+                    tagger
+                        .try_disassemble(mailmap.as_ref(), &mut *transaction)
+                        .await
+                }
+                None => ("Ghost".to_owned(), None, String::new()),
+            };
+
+            Some(TagAnnotation {
+                oid: oid.to_string(),
+                message: tag_object.message().unwrap_or_default().to_owned(),
+                time: tag.time,
+                tagger_name,
+                tagger_uid,
+                tagger_email,
+                signature: verify_tag(repo, libgit2_repo, oid, transaction).await?,
+            })
+        }
+        None => None,
+    };
+
+    Ok(TagEntry {
+        commit: git_commit(libgit2_repo, tag.commit, mailmap.as_ref(), transaction).await?,
+        name: tag.name,
+        annotation,
+    })
+}
+
+async fn git_commit(
+    libgit2_repo: &Git2Repository,
+    oid: Oid,
+    mailmap: Option<&Mailmap>,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<GitCommit> {
+    let commit = libgit2_repo.find_commit(oid)?;
+    let (author_name, author_uid, author_email) = commit
+        .author()
+        .try_disassemble(mailmap, &mut *transaction)
+        .await;
+
+    Ok(GitCommit {
+        oid: oid.to_string(),
+        message: commit.message().unwrap_or_default().to_owned(),
+        time: commit.time().seconds(),
+        date: None,
+        author_name,
+        author_uid,
+        author_email,
+        co_authors: commit.co_authors(mailmap),
+    })
+}
+
+#[route("/{username}/{repository}/branches", method = "GET", err = "html")]
+pub(crate) async fn all_branches(
+    repo: Repository,
+    uri: web::Path<GitRequest>,
+    web_user: WebUser,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let libgit2_repo = repo.libgit2(&mut transaction).await?;
+    let query = RefQuery::parse(&request.q_string());
+
+    let (branches, more) = branch_page(&repo, &libgit2_repo, &query, &mut transaction).await?;
+    let (stale, active): (Vec<_>, Vec<_>) = branches.into_iter().partition(|branch| branch.stale);
+
+    let mut context = Context::new();
+
+    context.insert_web_user(&web_user)?;
+    context.try_insert("repo_owner_name", uri.username.as_str())?;
+    context.try_insert("repo", &repo)?;
+    context.try_insert("active_branches", &active)?;
+    context.try_insert("stale_branches", &stale)?;
+    context.try_insert("search", query.search.as_str())?;
+    context.try_insert("state", query.state.as_str())?;
+    context.try_insert("offset", &query.offset)?;
+    context.try_insert("page_size", &PAGE_SIZE)?;
+    context.try_insert("more", &more)?;
+
+    render_template!("repo/branches.html", context, transaction)
+}
+
+#[route("/{username}/{repository}/tags", method = "GET", err = "html")]
+pub(crate) async fn all_tags(
+    repo: Repository,
+    uri: web::Path<GitRequest>,
+    web_user: WebUser,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let libgit2_repo = repo.libgit2(&mut transaction).await?;
+    let query = RefQuery::parse(&request.q_string());
+
+    let (tags, more) = tag_page(&repo, &libgit2_repo, &query, &mut transaction).await?;
+
+    let mut context = Context::new();
+
+    context.insert_web_user(&web_user)?;
+    context.try_insert("repo_owner_name", uri.username.as_str())?;
+    context.try_insert("repo", &repo)?;
+    context.try_insert("tags", &tags)?;
+    context.try_insert("search", query.search.as_str())?;
+    context.try_insert("offset", &query.offset)?;
+    context.try_insert("page_size", &PAGE_SIZE)?;
+    context.try_insert("more", &more)?;
+
+    render_template!("repo/tags.html", context, transaction)
+}
+
+#[route("/{username}/{repository}/tags/{tag:.*}", method = "GET", err = "html")]
+pub(crate) async fn view_tag(
+    repo: Repository,
+    uri: web::Path<ReleaseRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let libgit2_repo = repo.libgit2(&mut transaction).await?;
+
+    let tag = match find_tag_entry(&repo, &libgit2_repo, uri.tag.as_str(), &mut transaction).await?
+    {
+        Some(tag) => tag,
+        None => die!(NOT_FOUND, "Tag not found"),
+    };
+
+    // Drafts are only visible to people with push access, so they are not linked here
+    let release = Release::find(repo.id, uri.tag.as_str(), &mut transaction)
+        .await?
+        .filter(|release| !release.draft);
+
+    let mut context = Context::new();
+
+    context.insert_web_user(&web_user)?;
+    context.try_insert("repo_owner_name", uri.username.as_str())?;
+    context.try_insert("repo", &repo)?;
+    context.try_insert("tag", &tag)?;
+    context.try_insert("release", &release)?;
+
+    render_template!("repo/tag.html", context, transaction)
+}
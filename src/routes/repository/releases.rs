@@ -0,0 +1,267 @@
+use crate::markup::{render_markdown, MarkupContext};
+use crate::prelude::ContextExtensions;
+use crate::privileges::privilege;
+use crate::release::{Release, ReleaseAsset};
+use crate::repository::Repository;
+use crate::routes::repository::{GitRequest, ReleaseAssetRequest, ReleaseRequest};
+use crate::user::WebUser;
+use crate::{die, err, render_template};
+
+use actix_files::NamedFile;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType, LOCATION};
+use actix_web::mime::Mime;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use anyhow::{Context as _, Result};
+use gitarena_macros::{from_config, route};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use tera::Context;
+
+#[route("/{username}/{repository}/releases", method = "GET", err = "html")]
+pub(crate) async fn all_releases(
+    repo: Repository,
+    uri: web::Path<GitRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let can_push = privilege::check_push(&repo, web_user.as_ref(), &mut transaction).await?;
+    let releases = Release::all(repo.id, can_push, &mut transaction).await?;
+    let latest = Release::latest(repo.id, &mut transaction).await?;
+
+    let mut entries = Vec::<ReleaseEntry>::with_capacity(releases.len());
+
+    for release in releases {
+        let is_latest = latest
+            .as_ref()
+            .map_or(false, |latest| latest.id == release.id);
+
+        entries.push(
+            ReleaseEntry::new(
+                release,
+                uri.username.as_str(),
+                &repo,
+                is_latest,
+                &mut transaction,
+            )
+            .await?,
+        );
+    }
+
+    let mut context = Context::new();
+
+    context.insert_web_user(&web_user)?;
+    context.try_insert("repo_owner_name", uri.username.as_str())?;
+    context.try_insert("repo", &repo)?;
+    context.try_insert("releases", &entries)?;
+
+    render_template!("repo/releases.html", context, transaction)
+}
+
+#[route(
+    "/{username}/{repository}/releases/tag/{tag}",
+    method = "GET",
+    err = "html"
+)]
+pub(crate) async fn view_release(
+    repo: Repository,
+    uri: web::Path<ReleaseRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let release =
+        find_visible_release(&repo, uri.tag.as_str(), &web_user, &mut transaction).await?;
+    let is_latest = Release::latest(repo.id, &mut transaction)
+        .await?
+        .map_or(false, |latest| latest.id == release.id);
+
+    let entry = ReleaseEntry::new(
+        release,
+        uri.username.as_str(),
+        &repo,
+        is_latest,
+        &mut transaction,
+    )
+    .await?;
+
+    let mut context = Context::new();
+
+    context.insert_web_user(&web_user)?;
+    context.try_insert("repo_owner_name", uri.username.as_str())?;
+    context.try_insert("repo", &repo)?;
+    context.try_insert("releases", &vec![entry])?;
+    context.try_insert("single", &true)?;
+
+    render_template!("repo/releases.html", context, transaction)
+}
+
+#[route(
+    "/{username}/{repository}/releases/latest",
+    method = "GET",
+    err = "html"
+)]
+pub(crate) async fn latest_release(
+    repo: Repository,
+    uri: web::Path<GitRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let release = Release::latest(repo.id, &mut transaction)
+        .await?
+        .ok_or_else(|| err!(NOT_FOUND, "No release published yet"))?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Found()
+        .append_header((
+            LOCATION,
+            format!(
+                "/{}/{}/releases/tag/{}",
+                uri.username, repo.name, release.tag
+            ),
+        ))
+        .finish())
+}
+
+/// Stable download URL for CI and install scripts, always pointing to the asset of the latest release
+#[route(
+    "/{username}/{repository}/releases/latest/download/{name}",
+    method = "GET",
+    err = "html"
+)]
+pub(crate) async fn latest_asset(
+    repo: Repository,
+    uri: web::Path<LatestAssetRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let mut transaction = db_pool.begin().await?;
+
+    let release = Release::latest(repo.id, &mut transaction)
+        .await?
+        .ok_or_else(|| err!(NOT_FOUND, "No release published yet"))?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Found()
+        .append_header((
+            LOCATION,
+            format!(
+                "/{}/{}/releases/download/{}/{}",
+                uri.username, repo.name, release.tag, uri.name
+            ),
+        ))
+        .finish())
+}
+
+#[route(
+    "/{username}/{repository}/releases/download/{tag}/{name}",
+    method = "GET",
+    err = "html"
+)]
+pub(crate) async fn download_asset(
+    repo: Repository,
+    uri: web::Path<ReleaseAssetRequest>,
+    web_user: WebUser,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let releases_dir: String = from_config!("releases.dir" => String);
+
+    let mut transaction = db_pool.begin().await?;
+
+    let release =
+        find_visible_release(&repo, uri.tag.as_str(), &web_user, &mut transaction).await?;
+    let asset = ReleaseAsset::find(release.id, uri.name.as_str(), &mut transaction)
+        .await?
+        .ok_or_else(|| err!(NOT_FOUND, "Asset not found"))?;
+
+    let file = NamedFile::open_async(asset.fs_path(releases_dir.as_str()))
+        .await
+        .with_context(|| format!("Failed to open release asset {}", asset.id))?;
+
+    let content_type = asset
+        .content_type
+        .parse::<Mime>()
+        .unwrap_or(actix_web::mime::APPLICATION_OCTET_STREAM);
+
+    sqlx::query("update release_assets set downloads = downloads + 1 where id = $1")
+        .bind(asset.id)
+        .execute(&mut transaction)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(file
+        .set_content_type(content_type)
+        .set_content_disposition(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(asset.name)],
+        })
+        .into_response(&request))
+}
+
+/// Looks up a release by tag. Drafts are only returned if the user is allowed to push to the repository
+pub(crate) async fn find_visible_release(
+    repo: &Repository,
+    tag: &str,
+    web_user: &WebUser,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Release> {
+    let release = Release::find(repo.id, tag, &mut *transaction)
+        .await?
+        .ok_or_else(|| err!(NOT_FOUND, "Release not found"))?;
+
+    if release.draft && !privilege::check_push(repo, web_user.as_ref(), &mut *transaction).await? {
+        die!(NOT_FOUND, "Release not found");
+    }
+
+    Ok(release)
+}
+
+#[derive(Serialize)]
+struct ReleaseEntry {
+    release: Release,
+    author_name: String,
+    html: String,
+    assets: Vec<ReleaseAsset>,
+    latest: bool,
+}
+
+impl ReleaseEntry {
+    async fn new(
+        release: Release,
+        owner: &str,
+        repo: &Repository,
+        latest: bool,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<ReleaseEntry> {
+        let (author_name,): (String,) =
+            sqlx::query_as("select username from users where id = $1 limit 1")
+                .bind(release.author)
+                .fetch_one(&mut *transaction)
+                .await?;
+
+        let markup_context =
+            MarkupContext::new(owner, repo.name.as_str(), release.tag.as_str(), "");
+        let html = render_markdown(release.notes.as_str(), &markup_context);
+        let assets = release.assets(&mut *transaction).await?;
+
+        Ok(ReleaseEntry {
+            release,
+            author_name,
+            html,
+            assets,
+            latest,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct LatestAssetRequest {
+    username: String,
+    name: String,
+}
@@ -1,28 +1,29 @@
 use crate::git::history::{
-    all_branches, all_commits, all_tags, last_commit_for_blob, last_commit_for_ref,
+    all_branches, all_commits, all_tags, last_commit_for_ref, resolve_revision,
 };
-use crate::git::utils::{read_blob_content, repo_files_at_ref};
-use crate::git::GIT_HASH_KIND;
-use crate::prelude::{ContextExtensions, LibGit2SignatureExtensions};
+use crate::git::utils::repo_files_at_commit;
+use crate::issue::Issue;
+use crate::languages::repo_languages;
+use crate::prelude::{
+    link_co_authors, ContextExtensions, LibGit2CommitExtensions, LibGit2SignatureExtensions,
+};
+use crate::privileges::privilege;
+use crate::release::Release;
 use crate::repository::{RepoOwner, Repository};
+use crate::routes::repository::blobs::listing::list_files;
 use crate::routes::repository::GitTreeRequest;
-use crate::templates::web::{GitCommit, RepoFile};
+use crate::signatures::verify_commits;
+use crate::templates::web::GitCommit;
 use crate::user::WebUser;
+use crate::utils::oid;
 use crate::{die, err, render_template};
 
-use std::cmp::Ordering;
-
 use actix_web::{web, HttpMessage, HttpRequest, Responder};
 use anyhow::{anyhow, Result};
-use bstr::ByteSlice;
-use git_repository::hash::ObjectId;
-use git_repository::objs::tree::EntryMode;
 use git_repository::objs::Tree;
-use git_repository::refs::file::find::existing::Error as GitoxideFindError;
 use gitarena_macros::route;
 use sqlx::{PgPool, Postgres, Transaction};
 use tera::Context;
-use tracing_unwrap::OptionExt;
 
 async fn render(
     tree_option: Option<&str>,
@@ -36,30 +37,40 @@ async fn render(
     let mut context = Context::new();
 
     let libgit2_repo = repo.libgit2(&mut transaction).await?;
+    let mailmap = libgit2_repo.mailmap().ok();
     let gitoxide_repo = repo.gitoxide(&mut transaction).await?;
 
-    let (issues_count,): (i64,) = sqlx::query_as(
-        "select count(*) from issues where repo = $1 and closed = false and confidential = false",
+    let can_manage_issues =
+        privilege::check_manage_issues(&repo, web_user.as_ref(), &mut transaction).await?;
+    let issues_count = Issue::count_open(
+        repo.id,
+        web_user.as_ref(),
+        can_manage_issues,
+        &mut transaction,
     )
-    .bind(repo.id)
-    .fetch_one(&mut transaction)
     .await?;
 
     context.try_insert("repo", &repo)?;
     context.try_insert("repo_owner_name", &username)?;
     context.try_insert("issues_count", &issues_count)?;
     context.try_insert("merge_requests_count", &0_i32)?;
-    context.try_insert("releases_count", &0_i32)?;
+    context.try_insert(
+        "releases_count",
+        &Release::count(repo.id, &mut transaction).await?,
+    )?;
     context.try_insert("tree", tree_name)?;
     context.try_insert("branches", &all_branches(&libgit2_repo).await?)?;
     context.try_insert("tags", &all_tags(&libgit2_repo, None).await?)?;
     context.try_insert("repo_size", &repo.repo_size(&mut transaction).await?)?;
+    context.try_insert(
+        "languages",
+        &repo_languages(repo.id, &mut transaction).await?,
+    )?;
     context.insert_web_user(&web_user)?;
 
-    let loose_ref = match gitoxide_repo.refs.find_loose(tree_name) {
-        Ok(loose_ref) => Ok(loose_ref),
-        Err(GitoxideFindError::Find(err)) => Err(err),
-        Err(GitoxideFindError::NotFound(_)) => {
+    let (reference, commit_oid) = match resolve_revision(&libgit2_repo, tree_name).await {
+        Some(resolved) => resolved,
+        None => {
             if tree_name == repo.default_branch {
                 context.try_insert("files", &Vec::<()>::new())?;
 
@@ -68,79 +79,31 @@ async fn render(
                 die!(NOT_FOUND, "Not found")
             }
         }
-    }?; // Handle 404
+    }; // Handle 404
 
-    let full_tree_name = loose_ref.name.as_bstr().to_str()?;
+    let revision = commit_oid.to_string();
+    let full_tree_name = revision.as_str();
 
-    context.try_insert("full_tree", full_tree_name)?;
+    context.try_insert("full_tree", reference.as_deref().unwrap_or(full_tree_name))?;
 
     let mut buffer = Vec::<u8>::new();
     let store = gitoxide_repo.objects.clone();
 
-    let tree = repo_files_at_ref(&loose_ref, store.clone(), &gitoxide_repo, &mut buffer).await?;
+    let commit_id = oid::from_hex_str(Some(full_tree_name))?;
+    let tree = repo_files_at_commit(&commit_id, store.clone(), &mut buffer).await?;
     let tree = Tree::from(tree);
 
-    let mut files = Vec::<RepoFile>::with_capacity(tree.entries.len().min(1000));
-
-    for entry in tree.entries.iter().take(1000) {
-        let name = entry.filename.to_str().unwrap_or("Invalid file name");
-
-        let oid = last_commit_for_blob(&libgit2_repo, full_tree_name, name)
-            .await?
-            .unwrap_or_log();
-        let commit = libgit2_repo.find_commit(oid)?;
-
-        let submodule_target_oid = if matches!(entry.mode, EntryMode::Commit) {
-            Some(
-                read_blob_content(entry.oid.as_ref(), store.clone())
-                    .await
-                    .unwrap_or_else(|_| ObjectId::null(GIT_HASH_KIND).to_string()),
-            )
-        } else {
-            None
-        };
-
-        files.push(RepoFile {
-            file_type: entry.mode as u16,
-            file_name: name,
-            submodule_target_oid,
-            commit: GitCommit {
-                oid: format!("{}", oid),
-                message: commit.message().unwrap_or_default().to_owned(),
-                time: commit.time().seconds(),
-                date: None,
-                author_name: String::new(),  // Unused for file listing
-                author_uid: None,            // Unused for file listing
-                author_email: String::new(), // Unused for file listing
-            },
-        });
-    }
-
-    files.sort_by(|lhs, rhs| {
-        // 1. Directory
-        // 2. Submodules
-        // 3. Rest
-
-        if lhs.file_type == EntryMode::Tree as u16 && rhs.file_type != EntryMode::Tree as u16 {
-            Ordering::Less
-        } else if lhs.file_type != EntryMode::Tree as u16 && rhs.file_type == EntryMode::Tree as u16
-        {
-            Ordering::Greater
-        } else if lhs.file_type == EntryMode::Tree as u16 && rhs.file_type == EntryMode::Tree as u16
-        {
-            lhs.file_name.cmp(rhs.file_name)
-        } else if lhs.file_type == EntryMode::Commit as u16
-            && rhs.file_type != EntryMode::Commit as u16
-        {
-            Ordering::Less
-        } else if lhs.file_type != EntryMode::Commit as u16
-            && rhs.file_type == EntryMode::Commit as u16
-        {
-            Ordering::Greater
-        } else {
-            lhs.file_name.cmp(rhs.file_name)
-        }
-    });
+    let (files, pending) = list_files(
+        &repo,
+        &libgit2_repo,
+        store.clone(),
+        &tree,
+        "",
+        full_tree_name,
+        false,
+        &mut transaction,
+    )
+    .await?;
 
     if let Some(fork_repo_id) = repo.forked_from {
         const QUERY: &str = "select users.username, repositories.name from repositories \
@@ -159,6 +122,7 @@ async fn render(
     }
 
     context.try_insert("files", &files)?;
+    context.try_insert("pending", &pending)?;
     context.try_insert(
         "commits_count",
         &all_commits(&libgit2_repo, full_tree_name, 0).await?.len(),
@@ -169,23 +133,33 @@ async fn render(
         .ok_or_else(|| err!(OK, "Repository is empty"))?;
     let last_commit = libgit2_repo.find_commit(last_commit_oid)?;
 
-    // TODO: Additionally show last_commit.committer and if doesn't match with author
-    let (author_name, author_uid, author_email) =
-        last_commit.author().try_disassemble(&mut transaction).await;
-
+    let mut signatures =
+        verify_commits(&repo, &libgit2_repo, &[last_commit_oid], &mut transaction).await?;
     context.try_insert(
-        "last_commit",
-        &GitCommit {
-            oid: format!("{}", last_commit_oid),
-            message: last_commit.message().unwrap_or_default().to_owned(),
-            time: last_commit.time().seconds(),
-            date: None,
-            author_name,
-            author_uid,
-            author_email,
-        },
+        "last_commit_signature",
+        &signatures.remove(&last_commit_oid.to_string()),
     )?;
 
+    // TODO: Additionally show last_commit.committer and if doesn't match with author
+    let (author_name, author_uid, author_email) = last_commit
+        .author()
+        .try_disassemble(mailmap.as_ref(), &mut transaction)
+        .await;
+
+    let mut git_commit = GitCommit {
+        oid: format!("{}", last_commit_oid),
+        message: last_commit.message().unwrap_or_default().to_owned(),
+        time: last_commit.time().seconds(),
+        date: None,
+        author_name,
+        author_uid,
+        author_email,
+        co_authors: last_commit.co_authors(mailmap.as_ref()),
+    };
+
+    link_co_authors(Some(&mut git_commit), &mut transaction).await;
+    context.try_insert("last_commit", &git_commit)?;
+
     render_template!("repo/index.html", context, transaction)
 }
 
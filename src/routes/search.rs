@@ -0,0 +1,212 @@
+use crate::prelude::{ContextExtensions, HttpRequestExtensions};
+use crate::privileges::privilege;
+use crate::repository::Repository;
+use crate::search::{search_files, SearchCandidate, SearchQuery};
+use crate::user::WebUser;
+use crate::{err, render_template};
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use actix_web::{web, HttpRequest, Responder};
+use anyhow::{Error, Result};
+use gitarena_macros::route;
+use regex::Regex;
+use serde::Serialize;
+use sqlx::PgPool;
+use tera::Context;
+
+/// Maximum amount of matching lines displayed per file
+const MAX_LINES_PER_FILE: usize = 5;
+
+/// Amount of files displayed per page
+const PAGE_SIZE: u32 = 20;
+
+/// Postgres error code for `invalid_regular_expression`
+const INVALID_REGULAR_EXPRESSION: &str = "2201B";
+
+/// Postgres error code for `query_canceled`, raised once the search timeout is reached
+const QUERY_CANCELED: &str = "57014";
+
+#[route("/search", method = "GET", err = "html")]
+pub(crate) async fn search(
+    web_user: WebUser,
+    request: HttpRequest,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let query_string = request.q_string();
+
+    let input = query_string.get("q").unwrap_or_default().trim();
+    let query = SearchQuery::parse(
+        input,
+        query_string.get("regex") == Some("1"),
+        query_string.get("case") == Some("1"),
+    );
+    let offset = query_string
+        .get("offset")
+        .map_or_else(|| 0, |value| value.parse::<u32>().unwrap_or(0));
+
+    let mut transaction = db_pool.begin().await?;
+    let mut context = Context::new();
+
+    context.insert_web_user(&web_user)?;
+    context.try_insert("input", input)?;
+    context.try_insert("query", &query)?;
+    context.try_insert("offset", &offset)?;
+
+    if query.term.is_empty() {
+        return render_template!("search.html", context, transaction);
+    }
+
+    let regex = query
+        .build_regex()
+        .map_err(|_| err!(BAD_REQUEST, "Invalid regular expression"))?;
+
+    let candidates = search_files(
+        &query,
+        web_user.as_ref(),
+        offset,
+        PAGE_SIZE,
+        &mut transaction,
+    )
+    .await
+    .map_err(|error| -> Error {
+        let code = match error.downcast_ref::<sqlx::Error>() {
+            Some(sqlx::Error::Database(db_error)) => db_error.code().map(Cow::into_owned),
+            _ => None,
+        };
+
+        match code.as_deref() {
+            // Postgres supports a slightly different regex flavor, so it may still reject the pattern
+            Some(INVALID_REGULAR_EXPRESSION) => {
+                err!(BAD_REQUEST, "Invalid regular expression").into()
+            }
+            Some(QUERY_CANCELED) => err!(
+                BAD_REQUEST,
+                "Search took too long, please use a more specific search term"
+            )
+            .into(),
+            _ => error,
+        }
+    })?;
+
+    let more = candidates.len() as u32 == PAGE_SIZE;
+
+    // The query already filters out most repositories, but `check_access` is the source of truth
+    let mut access = HashMap::<i32, bool>::new();
+    let mut results = Vec::<SearchResult>::with_capacity(candidates.len());
+
+    for candidate in candidates {
+        let allowed = match access.get(&candidate.repo) {
+            Some(allowed) => *allowed,
+            None => {
+                let repo =
+                    sqlx::query_as::<_, Repository>("select * from repositories where id = $1")
+                        .bind(candidate.repo)
+                        .fetch_one(&mut transaction)
+                        .await?;
+
+                let allowed =
+                    privilege::check_access(&repo, web_user.as_ref(), &mut transaction).await?;
+                access.insert(candidate.repo, allowed);
+
+                allowed
+            }
+        };
+
+        if allowed {
+            results.push(SearchResult::new(candidate, &regex));
+        }
+    }
+
+    context.try_insert("results", &results)?;
+    context.try_insert("more", &more)?;
+
+    render_template!("search.html", context, transaction)
+}
+
+#[derive(Serialize)]
+struct SearchResult {
+    owner_name: String,
+    repo_name: String,
+    branch: String,
+    path: String,
+    language: Option<String>,
+    matches: usize,
+    lines: Vec<SearchLine>,
+}
+
+impl SearchResult {
+    fn new(candidate: SearchCandidate, regex: &Regex) -> SearchResult {
+        let mut matches = 0;
+        let mut lines = Vec::<SearchLine>::new();
+
+        for (index, line) in candidate.content.lines().enumerate() {
+            if !regex.is_match(line) {
+                continue;
+            }
+
+            matches += 1;
+
+            if lines.len() < MAX_LINES_PER_FILE {
+                lines.push(SearchLine::new(index + 1, line, regex));
+            }
+        }
+
+        SearchResult {
+            owner_name: candidate.owner_name,
+            repo_name: candidate.repo_name,
+            branch: candidate.branch,
+            path: candidate.path,
+            language: candidate.language,
+            matches,
+            lines,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SearchLine {
+    number: usize,
+    segments: Vec<SearchSegment>,
+}
+
+impl SearchLine {
+    fn new(number: usize, line: &str, regex: &Regex) -> SearchLine {
+        let mut segments = Vec::<SearchSegment>::new();
+        let mut last = 0;
+
+        for found in regex.find_iter(line) {
+            if found.start() > last {
+                segments.push(SearchSegment {
+                    text: line[last..found.start()].to_owned(),
+                    matched: false,
+                });
+            }
+
+            if !found.as_str().is_empty() {
+                segments.push(SearchSegment {
+                    text: found.as_str().to_owned(),
+                    matched: true,
+                });
+            }
+
+            last = found.end();
+        }
+
+        if last < line.len() {
+            segments.push(SearchSegment {
+                text: line[last..].to_owned(),
+                matched: false,
+            });
+        }
+
+        SearchLine { number, segments }
+    }
+}
+
+#[derive(Serialize)]
+struct SearchSegment {
+    text: String,
+    matched: bool,
+}
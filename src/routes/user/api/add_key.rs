@@ -1,3 +1,4 @@
+use crate::signatures;
 use crate::ssh::SshKey;
 use crate::user::WebUser;
 use crate::{die, err};
@@ -64,16 +65,21 @@ pub(crate) async fn put_ssh_key(
         die!(CONFLICT, "SSH key already exists");
     }
 
-    let key = sqlx::query_as::<_, SshKey>("insert into ssh_keys (owner, title, fingerprint, algorithm, key, expires_at) values ($1, $2, $3, $4, $5, $6) returning *")
+    let key = sqlx::query_as::<_, SshKey>("insert into ssh_keys (owner, title, fingerprint, algorithm, key, expires_at, signing) values ($1, $2, $3, $4, $5, $6, $7) returning *")
         .bind(user.id)
         .bind(key_title)
         .bind(fingerprint.as_str())
         .bind(algorithm)
         .bind(public_key.data().as_slice())
         .bind(body.expiration_date)
+        .bind(body.signing)
         .fetch_one(&mut transaction)
         .await?;
 
+    if key.signing {
+        signatures::invalidate(user.id, &mut transaction).await?;
+    }
+
     transaction.commit().await?;
 
     debug!(
@@ -96,6 +102,8 @@ pub(crate) struct AddKeyJsonRequest {
     key: String,
     #[serde(default, with = "ts_seconds_option")]
     expiration_date: Option<DateTime<Utc>>,
+    #[serde(default)]
+    signing: bool,
 }
 
 #[derive(Serialize)]
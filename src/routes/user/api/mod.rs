@@ -1,7 +1,12 @@
 use actix_web::web::ServiceConfig;
 
 mod add_key;
+mod signing_keys;
 
 pub(crate) fn init(config: &mut ServiceConfig) {
     config.service(add_key::put_ssh_key);
+    config.service(signing_keys::patch_ssh_key);
+
+    config.service(signing_keys::put_gpg_key);
+    config.service(signing_keys::delete_gpg_key);
 }
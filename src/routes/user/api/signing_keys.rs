@@ -0,0 +1,150 @@
+use crate::signatures::{self, parse_public_key};
+use crate::user::WebUser;
+use crate::{die, err};
+
+use actix_web::{web, HttpResponse, Responder};
+use anyhow::Result;
+use gitarena_macros::route;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+#[route("/api/gpg-key", method = "PUT", err = "json")]
+pub(crate) async fn put_gpg_key(
+    body: web::Json<AddGpgKeyJsonRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let user = web_user.into_user()?;
+
+    if body.key.trim().is_empty() {
+        die!(BAD_REQUEST, "Key is not a valid argument");
+    }
+
+    let public_key = parse_public_key(body.key.as_str())
+        .await
+        .map_err(|err| err!(BAD_REQUEST, "Failed to parse GPG public key: {}", err))?;
+
+    let mut transaction = db_pool.begin().await?;
+
+    let (exists,): (bool,) =
+        sqlx::query_as("select exists(select 1 from gpg_keys where fingerprint = $1 limit 1)")
+            .bind(public_key.fingerprint.as_str())
+            .fetch_one(&mut transaction)
+            .await?;
+
+    if exists {
+        die!(CONFLICT, "GPG key already exists");
+    }
+
+    let (id,): (i32,) = sqlx::query_as(
+        "insert into gpg_keys (owner, key_id, fingerprint, subkey_ids, emails, key, created_at, expires_at) \
+        values ($1, $2, $3, $4, $5, $6, $7, $8) returning id",
+    )
+    .bind(user.id)
+    .bind(public_key.key_id.as_str())
+    .bind(public_key.fingerprint.as_str())
+    .bind(&public_key.subkey_ids)
+    .bind(&public_key.emails)
+    .bind(body.key.trim())
+    .bind(public_key.created_at)
+    .bind(public_key.expires_at)
+    .fetch_one(&mut transaction)
+    .await?;
+
+    signatures::invalidate(user.id, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    debug!(
+        "New GPG key added for user {}: {} (id {})",
+        &user.id, &public_key.fingerprint, id
+    );
+
+    Ok(HttpResponse::Created().json(AddGpgKeyJsonResponse {
+        id,
+        key_id: public_key.key_id,
+        fingerprint: public_key.fingerprint,
+        emails: public_key.emails,
+    }))
+}
+
+#[route("/api/gpg-key/{id}", method = "DELETE", err = "json")]
+pub(crate) async fn delete_gpg_key(
+    uri: web::Path<KeyRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let user = web_user.into_user()?;
+    let mut transaction = db_pool.begin().await?;
+
+    let deleted = sqlx::query("delete from gpg_keys where id = $1 and owner = $2")
+        .bind(uri.id)
+        .bind(user.id)
+        .execute(&mut transaction)
+        .await?
+        .rows_affected();
+
+    if deleted == 0 {
+        die!(NOT_FOUND, "GPG key not found");
+    }
+
+    signatures::invalidate(user.id, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Marks an existing SSH key as (or no longer as) key used for signing commits and tags
+#[route("/api/ssh-key/{id}", method = "PATCH", err = "json")]
+pub(crate) async fn patch_ssh_key(
+    uri: web::Path<KeyRequest>,
+    body: web::Json<UpdateSshKeyJsonRequest>,
+    web_user: WebUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<impl Responder> {
+    let user = web_user.into_user()?;
+    let mut transaction = db_pool.begin().await?;
+
+    let updated = sqlx::query("update ssh_keys set signing = $1 where id = $2 and owner = $3")
+        .bind(body.signing)
+        .bind(uri.id)
+        .bind(user.id)
+        .execute(&mut transaction)
+        .await?
+        .rows_affected();
+
+    if updated == 0 {
+        die!(NOT_FOUND, "SSH key not found");
+    }
+
+    signatures::invalidate(user.id, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct KeyRequest {
+    id: i32,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct AddGpgKeyJsonRequest {
+    key: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct AddGpgKeyJsonResponse {
+    id: i32,
+    key_id: String,
+    fingerprint: String,
+    emails: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct UpdateSshKeyJsonRequest {
+    signing: bool,
+}
@@ -1,4 +1,5 @@
 use crate::die;
+use crate::signatures;
 
 use actix_web::{web, Responder};
 use anyhow::Result;
@@ -40,6 +41,8 @@ pub(crate) async fn verify(
         .execute(&mut transaction)
         .await?;
 
+    signatures::invalidate(user_id, &mut transaction).await?;
+
     sqlx::query("delete from user_verifications where id = $1")
         .bind(row_id)
         .execute(&mut transaction)
@@ -0,0 +1,385 @@
+use crate::config::get_setting;
+use crate::git::utils::is_binary;
+use crate::highlighting::detect_language;
+use crate::repository::Repository;
+use crate::user::User;
+use crate::utils::repo_scheduler::RepoScheduler;
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use git2::{BranchType, ObjectType, Repository as Git2Repository, TreeWalkMode, TreeWalkResult};
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use sqlx::{Acquire, Executor, FromRow, PgPool, Postgres, Transaction};
+use tracing::instrument;
+
+/// Maximum amount of time a search query may take, as user supplied regular expressions can be arbitrarily expensive
+const SEARCH_TIMEOUT: &str = "5s";
+
+static INDEXING: Lazy<RepoScheduler> = Lazy::new(|| RepoScheduler::new("update code search index"));
+
+/// A file which changed since the last time the branch was indexed
+struct IndexedFile {
+    path: String,
+    blob: String,
+    language: String,
+    content: String,
+}
+
+/// Updates the code search index of a repository in the background.
+///
+/// Only files which changed since the last run are read from disk.
+/// If the repository is already being indexed, it gets indexed again after the current run finishes.
+pub(crate) fn schedule_index(db_pool: PgPool, repo_id: i32) {
+    INDEXING.schedule(db_pool, repo_id, |db_pool, repo_id| async move {
+        index_repository(&db_pool, repo_id).await
+    });
+}
+
+#[instrument(err, skip(db_pool))]
+async fn index_repository(db_pool: &PgPool, repo_id: i32) -> Result<()> {
+    let mut transaction = db_pool.begin().await?;
+
+    if !get_setting::<bool, _>("search.enabled", &mut transaction).await? {
+        return Ok(());
+    }
+
+    let all_branches = get_setting::<bool, _>("search.all_branches", &mut transaction).await?;
+    let max_file_size = get_setting::<i32, _>("search.max_file_size", &mut transaction).await?;
+
+    let repo = match sqlx::query_as::<_, Repository>("select * from repositories where id = $1")
+        .bind(repo_id)
+        .fetch_optional(&mut transaction)
+        .await?
+    {
+        Some(repo) => repo,
+        None => return Ok(()), // Repository got deleted in the meantime
+    };
+
+    let path = repo.get_fs_path(&mut transaction).await?;
+    transaction.commit().await?;
+
+    let default_branch = repo.default_branch.clone();
+    let branches = tokio::task::spawn_blocking(move || -> Result<Vec<String>> {
+        let git2_repo = Git2Repository::open(path)?;
+
+        if !all_branches {
+            return Ok(vec![default_branch]);
+        }
+
+        let mut branches = Vec::new();
+
+        for branch in git2_repo.branches(Some(BranchType::Local))? {
+            let (branch, _) = branch?;
+
+            if let Some(name) = branch.name()? {
+                branches.push(name.to_owned());
+            }
+        }
+
+        Ok(branches)
+    })
+    .await??;
+
+    for branch in branches.iter() {
+        index_branch(
+            db_pool,
+            &repo,
+            branch.as_str(),
+            max_file_size.max(0) as usize,
+        )
+        .await?;
+    }
+
+    // Remove branches which got deleted or are no longer indexed
+    sqlx::query("delete from code_search_files where repo = $1 and branch <> all($2)")
+        .bind(repo.id)
+        .bind(&branches)
+        .execute(db_pool)
+        .await?;
+
+    info!(
+        "Updated code search index for repo id {} ({} branches)",
+        repo.id,
+        branches.len()
+    );
+
+    Ok(())
+}
+
+async fn index_branch(
+    db_pool: &PgPool,
+    repo: &Repository,
+    branch: &str,
+    max_file_size: usize,
+) -> Result<()> {
+    let mut transaction = db_pool.begin().await?;
+
+    let indexed: HashMap<String, String> = sqlx::query_as::<_, (String, String)>(
+        "select path, blob from code_search_files where repo = $1 and branch = $2",
+    )
+    .bind(repo.id)
+    .bind(branch)
+    .fetch_all(&mut transaction)
+    .await?
+    .into_iter()
+    .collect();
+
+    let path = repo.get_fs_path(&mut transaction).await?;
+    let reference = format!("refs/heads/{}", branch);
+
+    let (present, changed) = tokio::task::spawn_blocking(move || {
+        changed_files(path.as_str(), reference.as_str(), &indexed, max_file_size)
+    })
+    .await??;
+
+    sqlx::query(
+        "delete from code_search_files where repo = $1 and branch = $2 and path <> all($3)",
+    )
+    .bind(repo.id)
+    .bind(branch)
+    .bind(&present)
+    .execute(&mut transaction)
+    .await?;
+
+    for file in changed {
+        // A single file which can't be stored should not prevent indexing the rest of the branch
+        let mut savepoint = transaction.begin().await?;
+
+        match upsert_file(repo.id, branch, &file, &mut savepoint).await {
+            Ok(()) => savepoint.commit().await?,
+            Err(err) => warn!(
+                "Skipping {} on branch {} of repo id {} in code search index: {}",
+                &file.path, branch, repo.id, err
+            ),
+        }
+    }
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+async fn upsert_file<'e, E: Executor<'e, Database = Postgres>>(
+    repo_id: i32,
+    branch: &str,
+    file: &IndexedFile,
+    executor: E,
+) -> Result<()> {
+    sqlx::query(
+        "insert into code_search_files (repo, branch, path, blob, language, content) values ($1, $2, $3, $4, $5, $6) \
+        on conflict (repo, branch, path) do update set blob = excluded.blob, language = excluded.language, content = excluded.content",
+    )
+    .bind(repo_id)
+    .bind(branch)
+    .bind(file.path.as_str())
+    .bind(file.blob.as_str())
+    .bind(file.language.as_str())
+    .bind(file.content.as_str())
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Walks the tree of `reference` and returns all indexable paths as well as the files which changed compared to `indexed`
+fn changed_files(
+    path: &str,
+    reference: &str,
+    indexed: &HashMap<String, String>,
+    max_file_size: usize,
+) -> Result<(Vec<String>, Vec<IndexedFile>)> {
+    let repo = Git2Repository::open(path)?;
+
+    let tree = match repo.find_reference(reference) {
+        Ok(reference) => reference.peel_to_tree()?,
+        Err(_) => return Ok((Vec::new(), Vec::new())), // Branch does not exist (yet)
+    };
+
+    let mut blobs = Vec::<(String, git2::Oid)>::new();
+
+    tree.walk(TreeWalkMode::PreOrder, |directory, entry| {
+        if entry.kind() == Some(ObjectType::Blob) {
+            if let Some(name) = entry.name() {
+                blobs.push((format!("{}{}", directory, name), entry.id()));
+            }
+        }
+
+        TreeWalkResult::Ok
+    })?;
+
+    let mut present = HashSet::<String>::with_capacity(blobs.len());
+    let mut changed = Vec::<IndexedFile>::new();
+
+    for (path, oid) in blobs {
+        let blob_id = format!("{}", oid);
+
+        if indexed.get(&path) == Some(&blob_id) {
+            present.insert(path);
+            continue;
+        }
+
+        let blob = repo.find_blob(oid)?;
+        let content = blob.content();
+
+        if content.len() > max_file_size || is_binary(content) {
+            continue;
+        }
+
+        let content = String::from_utf8_lossy(content).into_owned();
+        let language = detect_language(path.as_str(), content.as_str())
+            .name
+            .clone();
+
+        present.insert(path.clone());
+        changed.push(IndexedFile {
+            path,
+            blob: blob_id,
+            language,
+            content,
+        });
+    }
+
+    Ok((present.into_iter().collect(), changed))
+}
+
+/// Search query entered by the user. Qualifiers (`repo:`, `lang:`, `path:` and `branch:`) can be mixed into the search term.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct SearchQuery {
+    pub(crate) term: String,
+
+    pub(crate) repo: Option<String>, // owner/name
+    pub(crate) language: Option<String>,
+    pub(crate) path: Option<String>,
+    pub(crate) branch: Option<String>,
+
+    pub(crate) regex: bool,
+    pub(crate) case_sensitive: bool,
+}
+
+impl SearchQuery {
+    pub(crate) fn parse(input: &str, regex: bool, case_sensitive: bool) -> SearchQuery {
+        let mut query = SearchQuery {
+            regex,
+            case_sensitive,
+            ..Default::default()
+        };
+
+        let mut term = Vec::<&str>::new();
+
+        for token in input.split_whitespace() {
+            match token.split_once(':') {
+                Some(("repo", value)) if !value.is_empty() => query.repo = Some(value.to_owned()),
+                Some(("lang", value)) if !value.is_empty() => {
+                    query.language = Some(value.to_owned())
+                }
+                Some(("path", value)) if !value.is_empty() => query.path = Some(value.to_owned()),
+                Some(("branch", value)) if !value.is_empty() => {
+                    query.branch = Some(value.to_owned())
+                }
+                _ => term.push(token),
+            }
+        }
+
+        query.term = term.join(" ");
+        query
+    }
+
+    /// Returns the regular expression used to search. Literal searches get escaped.
+    pub(crate) fn pattern(&self) -> String {
+        if self.regex {
+            self.term.clone()
+        } else {
+            regex::escape(self.term.as_str())
+        }
+    }
+
+    /// Builds the regular expression used to find the matching lines in a file
+    pub(crate) fn build_regex(&self) -> Result<Regex> {
+        Ok(RegexBuilder::new(self.pattern().as_str())
+            .case_insensitive(!self.case_sensitive)
+            .size_limit(1 << 20)
+            .build()?)
+    }
+}
+
+#[derive(FromRow, Debug)]
+pub(crate) struct SearchCandidate {
+    pub(crate) repo: i32,
+    pub(crate) owner_name: String,
+    pub(crate) repo_name: String,
+    pub(crate) branch: String,
+    pub(crate) path: String,
+    pub(crate) language: Option<String>,
+    pub(crate) content: String,
+}
+
+/// Searches the index for files matching the query.
+///
+/// Repositories the user is definitely not allowed to see are filtered out in the query already,
+/// but callers still need to check the results with [check_access](crate::privileges::privilege::check_access).
+/// The query gets cancelled after [SEARCH_TIMEOUT] (Postgres error code `57014`).
+#[instrument(err, skip(transaction))]
+pub(crate) async fn search_files(
+    query: &SearchQuery,
+    user: Option<&User>,
+    offset: u32,
+    limit: u32,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Vec<SearchCandidate>> {
+    let operator = if query.case_sensitive { "~" } else { "~*" };
+
+    // Only the operator gets formatted into the query, everything else is bound
+    let sql = format!(
+        "select code_search_files.repo, \
+        users.username as owner_name, \
+        repositories.name as repo_name, \
+        code_search_files.branch, \
+        code_search_files.path, \
+        code_search_files.language, \
+        code_search_files.content \
+        from code_search_files \
+        inner join repositories on code_search_files.repo = repositories.id \
+        inner join users on repositories.owner = users.id \
+        where code_search_files.content {} $1 \
+        and ($2::varchar is null or lower(users.username || '/' || repositories.name) = lower($2)) \
+        and ($3::varchar is null or lower(code_search_files.language) = lower($3)) \
+        and ($4::varchar is null or strpos(lower(code_search_files.path), lower($4)) > 0) \
+        and (code_search_files.branch = coalesce($5, repositories.default_branch)) \
+        and (repositories.disabled = false or $7) \
+        and (repositories.visibility = 'public' \
+            or $7 \
+            or ($6::integer is not null and repositories.visibility = 'internal') \
+            or repositories.owner = $6 \
+            or exists(select 1 from privileges where privileges.user_id = $6 and privileges.repo_id = repositories.id)) \
+        order by repositories.id, code_search_files.path \
+        offset $8 limit $9",
+        operator
+    );
+
+    sqlx::query(format!("set local statement_timeout = '{}'", SEARCH_TIMEOUT).as_str())
+        .execute(&mut *transaction)
+        .await?;
+
+    let candidates = sqlx::query_as::<_, SearchCandidate>(sql.as_str())
+        .bind(query.pattern())
+        .bind(query.repo.as_deref())
+        .bind(query.language.as_deref())
+        .bind(query.path.as_deref())
+        .bind(query.branch.as_deref())
+        .bind(user.map(|user| user.id))
+        .bind(user.map_or(false, |user| user.admin))
+        .bind(offset as i64)
+        .bind(limit as i64)
+        .fetch_all(&mut *transaction)
+        .await?;
+
+    sqlx::query("set local statement_timeout = default")
+        .execute(&mut *transaction)
+        .await?;
+
+    Ok(candidates)
+}
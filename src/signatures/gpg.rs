@@ -0,0 +1,262 @@
+use crate::signatures::{is_verified_email, run, SignatureStatus, SignedObject, Verification};
+
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use sqlx::{Postgres, Transaction};
+use tempfile::Builder;
+use tokio::process::Command;
+
+/// Public key as listed by `gpg --with-colons`
+pub(crate) struct GpgPublicKey {
+    /// Long key id (last 16 hex characters of the fingerprint) of the primary key
+    pub(crate) key_id: String,
+    pub(crate) fingerprint: String,
+    pub(crate) subkey_ids: Vec<String>,
+    /// Lowercase emails of all user ids
+    pub(crate) emails: Vec<String>,
+    pub(crate) created_at: DateTime<Utc>,
+    pub(crate) expires_at: Option<DateTime<Utc>>,
+}
+
+/// Parses an armored public key without importing it anywhere
+pub(crate) async fn parse_public_key(armored: &str) -> Result<GpgPublicKey> {
+    let home = Builder::new().prefix("gitarena_gpg_").tempdir()?;
+
+    let mut command = gpg(home.path());
+    command.args([
+        "--with-colons",
+        "--fixed-list-mode",
+        "--import-options",
+        "show-only",
+        "--import",
+    ]);
+
+    let output = run(command, armored.as_bytes()).await?;
+
+    if !output.status.success() {
+        bail!("Invalid GPG public key");
+    }
+
+    let listing = String::from_utf8_lossy(&output.stdout);
+
+    let mut key: Option<GpgPublicKey> = None;
+    let mut in_subkey = false;
+
+    for line in listing.lines() {
+        let fields = line.split(':').collect::<Vec<_>>();
+        let field = |index: usize| fields.get(index).copied().unwrap_or_default();
+
+        match field(0) {
+            "pub" => {
+                if key.is_some() {
+                    bail!("Only a single public key can be added at once");
+                }
+
+                key = Some(GpgPublicKey {
+                    key_id: field(4).to_uppercase(),
+                    fingerprint: String::new(),
+                    subkey_ids: Vec::new(),
+                    emails: Vec::new(),
+                    created_at: parse_timestamp(field(5)).unwrap_or_else(Utc::now),
+                    expires_at: parse_timestamp(field(6)),
+                });
+                in_subkey = false;
+            }
+            "sub" => {
+                if let Some(key) = key.as_mut() {
+                    key.subkey_ids.push(field(4).to_uppercase());
+                }
+
+                in_subkey = true;
+            }
+            "fpr" if !in_subkey => {
+                if let Some(key) = key.as_mut() {
+                    key.fingerprint = field(9).to_uppercase();
+                }
+            }
+            "uid" => {
+                if let Some(key) = key.as_mut() {
+                    // Colons within user ids are escaped as \x3a
+                    let user_id = field(9).replace("\\x3a", ":");
+
+                    if let Some(email) = user_id
+                        .rsplit_once('<')
+                        .and_then(|(_, email)| email.strip_suffix('>'))
+                    {
+                        key.emails.push(email.to_lowercase());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let key = key.ok_or_else(|| anyhow!("No public key found"))?;
+
+    if key.fingerprint.is_empty() {
+        bail!("Public key does not have a fingerprint");
+    }
+
+    Ok(key)
+}
+
+pub(super) async fn verify(
+    signed: &SignedObject,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Verification> {
+    let home = Builder::new().prefix("gitarena_gpg_").tempdir()?;
+    let signature_path = home.path().join("signature.asc");
+
+    tokio::fs::write(&signature_path, &signed.signature).await?;
+
+    // Without any keys in the keyring gpg tells us which key created the signature
+    let status = verify_status(home.path(), &signature_path, &signed.signed_data).await?;
+
+    let key_id = match status_lines(status.as_str())
+        .find(|(keyword, _)| *keyword == "NO_PUBKEY" || *keyword == "ERRSIG")
+        .and_then(|(_, args)| args.first().map(|key_id| key_id.to_uppercase()))
+    {
+        Some(key_id) => key_id,
+        None => {
+            return Ok(Verification::new(
+                SignatureStatus::Unverified,
+                "gpg",
+                "",
+                None,
+                Some("Signature is malformed"),
+            ))
+        }
+    };
+
+    let key_option: Option<(i32, String, Vec<String>, String)> = sqlx::query_as(
+        "select gpg_keys.owner, users.username, gpg_keys.emails, gpg_keys.key from gpg_keys \
+        join users on users.id = gpg_keys.owner \
+        where gpg_keys.key_id = $1 or $1 = any(gpg_keys.subkey_ids) limit 1",
+    )
+    .bind(key_id.as_str())
+    .fetch_optional(&mut *transaction)
+    .await?;
+
+    let (owner, username, emails, armored) = match key_option {
+        Some(key) => key,
+        None => {
+            return Ok(Verification::new(
+                SignatureStatus::UnknownKey,
+                "gpg",
+                key_id.as_str(),
+                None,
+                None,
+            ))
+        }
+    };
+
+    let mut command = gpg(home.path());
+    command.arg("--import");
+
+    if !run(command, armored.as_bytes()).await?.status.success() {
+        bail!("Failed to import GPG key {} into temporary keyring", key_id);
+    }
+
+    let status = verify_status(home.path(), &signature_path, &signed.signed_data).await?;
+    let signer = Some((owner, username));
+
+    let unverified = |reason: &str| {
+        Ok(Verification::new(
+            SignatureStatus::Unverified,
+            "gpg",
+            key_id.as_str(),
+            signer.clone(),
+            Some(reason),
+        ))
+    };
+
+    // gpg reports good signatures of expired keys using `EXPKEYSIG` instead of `GOODSIG`
+    let mut expired_key = false;
+
+    for (keyword, _) in status_lines(status.as_str()) {
+        match keyword {
+            "GOODSIG" => {}
+            "EXPKEYSIG" => expired_key = true,
+            "BADSIG" => return unverified("Signature does not match the signed data"),
+            "EXPSIG" => return unverified("Signature expired"),
+            "REVKEYSIG" => return unverified("Key has been revoked"),
+            "ERRSIG" => return unverified("Signature could not be checked"),
+            _ => continue,
+        }
+    }
+
+    if !status_lines(status.as_str())
+        .any(|(keyword, _)| keyword == "GOODSIG" || keyword == "EXPKEYSIG")
+    {
+        return unverified("Signature could not be checked");
+    }
+
+    let email = signed.email.to_lowercase();
+
+    if !emails.contains(&email) {
+        return unverified("Committer email is not part of the key");
+    }
+
+    if !is_verified_email(owner, email.as_str(), &mut *transaction).await? {
+        return unverified("Committer email is not a verified email of the key owner");
+    }
+
+    if expired_key {
+        return Ok(Verification::new(
+            SignatureStatus::ExpiredKey,
+            "gpg",
+            key_id.as_str(),
+            signer,
+            Some("Key expired"),
+        ));
+    }
+
+    Ok(Verification::new(
+        SignatureStatus::Verified,
+        "gpg",
+        key_id.as_str(),
+        signer,
+        None,
+    ))
+}
+
+/// Returns the machine readable status output of `gpg --verify`
+async fn verify_status(home: &Path, signature_path: &Path, data: &[u8]) -> Result<String> {
+    let mut command = gpg(home);
+    command.args(["--status-fd", "1", "--verify"]);
+    command.arg(signature_path);
+    command.arg("-");
+
+    // Exit status is non-zero for every signature that isn't good, the status lines tell us why
+    let output = run(command, data).await?;
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Iterates over the status lines (`[GNUPG:] KEYWORD args...`) of gpg's status output
+fn status_lines(status: &str) -> impl Iterator<Item = (&str, Vec<&str>)> {
+    status.lines().filter_map(|line| {
+        let mut parts = line.strip_prefix("[GNUPG:] ")?.split(' ');
+        let keyword = parts.next()?;
+
+        Some((keyword, parts.collect()))
+    })
+}
+
+fn gpg(home: &Path) -> Command {
+    let mut command = Command::new("gpg");
+    command.arg("--homedir");
+    command.arg(home);
+    command.args(["--batch", "--no-tty", "--no-auto-key-retrieve"]);
+    command
+}
+
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    value
+        .parse::<i64>()
+        .ok()
+        .filter(|timestamp| *timestamp > 0)
+        .and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single())
+}
@@ -0,0 +1,367 @@
+use crate::repository::Repository;
+
+use std::collections::HashMap;
+use std::process::{Output, Stdio};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+use git2::{ErrorCode, Oid, Repository as Git2Repository, Sort};
+use serde::Serialize;
+use sqlx::{Executor, FromRow, Postgres, Transaction, Type};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::time::timeout;
+
+mod gpg;
+mod ssh;
+
+pub(crate) use gpg::parse_public_key;
+
+/// `gpg` and `ssh-keygen` get killed if they take longer than this
+const PROCESS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Armor headers of signatures appended to tag messages
+const TAG_SIGNATURE_HEADERS: &[&[u8]] = &[
+    b"\n-----BEGIN PGP SIGNATURE-----",
+    b"\n-----BEGIN SSH SIGNATURE-----",
+    b"\n-----BEGIN SIGNED MESSAGE-----",
+];
+
+#[derive(Type, Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[sqlx(type_name = "signature_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SignatureStatus {
+    /// Valid signature by a key of a user which has the committer (or tagger) email verified
+    Verified,
+    /// Same as verified, but the GPG key has expired since
+    ExpiredKey,
+    /// Signature is invalid, the key expired or the email does not belong to the key owner
+    Unverified,
+    /// Key is not registered by any user (or, for SSH keys, not marked as signing key)
+    UnknownKey,
+}
+
+#[derive(FromRow, Debug, Serialize)]
+pub(crate) struct Verification {
+    #[serde(skip)]
+    pub(crate) object: String,
+
+    pub(crate) status: SignatureStatus,
+    /// Either `gpg`, `ssh` or `x509`
+    pub(crate) kind: String,
+    /// Long key id for GPG keys, SHA256 fingerprint for SSH keys
+    pub(crate) key_id: String,
+
+    /// Owner of the key, if known
+    pub(crate) signer: Option<i32>,
+    pub(crate) signer_name: Option<String>,
+
+    /// Why the signature is not verified
+    pub(crate) reason: Option<String>,
+}
+
+impl Verification {
+    fn new(
+        status: SignatureStatus,
+        kind: &str,
+        key_id: &str,
+        signer: Option<(i32, String)>,
+        reason: Option<&str>,
+    ) -> Verification {
+        let (signer, signer_name) = signer.unzip();
+
+        Verification {
+            object: String::new(),
+            status,
+            kind: kind.to_owned(),
+            key_id: key_id.to_owned(),
+            signer,
+            signer_name,
+            reason: reason.map(str::to_owned),
+        }
+    }
+}
+
+/// Signature of a commit or tag alongside the data it signs
+pub(crate) struct SignedObject {
+    pub(crate) signature: Vec<u8>,
+    pub(crate) signed_data: Vec<u8>,
+
+    /// Committer or tagger email, which needs to be a verified email of the key owner
+    pub(crate) email: String,
+}
+
+/// Returns the signature of a commit or `None` if it isn't signed
+pub(crate) fn commit_signature(repo: &Git2Repository, oid: Oid) -> Result<Option<SignedObject>> {
+    let (signature, signed_data) = match repo.extract_signature(&oid, None) {
+        Ok(result) => result,
+        Err(err) if err.code() == ErrorCode::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+
+    let commit = repo.find_commit(oid)?;
+    let email = commit.committer().email().unwrap_or_default().to_owned();
+
+    Ok(Some(SignedObject {
+        signature: signature.to_vec(),
+        signed_data: signed_data.to_vec(),
+        email,
+    }))
+}
+
+/// Returns the signature of an annotated tag or `None` if it isn't signed
+pub(crate) fn tag_signature(repo: &Git2Repository, oid: Oid) -> Result<Option<SignedObject>> {
+    let tag = repo.find_tag(oid)?;
+    let email = tag
+        .tagger()
+        .and_then(|tagger| tagger.email().map(str::to_owned))
+        .unwrap_or_default();
+
+    let odb = repo.odb()?;
+    let object = odb.read(oid)?;
+    let data = object.data();
+
+    // In contrast to commits the signature is not a header but appended to the message
+    let position = TAG_SIGNATURE_HEADERS
+        .iter()
+        .filter_map(|header| data.rfind(header))
+        .max()
+        .map(|position| position + 1);
+
+    Ok(position.map(|position| SignedObject {
+        signature: data[position..].to_vec(),
+        signed_data: data[..position].to_vec(),
+        email,
+    }))
+}
+
+/// Verifies a signature against the registered keys of all users
+pub(crate) async fn verify(
+    signed: &SignedObject,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Verification> {
+    if signed
+        .signature
+        .starts_with(b"-----BEGIN SSH SIGNATURE-----")
+    {
+        ssh::verify(signed, transaction).await
+    } else if signed
+        .signature
+        .starts_with(b"-----BEGIN PGP SIGNATURE-----")
+    {
+        gpg::verify(signed, transaction).await
+    } else {
+        Ok(Verification::new(
+            SignatureStatus::Unverified,
+            "x509",
+            "",
+            None,
+            Some("X.509 signatures are not supported"),
+        ))
+    }
+}
+
+/// Returns the verification results of the signed commits out of `oids`, keyed by their oid.
+/// Unsigned commits are not part of the result.
+///
+/// Results are cached until keys or emails of a user change.
+pub(crate) async fn verify_commits(
+    repo: &Repository,
+    libgit2_repo: &Git2Repository,
+    oids: &[Oid],
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<HashMap<String, Verification>> {
+    let objects = oids.iter().map(|oid| oid.to_string()).collect::<Vec<_>>();
+    let mut results = cached_verifications(repo.id, &objects, &mut *transaction).await?;
+
+    for oid in oids {
+        let object = oid.to_string();
+
+        if results.contains_key(&object) {
+            continue;
+        }
+
+        if let Some(signed) = commit_signature(libgit2_repo, *oid)? {
+            let verification =
+                verify_and_store(repo.id, object.as_str(), &signed, transaction).await?;
+            results.insert(object, verification);
+        }
+    }
+
+    Ok(results)
+}
+
+/// Returns the verification result of an annotated tag or `None` if it isn't signed
+pub(crate) async fn verify_tag(
+    repo: &Repository,
+    libgit2_repo: &Git2Repository,
+    oid: Oid,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Option<Verification>> {
+    let object = oid.to_string();
+
+    let mut cached = cached_verifications(repo.id, &[object.clone()], &mut *transaction).await?;
+
+    if let Some(verification) = cached.remove(&object) {
+        return Ok(Some(verification));
+    }
+
+    match tag_signature(libgit2_repo, oid)? {
+        Some(signed) => Ok(Some(
+            verify_and_store(repo.id, object.as_str(), &signed, transaction).await?,
+        )),
+        None => Ok(None),
+    }
+}
+
+async fn cached_verifications<'e, E: Executor<'e, Database = Postgres>>(
+    repo_id: i32,
+    objects: &[String],
+    executor: E,
+) -> Result<HashMap<String, Verification>> {
+    let verifications = sqlx::query_as::<_, Verification>(
+        "select signature_verifications.*, users.username as signer_name from signature_verifications \
+        left join users on users.id = signature_verifications.signer \
+        where repo = $1 and object = any($2)",
+    )
+    .bind(repo_id)
+    .bind(objects)
+    .fetch_all(executor)
+    .await?;
+
+    Ok(verifications
+        .into_iter()
+        .map(|verification| (verification.object.clone(), verification))
+        .collect())
+}
+
+async fn verify_and_store(
+    repo_id: i32,
+    object: &str,
+    signed: &SignedObject,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Verification> {
+    let mut verification = verify(signed, transaction).await?;
+    verification.object = object.to_owned();
+
+    sqlx::query(
+        "insert into signature_verifications (repo, object, status, kind, key_id, signer, reason) \
+        values ($1, $2, $3, $4, $5, $6, $7) on conflict (repo, object) do update \
+        set status = excluded.status, kind = excluded.kind, key_id = excluded.key_id, \
+        signer = excluded.signer, reason = excluded.reason",
+    )
+    .bind(repo_id)
+    .bind(object)
+    .bind(verification.status)
+    .bind(verification.kind.as_str())
+    .bind(verification.key_id.as_str())
+    .bind(verification.signer)
+    .bind(verification.reason.as_deref())
+    .execute(&mut *transaction)
+    .await?;
+
+    Ok(verification)
+}
+
+/// Drops cached results which may change now that keys or verified emails of `user_id` changed
+pub(crate) async fn invalidate<'e, E: Executor<'e, Database = Postgres>>(
+    user_id: i32,
+    executor: E,
+) -> Result<()> {
+    sqlx::query("delete from signature_verifications where signer = $1 or status = 'unknown_key'")
+        .bind(user_id)
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+/// Returns the reason why a push updating a branch from `old` to `new` is rejected by the signed commits rule,
+/// or `None` if all commits introduced by the push carry a verified signature.
+///
+/// Commits which are already reachable from another branch have been accepted before and are not checked again.
+pub(crate) async fn check_signed_commits(
+    repo: &Repository,
+    libgit2_repo: &Git2Repository,
+    old: Option<&str>,
+    new: &str,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Option<String>> {
+    let oids = {
+        let mut rev_walk = libgit2_repo.revwalk()?;
+        rev_walk.set_sorting(Sort::TOPOLOGICAL)?;
+        rev_walk.push(Oid::from_str(new)?)?;
+        rev_walk.hide_glob("refs/heads/*")?;
+
+        if let Some(old) = old {
+            rev_walk.hide(Oid::from_str(old)?)?;
+        }
+
+        rev_walk.collect::<Result<Vec<_>, _>>()?
+    };
+
+    let verifications = verify_commits(repo, libgit2_repo, oids.as_slice(), transaction).await?;
+
+    for oid in oids {
+        let object = oid.to_string();
+        let short = &object[..7];
+
+        match verifications.get(&object) {
+            Some(verification) if verification.status == SignatureStatus::Verified => {}
+            Some(verification) if verification.status == SignatureStatus::ExpiredKey => {
+                return Ok(Some(format!(
+                    "commit {} is signed with an expired key",
+                    short
+                )))
+            }
+            Some(_) => {
+                return Ok(Some(format!(
+                    "commit {} does not have a verified signature",
+                    short
+                )))
+            }
+            None => return Ok(Some(format!("commit {} is not signed", short))),
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns whether `email` is a verified email of `user_id`
+async fn is_verified_email<'e, E: Executor<'e, Database = Postgres>>(
+    user_id: i32,
+    email: &str,
+    executor: E,
+) -> Result<bool> {
+    let (verified,): (bool,) = sqlx::query_as(
+        "select exists(select 1 from emails where owner = $1 and lower(email) = lower($2) and verified_at is not null)",
+    )
+    .bind(user_id)
+    .bind(email)
+    .fetch_one(executor)
+    .await?;
+
+    Ok(verified)
+}
+
+/// Runs `command` with `stdin` piped into it and waits for it to exit
+async fn run(mut command: Command, stdin: &[u8]) -> Result<Output> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let mut child_stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("Failed to open stdin of child process"))?;
+
+    // The process may exit before reading all input (e.g. malformed signatures), its exit status tells us why
+    let _ = child_stdin.write_all(stdin).await;
+    drop(child_stdin);
+
+    Ok(timeout(PROCESS_TIMEOUT, child.wait_with_output()).await??)
+}
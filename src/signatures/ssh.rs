@@ -0,0 +1,143 @@
+use crate::signatures::{is_verified_email, run, SignatureStatus, SignedObject, Verification};
+
+use std::convert::TryInto;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::{Postgres, Transaction};
+use tempfile::Builder;
+use tokio::process::Command;
+
+/// Magic preamble of the `SSHSIG` signature format (see PROTOCOL.sshsig of OpenSSH)
+const MAGIC_PREAMBLE: &[u8] = b"SSHSIG";
+
+/// Namespace git uses when signing commits and tags
+const NAMESPACE: &str = "git";
+
+pub(super) async fn verify(
+    signed: &SignedObject,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Verification> {
+    let public_key = match embedded_public_key(signed.signature.as_slice()) {
+        Ok(public_key) => public_key,
+        Err(_) => {
+            return Ok(Verification::new(
+                SignatureStatus::Unverified,
+                "ssh",
+                "",
+                None,
+                Some("Signature is malformed"),
+            ))
+        }
+    };
+
+    let fingerprint = format!(
+        "SHA256:{}",
+        base64::encode_config(Sha256::digest(&public_key), base64::STANDARD_NO_PAD)
+    );
+
+    let key_option: Option<(i32, String, Option<DateTime<Utc>>)> = sqlx::query_as(
+        "select ssh_keys.owner, users.username, ssh_keys.expires_at from ssh_keys \
+        join users on users.id = ssh_keys.owner \
+        where ssh_keys.key = $1 and ssh_keys.signing limit 1",
+    )
+    .bind(public_key.as_slice())
+    .fetch_optional(&mut *transaction)
+    .await?;
+
+    let (owner, username, expires_at) = match key_option {
+        Some(key) => key,
+        None => {
+            return Ok(Verification::new(
+                SignatureStatus::UnknownKey,
+                "ssh",
+                fingerprint.as_str(),
+                None,
+                None,
+            ))
+        }
+    };
+
+    let signer = Some((owner, username));
+
+    let unverified = |reason: &str| {
+        Ok(Verification::new(
+            SignatureStatus::Unverified,
+            "ssh",
+            fingerprint.as_str(),
+            signer.clone(),
+            Some(reason),
+        ))
+    };
+
+    if expires_at.map_or(false, |expires_at| expires_at < Utc::now()) {
+        return unverified("Key expired");
+    }
+
+    // The public key embedded into the signature is the registered one, so there is no need for an allowed signers file
+    let temp_dir = Builder::new().prefix("gitarena_ssh_").tempdir()?;
+    let signature_path = temp_dir.path().join("signature.sig");
+
+    tokio::fs::write(&signature_path, &signed.signature).await?;
+
+    let mut command = Command::new("ssh-keygen");
+    command.args(["-Y", "check-novalidate", "-n", NAMESPACE, "-s"]);
+    command.arg(&signature_path);
+
+    if !run(command, signed.signed_data.as_slice())
+        .await?
+        .status
+        .success()
+    {
+        return unverified("Signature does not match the signed data");
+    }
+
+    if !is_verified_email(owner, signed.email.as_str(), &mut *transaction).await? {
+        return unverified("Committer email is not a verified email of the key owner");
+    }
+
+    Ok(Verification::new(
+        SignatureStatus::Verified,
+        "ssh",
+        fingerprint.as_str(),
+        signer,
+        None,
+    ))
+}
+
+/// Returns the public key (in wire format, same as stored in `ssh_keys.key`) which created an armored signature
+fn embedded_public_key(armored: &[u8]) -> Result<Vec<u8>> {
+    let armored = std::str::from_utf8(armored)?;
+    let encoded = armored
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect::<String>();
+
+    let blob = base64::decode(encoded.trim())?;
+
+    let remaining = blob
+        .strip_prefix(MAGIC_PREAMBLE)
+        .ok_or_else(|| anyhow!("Signature does not start with magic preamble"))?;
+    let remaining = remaining
+        .get(4..) // Version
+        .ok_or_else(|| anyhow!("Signature is truncated"))?;
+
+    let (public_key, _) = read_string(remaining)?;
+
+    Ok(public_key.to_vec())
+}
+
+/// Reads a length prefixed string as defined in RFC 4251
+fn read_string(data: &[u8]) -> Result<(&[u8], &[u8])> {
+    let length = data
+        .get(..4)
+        .ok_or_else(|| anyhow!("Signature is truncated"))?;
+    let length = u32::from_be_bytes(length.try_into()?) as usize;
+
+    let string = data
+        .get(4..4 + length)
+        .ok_or_else(|| anyhow!("Signature is truncated"))?;
+
+    Ok((string, &data[4 + length..]))
+}
@@ -14,6 +14,8 @@ pub(crate) struct SshKey {
     pub(crate) fingerprint: String,
     pub(crate) algorithm: KeyType,
     key: Vec<u8>,
+    /// Whether the key is used to verify commit and tag signatures
+    pub(crate) signing: bool,
     pub(crate) created_at: DateTime<Utc>,
     pub(crate) expires_at: Option<DateTime<Utc>>,
 }
@@ -1,3 +1,5 @@
+use crate::languages;
+
 use std::collections::HashMap;
 
 use chrono::{DateTime, NaiveDateTime, Utc};
@@ -27,3 +29,9 @@ pub(crate) fn human_time(value: &Value, _: &HashMap<String, Value>) -> Result<Va
 
     Ok(Value::String(format!("{}", human_time)))
 }
+
+pub(crate) fn language_color(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
+    let input = value.as_str().ok_or("Value needs to be a string")?;
+
+    Ok(Value::String(languages::language_color(input)))
+}
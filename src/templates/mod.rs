@@ -130,6 +130,7 @@ fn init_tera() -> GlobalTera {
 
     tera.register_filter("human_prefix", filters::human_prefix);
     tera.register_filter("human_time", filters::human_time);
+    tera.register_filter("language_color", filters::language_color);
 
     tera.register_tester("empty", tests::empty);
     tera.register_tester("none", tests::none);
@@ -5,7 +5,8 @@ use serde::Serialize;
 pub(crate) struct RepoFile<'a> {
     pub(crate) file_type: u16,
     pub(crate) file_name: &'a str,
-    pub(crate) commit: GitCommit,
+    /// `None` if the last commit is not known yet and gets loaded by the listing later on
+    pub(crate) commit: Option<GitCommit>,
     pub(crate) submodule_target_oid: Option<String>,
 }
 
@@ -15,6 +16,15 @@ pub(crate) struct RepoReadme<'a> {
     pub(crate) content: &'a str,
 }
 
+#[derive(Serialize)]
+pub(crate) struct RepoBlameHunk<'a> {
+    pub(crate) commit: String, // Key into the `commits` map of the blame view
+    pub(crate) start_line: usize,
+    pub(crate) lines: Vec<&'a str>,
+    pub(crate) orig_path: &'a str,
+    pub(crate) has_parent: bool,
+}
+
 #[derive(Serialize)]
 pub(crate) struct GitCommit {
     pub(crate) oid: String,
@@ -27,4 +37,15 @@ pub(crate) struct GitCommit {
     pub(crate) author_name: String,
     pub(crate) author_uid: Option<i32>,
     pub(crate) author_email: String,
+
+    /// Authors listed in `Co-authored-by` trailers
+    pub(crate) co_authors: Vec<CommitAuthor>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct CommitAuthor {
+    /// Username if linked to an user, otherwise the name of the trailer
+    pub(crate) name: String,
+    pub(crate) uid: Option<i32>,
+    pub(crate) email: String,
 }
@@ -51,14 +51,15 @@ impl User {
         user
     }
 
-    pub(crate) async fn find_using_email<'e, E, S>(email: S, executor: E) -> Option<User>
+    /// Returns the user who has `email` verified
+    pub(crate) async fn find_using_verified_email<'e, E, S>(email: S, executor: E) -> Option<User>
     where
         E: Executor<'e, Database = Postgres>,
         S: AsRef<str>,
     {
         let email = email.as_ref();
 
-        let user = sqlx::query_as::<_, User>("select * from users where id = (select owner from emails where lower(email) = lower($1) limit 1) limit 1")
+        let user = sqlx::query_as::<_, User>("select * from users where id = (select owner from emails where lower(email) = lower($1) and verified_at is not null limit 1) limit 1")
             .bind(email)
             .fetch_optional(executor)
             .await
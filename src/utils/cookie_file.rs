@@ -42,3 +42,16 @@ impl CookieExtensions for Cookie {
         })
     }
 }
+
+/// libmagic cookie opened with `MIME_TYPE | MIME_ENCODING`, used to find the `Content-Type` of raw blobs.
+/// Wrapped in its own type so it can be registered as app data next to the description cookie.
+pub(crate) struct MimeCookie(pub(crate) Cookie);
+
+impl MimeCookie {
+    /// Returns the mime type including charset, for example `text/plain; charset=us-ascii`
+    pub(crate) fn mime_type(&self, buffer: &[u8]) -> Result<String> {
+        self.0
+            .buffer(buffer)
+            .context("Failed to find mime type of buffer")
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use log::warn;
+use sqlx::PgPool;
+use tracing_unwrap::ResultExt;
+
+/// Runs a background job at most once at a time per repository.
+///
+/// If a job gets scheduled while it is already running for the same repository, it runs again once the current run
+/// finishes. Any amount of schedules during a run result in only one additional run.
+pub(crate) struct RepoScheduler {
+    /// Used in the warning logged if the job fails, e.g. `update language statistics`
+    description: &'static str,
+
+    /// Repositories the job is currently running for. The value is true if the job was scheduled again in the meantime
+    running: Mutex<HashMap<i32, bool>>,
+}
+
+impl RepoScheduler {
+    pub(crate) fn new(description: &'static str) -> RepoScheduler {
+        RepoScheduler {
+            description,
+            running: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn schedule<F, T>(&'static self, db_pool: PgPool, repo_id: i32, job: F)
+    where
+        F: Fn(PgPool, i32) -> T + Send + 'static,
+        T: Future<Output = Result<()>> + Send + 'static,
+    {
+        {
+            let mut running = self.running.lock().unwrap_or_log();
+
+            if let Some(rerun) = running.get_mut(&repo_id) {
+                *rerun = true;
+                return;
+            }
+
+            running.insert(repo_id, false);
+        }
+
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = job(db_pool.clone(), repo_id).await {
+                    warn!(
+                        "Failed to {} for repo id {}: {}",
+                        self.description, repo_id, err
+                    );
+                }
+
+                let rerun = {
+                    let mut running = self.running.lock().unwrap_or_log();
+
+                    if running.get(&repo_id) == Some(&true) {
+                        running.insert(repo_id, false);
+                        true
+                    } else {
+                        running.remove(&repo_id);
+                        false
+                    }
+                };
+
+                if !rerun {
+                    break;
+                }
+            }
+        });
+    }
+}
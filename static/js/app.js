@@ -61,6 +61,70 @@ document.addEventListener("DOMContentLoaded", () => {
     });
 });
 
+/**
+ * Highlights the lines referenced by the location hash (`#L10` or `#L10-L20`) and allows
+ * selecting line ranges by shift-clicking line numbers
+ */
+function initLineHighlighting() {
+    const highlightLines = () => {
+        $("tr.line.highlighted").removeClass("highlighted");
+
+        const match = /^#L(\d+)(?:-L(\d+))?$/.exec(window.location.hash);
+
+        if (!match) {
+            return;
+        }
+
+        const first = parseInt(match[1]);
+        const last = match[2] ? parseInt(match[2]) : first;
+
+        for (let i = Math.min(first, last); i <= Math.max(first, last); i++) {
+            $(`#L${i}`).addClass("highlighted");
+        }
+    };
+
+    $("td.line.number a").click(function (event) {
+        const match = /^#L(\d+)/.exec(window.location.hash);
+
+        if (event.shiftKey && match) {
+            event.preventDefault();
+
+            const first = parseInt(match[1]);
+            const clicked = parseInt($(this).text());
+
+            history.replaceState(null, "", `#L${Math.min(first, clicked)}-L${Math.max(first, clicked)}`);
+            highlightLines();
+        }
+    });
+
+    window.addEventListener("hashchange", highlightLines);
+    highlightLines();
+
+    const start = document.querySelector("tr.line.highlighted");
+
+    if (start) {
+        start.scrollIntoView({ block: "center" });
+    }
+}
+
+/**
+ * Scrolls to the element referenced by the location hash in rendered markup.
+ * Ids in rendered markup are prefixed with `user-content-` to not clash with ids of the page itself.
+ */
+function scrollToUserContent() {
+    const hash = decodeURIComponent(window.location.hash.substring(1));
+
+    if (!hash || hash.startsWith("user-content-")) {
+        return;
+    }
+
+    const element = document.getElementById(`user-content-${hash}`);
+
+    if (element) {
+        element.scrollIntoView();
+    }
+}
+
 function displayHtmxError(event) {
     sendNotification("error", "Error occurred while sending request");
     console.error(event);